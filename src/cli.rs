@@ -0,0 +1,399 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::{Config, CONFIG_KEYS},
+    engine::{SearchEngine, SearchOptions},
+    error::Error,
+    evaluation::{benchmark::percentile, query_extractor::extract_queries},
+    preprocessing::persistence::INDEX_FILE_NAME,
+    querying::batch::{parse_batch, run_batch, BatchResult},
+    server::{search_results_json, serve, ServerConfig},
+    utils::{display_eval_report, display_search_results, json_string},
+};
+
+pub const EXIT_SUCCESS: i32 = 0;
+/// The command ran but failed, e.g. a missing file or a MAP below `--min-map`.
+pub const EXIT_FAILURE: i32 = 1;
+/// The command line itself was invalid.
+pub const EXIT_USAGE: i32 = 2;
+
+const DEFAULT_TOP: usize = 10;
+const DEFAULT_RUN_TAG: &str = "mini-retrieve";
+const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_SERVER_THREADS: usize = 4;
+
+const USAGE: &str = "Usage:
+  mini-retrieve                          Start the interactive prompt
+  mini-retrieve index  [--corpus <file>] [--out <dir>]
+  mini-retrieve search [--index <dir>] [--top <n>] [--format text|json] <query>...
+  mini-retrieve eval   [--index <dir>] [--queries <file>] [--qrels <file>] [--min-map <x>]
+  mini-retrieve batch  [--index <dir>] [--input <file>|-] [--top <n>] [--threads <n>]
+                       [--format tsv|jsonl|trec] [--tag <run tag>]
+  mini-retrieve serve  [--index <dir>] [--port <port>] [--threads <n>] [--queries <file>] [--qrels <file>]
+  mini-retrieve help
+
+Every command also takes --config <file> (default: $MINI_RETRIEVE_CONFIG, then
+mini-retrieve.conf) and overrides of its keys: --corpus, --queries, --qrels,
+--index, --stopwords, --analyzer, --model, --k1 and --b. Environment variables
+MINI_RETRIEVE_<KEY> override the file, command line options override both.
+
+Exit codes: 0 success, 1 failure (missing files, MAP below --min-map), 2 invalid usage.";
+
+/// Outcome of a subcommand that did not succeed.
+enum CliError {
+    Usage(String),
+    Failure(String),
+}
+
+impl From<Error> for CliError {
+    fn from(e: Error) -> Self {
+        CliError::Failure(e.to_string())
+    }
+}
+
+/// `--name value` / `--name=value` options and the remaining positional
+/// arguments of a subcommand.
+struct Options {
+    values: HashMap<String, String>,
+    positional: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String], allowed: &[&str]) -> Result<Self, CliError> {
+        let mut values = HashMap::new();
+        let mut positional = Vec::new();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let Some(option) = arg.strip_prefix("--") else {
+                positional.push(arg.clone());
+                continue;
+            };
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| CliError::Usage(format!("--{} needs a value", option)))?;
+                    (option.to_string(), value.clone())
+                }
+            };
+            if !allowed.contains(&name.as_str()) && name != "config" && !CONFIG_KEYS.contains(&name.as_str()) {
+                return Err(CliError::Usage(format!("unknown option --{}", name)));
+            }
+            values.insert(name, value);
+        }
+
+        Ok(Options { values, positional })
+    }
+
+    fn get_or<'a>(&'a self, name: &str, default: &'a str) -> &'a str {
+        self.values.get(name).map(String::as_str).unwrap_or(default)
+    }
+
+    fn parsed<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>, CliError> {
+        self.values
+            .get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_e| CliError::Usage(format!("invalid value '{}' for --{}", value, name)))
+            })
+            .transpose()
+    }
+}
+
+/// Runs the subcommand in `args` (without the program name) and returns the
+/// process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => ("help", args),
+    };
+
+    let result = match command {
+        "index" => index(rest),
+        "search" => search(rest),
+        "eval" => eval(rest),
+        "batch" => batch(rest),
+        "serve" => serve_index(rest),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(CliError::Usage(format!("unknown command '{}'", command))),
+    };
+
+    match result {
+        Ok(()) => EXIT_SUCCESS,
+        Err(CliError::Usage(message)) => {
+            eprintln!("ERROR: {}", message);
+            eprintln!("{}", USAGE);
+            EXIT_USAGE
+        }
+        Err(CliError::Failure(message)) => {
+            eprintln!("ERROR: {}", message);
+            EXIT_FAILURE
+        }
+    }
+}
+
+fn index(args: &[String]) -> Result<(), CliError> {
+    let options = Options::parse(args, &["out"])?;
+    reject_positional(&options)?;
+    let mut config = load_config(&options)?;
+    if let Some(out) = options.values.get("out") {
+        config.index_dir = PathBuf::from(out);
+    }
+    let out = config.index_dir.as_path();
+
+    println!("> Indexing corpus at path: {}", config.corpus.display());
+    let engine = SearchEngine::build(&config, None)?;
+    engine
+        .save(out)
+        .map_err(|e| CliError::Failure(format!("could not write index to '{}': {}", out.display(), e)))?;
+
+    let stats = engine.stats();
+    println!(
+        "> Stored index of {} documents with {} terms in {}",
+        stats.documents,
+        stats.terms,
+        out.join(INDEX_FILE_NAME).display()
+    );
+    Ok(())
+}
+
+fn search(args: &[String]) -> Result<(), CliError> {
+    let options = Options::parse(args, &["top", "format"])?;
+    let text = options.positional.join(" ");
+    if text.trim().is_empty() {
+        return Err(CliError::Usage("search needs a query".to_string()));
+    }
+    let top = options.parsed::<usize>("top")?.unwrap_or(DEFAULT_TOP);
+    let format = options.get_or("format", "text");
+    if format != "text" && format != "json" {
+        return Err(CliError::Usage(format!("unknown format '{}'", format)));
+    }
+
+    let engine = open_index(&load_config(&options)?)?;
+    let search_options = SearchOptions {
+        k: top,
+        ..SearchOptions::default()
+    };
+    let results = engine.search(&text, &search_options);
+
+    if format == "json" {
+        println!("{}", search_results_json(&text, &results));
+    } else {
+        display_search_results(&results, false);
+    }
+    Ok(())
+}
+
+fn eval(args: &[String]) -> Result<(), CliError> {
+    let options = Options::parse(args, &["min-map"])?;
+    reject_positional(&options)?;
+    let min_map = options.parsed::<f32>("min-map")?;
+    let config = load_config(&options)?;
+
+    let queries = extract_queries(&config.queries)?;
+    let engine = open_index(&config)?;
+    let report = engine.evaluate(&queries, &config.qrels, None, None, None)?;
+    display_eval_report(&report);
+
+    let map = report.map;
+    match min_map {
+        Some(min_map) if map < min_map => Err(CliError::Failure(format!(
+            "MAP {} is below the required {}",
+            map, min_map
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Runs the queries of a file (or stdin) and writes their rankings to
+/// stdout; latency statistics go to stderr so they stay out of the run.
+fn batch(args: &[String]) -> Result<(), CliError> {
+    let options = Options::parse(args, &["input", "top", "threads", "format", "tag"])?;
+    reject_positional(&options)?;
+    let top = options.parsed::<usize>("top")?.unwrap_or(DEFAULT_TOP);
+    let threads = options.parsed::<usize>("threads")?.unwrap_or(1).max(1);
+    let format = options.get_or("format", "tsv");
+    if !["tsv", "jsonl", "trec"].contains(&format) {
+        return Err(CliError::Usage(format!("unknown format '{}'", format)));
+    }
+    let tag = options.get_or("tag", DEFAULT_RUN_TAG);
+
+    let input = match options.get_or("input", "-") {
+        "-" => {
+            let mut input = String::new();
+            io::stdin()
+                .read_to_string(&mut input)
+                .map_err(|e| CliError::Failure(format!("could not read stdin: {}", e)))?;
+            input
+        }
+        path => fs::read_to_string(path)
+            .map_err(|e| CliError::Failure(format!("could not read '{}': {}", path, e)))?,
+    };
+    let queries = parse_batch(&input);
+
+    let engine = open_index(&load_config(&options)?)?;
+    let start_time = Instant::now();
+    let results = run_batch(&queries, engine.index(), top, threads);
+    let wall_time = start_time.elapsed();
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    write_batch_results(&mut out, &results, format, tag)
+        .and_then(|()| out.flush())
+        .map_err(|e| CliError::Failure(format!("could not write results: {}", e)))?;
+
+    let mut latencies: Vec<Duration> = results.iter().map(|result| result.latency).collect();
+    latencies.sort();
+    let total: Duration = latencies.iter().sum();
+    eprintln!("⏱️ {} queries on {} thread(s) in {:?}", results.len(), threads, wall_time);
+    eprintln!(
+        "   Mean latency: {:?}",
+        total.checked_div(latencies.len().max(1) as u32).unwrap_or_default()
+    );
+    eprintln!("   Median:       {:?}", percentile(&latencies, 0.5));
+    eprintln!("   95th pct.:    {:?}", percentile(&latencies, 0.95));
+    eprintln!("   Max latency:  {:?}", latencies.last().copied().unwrap_or_default());
+    eprintln!(
+        "   Throughput:   {:.1} queries/s",
+        results.len() as f64 / wall_time.as_secs_f64().max(f64::EPSILON)
+    );
+
+    Ok(())
+}
+
+/// Serves `/search`, `/doc/{id}`, `/stats` and `/eval` on localhost until
+/// the process is stopped.
+fn serve_index(args: &[String]) -> Result<(), CliError> {
+    let options = Options::parse(args, &["port", "threads"])?;
+    reject_positional(&options)?;
+    let config = load_config(&options)?;
+    let server_config = ServerConfig {
+        port: options.parsed::<u16>("port")?.unwrap_or(DEFAULT_PORT),
+        threads: options.parsed::<usize>("threads")?.unwrap_or(DEFAULT_SERVER_THREADS),
+        queries_path: config.queries.clone(),
+        qrels_path: config.qrels.clone(),
+    };
+
+    let engine = open_index(&config)?;
+    serve(engine, server_config)
+        .map_err(|e| CliError::Failure(format!("could not start the server: {}", e)))
+}
+
+fn write_batch_results(
+    out: &mut impl Write,
+    results: &[BatchResult],
+    format: &str,
+    tag: &str,
+) -> io::Result<()> {
+    for result in results {
+        let ranked = result.ranked_results.iter().enumerate();
+        match format {
+            "jsonl" => {
+                let hits: Vec<String> = ranked
+                    .map(|(rank, (doc_id, score))| {
+                        format!("{{\"rank\":{},\"doc_id\":{},\"score\":{}}}", rank + 1, doc_id, score)
+                    })
+                    .collect();
+                writeln!(
+                    out,
+                    "{{\"qid\":{},\"query\":{},\"latency_us\":{},\"results\":[{}]}}",
+                    json_string(&result.qid),
+                    json_string(&result.text),
+                    result.latency.as_micros(),
+                    hits.join(",")
+                )?;
+            }
+            "trec" => {
+                for (rank, (doc_id, score)) in ranked {
+                    writeln!(out, "{} Q0 {} {} {} {}", result.qid, doc_id, rank + 1, score, tag)?;
+                }
+            }
+            _ => {
+                for (rank, (doc_id, score)) in ranked {
+                    writeln!(out, "{}\t{}\t{}\t{}", result.qid, rank + 1, doc_id, score)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Loads the config file and environment overrides and applies the config
+/// keys given as options.
+fn load_config(options: &Options) -> Result<Config, CliError> {
+    let mut config = Config::load(options.values.get("config").map(Path::new))?;
+    for key in CONFIG_KEYS {
+        if let Some(value) = options.values.get(key) {
+            config
+                .set(key, value)
+                .map_err(|e| CliError::Usage(format!("--{}: {}", key, e)))?;
+        }
+    }
+    Ok(config)
+}
+
+fn open_index(config: &Config) -> Result<SearchEngine, CliError> {
+    let index_dir = config.index_dir.display();
+    SearchEngine::open(config).map_err(|e| {
+        CliError::Failure(format!(
+            "could not load index from '{}' ({}); build it with 'mini-retrieve index --out {}'",
+            index_dir, e, index_dir
+        ))
+    })
+}
+
+fn reject_positional(options: &Options) -> Result<(), CliError> {
+    match options.positional.first() {
+        Some(arg) => Err(CliError::Usage(format!("unexpected argument '{}'", arg))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::querying::score::Bm25Params;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn exit_codes_tell_usage_errors_from_failures() {
+        let missing = std::env::temp_dir().join(format!("mini-retrieve-no-index-{}", std::process::id()));
+        let missing = missing.display();
+
+        assert_eq!(run(&args("help")), EXIT_SUCCESS);
+        assert_eq!(run(&args("frobnicate")), EXIT_USAGE);
+        assert_eq!(run(&args("search")), EXIT_USAGE);
+        assert_eq!(run(&args("search --top many flow")), EXIT_USAGE);
+        assert_eq!(run(&args("search --colour red flow")), EXIT_USAGE);
+        assert_eq!(run(&args("eval --min-map")), EXIT_USAGE);
+        assert_eq!(run(&args(&format!("search --index {} flow", missing))), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn command_line_options_override_the_config_file() {
+        let path = std::env::temp_dir().join(format!("mini-retrieve-cli-{}.conf", std::process::id()));
+        fs::write(&path, "k1 = 1.5\nb = 0.5\nindex = from-file\n").unwrap();
+
+        let options = Options::parse(&args(&format!("--config {} --k1=3 --index from-cli", path.display())), &[]);
+        let config = load_config(&options.ok().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let config = config.ok().unwrap();
+        assert_eq!(config.bm25, Bm25Params { k1: 3.0, b: 0.5 });
+        assert_eq!(config.index_dir, PathBuf::from("from-cli"));
+    }
+}
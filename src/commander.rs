@@ -1,22 +1,101 @@
 use crate::{
-    evaluation::{precision_calculator::mean_average_precision, query_extractor::extract_queries},
-    preprocessing::{indexer, splitter, tokenizer::tokenize},
-    querying::score::idf,
-    types::InvertedIndex,
+    config::Config,
+    engine::{SearchEngine, SearchOptions},
+    federated::Normalization,
+    evaluation::{
+        benchmark::{run_benchmark, BenchmarkScorer},
+        ltr_training::{train_ranker, TrainingReport, DEFAULT_FOLDS},
+        precision_calculator::{mean_average_precision, merge_judgments},
+        query_extractor::extract_queries,
+    },
+    preprocessing::{
+        pruning::{prune_index, PruningStrategy},
+        tokenizer::Analyzer,
+    },
+    querying::{
+        feedback::{rocchio, FeedbackModel, FeedbackParams, RocchioWeights},
+        fusion::{FusionMethod, FusionParams, Run},
+        features::FEATURE_NAMES,
+        ltr::{LinearModel, DEFAULT_CANDIDATES, DEFAULT_MODEL_PATH},
+        query::Query,
+        score::{idf, ScoringModel},
+    },
+    types::{Collection, DocId, InvertedIndex, Session, DEFAULT_COLLECTION},
+    utils::{
+        display_error, display_eval_report, display_expansions, display_top_results, display_weighted_query,
+        field_term_frequencies, highlight_line,
+    },
 };
 use std::{cmp::max, fs};
-use std::{collections::HashSet, path::PathBuf};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// Share of postings impact mode processes unless `::reindex impact` is
+/// given one. Not tuned; compare budgets on a collection with `::eval`.
+const DEFAULT_IMPACT_BUDGET: f32 = 0.3;
+const DOC_PAGE_LINES: usize = 25;
+/// Shortfall of the pruned share below the requested ratio that is still
+/// reported as reached.
+const PRUNING_RATIO_TOLERANCE: f32 = 0.005;
+/// Appended to the index directory to get where `::prune` saves to unless
+/// another directory is given.
+const PRUNED_INDEX_SUFFIX: &str = "-pruned";
+const EXPLICIT_FEEDBACK_WEIGHTS: RocchioWeights = RocchioWeights {
+    alpha: 1.0,
+    beta: 0.75,
+    gamma: 0.15,
+};
+const EXPLICIT_FEEDBACK_TERMS: usize = 10;
+const RELEVANT_GRADE: i32 = 1;
+const NONRELEVANT_GRADE: i32 = -1;
+/// Qrels file `::savejudgments` writes to unless another path is given. It is
+/// kept apart from the evaluation qrels so that judging does not change MAP.
+const DEFAULT_JUDGMENTS_PATH: &str = "judgments.qrel";
+/// Options of `::eval` that configure fusion rather than feedback.
+const FUSION_OPTIONS: [&str; 5] = ["fusion", "runs", "depth", "rrf_k", "mu"];
 
-pub fn commander(command: String, args: Vec<&str>, inverted_index: &mut InvertedIndex) -> bool {
+pub fn commander(command: String, args: Vec<&str>, session: &mut Session) -> bool {
+    let name = session.active.clone();
+    let Collection {
+        config,
+        engine,
+        from_corpus,
+    } = session.collection_mut();
     match command.as_str() {
         "exit" => exit(),
-        "reindex" => index_build(inverted_index),
-        "stats" => stats(inverted_index),
-        "postings" => postings(args, inverted_index),
-        "commons" => commons(inverted_index, args),
-        "doc" => read_doc(args),
-        "eval" => eval_queries(inverted_index, args),
-        "tokenize" => print_tokenized(args),
+        "reindex" if !*from_corpus => {
+            println!("⚠️ '{}' was loaded from a stored index, rebuild it with 'mini-retrieve index'.", name);
+            true
+        }
+        "reindex" => index_build(engine, config, args),
+        "stats" => stats(&name, engine),
+        "postings" => postings(args, engine.index()),
+        "commons" => commons(engine.index(), args),
+        "doc" => read_doc(session, args),
+        "eval" => eval_queries(engine, config, args),
+        "bench" => bench(engine.index(), config, args),
+        "and" => search_conjunctive(engine, args),
+        "phrase" => search_phrase(engine, args),
+        "prune" => prune(engine, config, *from_corpus, args),
+        "autocorrect" => autocorrect(session, args),
+        "feedback" => feedback(session, args),
+        "fusion" => fusion(session, args),
+        "ltr" => ltr(session, args),
+        "relevant" => judge(session, args, true),
+        "nonrelevant" => judge(session, args, false),
+        "savejudgments" => save_judgments(session, args),
+        "similar" => similar(engine, args),
+        "termvector" => term_vector(engine.index(), args),
+        "explain" => explain_score(engine, args),
+        "analyze" => analyze(engine, config, args),
+        "tokenize" => print_tokenized(args, &engine.index().analyzer),
+        "config" => print_config(config),
+        "open" => open_collection(session, args),
+        "use" => use_collection(session, args),
+        "indexes" => list_collections(session),
+        "federated" => federated(session, args),
         _ => print_help(),
     }
 }
@@ -26,27 +105,62 @@ fn exit() -> bool {
     false
 }
 
-fn index_build(inverted_index: &mut InvertedIndex) -> bool {
-    let doc_path = "in/documents.all";
-    println!("");
-    splitter::split_documents(doc_path);
-    println!("");
-    *inverted_index = indexer::create_inverted_index();
+fn index_build(engine: &mut SearchEngine, config: &Config, args: Vec<&str>) -> bool {
+    let impact_mode = args.first() == Some(&"impact");
+    let budget = match args.get(1).map(|arg| arg.parse::<f32>()) {
+        None => DEFAULT_IMPACT_BUDGET,
+        Some(Ok(budget)) if budget > 0.0 && budget <= 1.0 => budget,
+        Some(_) => {
+            println!("⚠️ The impact budget must be a share of postings in (0, 1], e.g. ::reindex impact 0.3");
+            return true;
+        }
+    };
+
+    println!();
+    println!("> Indexing corpus at path: {}", config.corpus.display());
+    if impact_mode {
+        println!("  > Precomputing quantized impacts ({:.0}% postings budget)", budget * 100.0);
+    }
+    match SearchEngine::build(config, impact_mode.then_some(budget)) {
+        Ok(built) => *engine = built,
+        Err(e) => {
+            display_error(&e);
+            return true;
+        }
+    }
+    let stats = engine.stats();
+    println!("  > Indexed {} documents with {} terms", stats.documents, stats.terms);
+    if let Err(e) = engine.save(&config.index_dir) {
+        display_error(&e);
+        return true;
+    }
+    println!("  > Saved index to {}", config.index_dir.display());
 
-    println!("");
+    println!();
     println!("> Preparations are done");
-    println!("");
+    println!();
     println!("🤖 Mini Retrieve is ready to serve your queries!");
     println!("Type '::help' for available commands.");
 
     true
 }
 
-fn stats(inverted_index: &InvertedIndex) -> bool {
-    println!("📊 Inverted Index Statistics:");
-    println!("   Total Documents: {}", inverted_index.n);
-    println!("   Total Terms: {}", inverted_index.dictionary.len());
-    println!("   Average Document Length: {:.2}", inverted_index.avdl);
+fn stats(name: &str, engine: &SearchEngine) -> bool {
+    let stats = engine.stats();
+    println!("📊 Inverted Index Statistics of '{}':", name);
+    println!("   Total Documents: {}", stats.documents);
+    println!("   Total Terms: {}", stats.terms);
+    println!("   Average Document Length: {:.2}", stats.avdl);
+    match (engine.index().model, stats.impact_budget) {
+        (ScoringModel::LmDirichlet { mu }, _) => {
+            println!("   Query Processing: exhaustive LM-Dirichlet (mu = {})", mu)
+        }
+        (ScoringModel::Bm25, Some(budget)) => println!(
+            "   Query Processing: impact-ordered, {:.0}% postings budget",
+            budget * 100.0
+        ),
+        (ScoringModel::Bm25, None) => println!("   Query Processing: exhaustive BM25"),
+    }
 
     true
 }
@@ -57,7 +171,7 @@ fn postings(args: Vec<&str>, inverted_index: &InvertedIndex) -> bool {
     } else {
         let term = args[0];
         println!("\n🔤 Postings for Term: \"{}\"", term);
-        let Some(token) = tokenize(term) else {
+        let Some(token) = inverted_index.analyzer.analyze(term) else {
             println!("⚠️ You have entered a stopword. That doesn't have any postings.");
             return true;
         };
@@ -72,14 +186,10 @@ fn postings(args: Vec<&str>, inverted_index: &InvertedIndex) -> bool {
             println!("+--------+----------------------------------------------------+-------+");
 
             for posting in postings {
-                let doc_id = posting.doc_id;
+                let doc_id = inverted_index.doc_id(posting.doc_idx);
                 let tf = posting.tf;
 
-                let title = inverted_index
-                    .doc_titles
-                    .get(&doc_id)
-                    .map(|s| s.as_str())
-                    .unwrap_or("!TITLE NOT FOUND!");
+                let title = inverted_index.title(posting.doc_idx);
 
                 let display_title = if title.len() > 50 {
                     format!("{:.47}...", title) // Truncate and add ellipsis
@@ -101,17 +211,17 @@ fn postings(args: Vec<&str>, inverted_index: &InvertedIndex) -> bool {
 }
 
 fn commons(inverted_index: &InvertedIndex, args: Vec<&str>) -> bool {
-    let mut count: u32 = 10;
-    if !args.is_empty() {
-        count = args[0].parse::<u32>().unwrap_or(10);
-    }
-    let mut term_stats: Vec<(&String, usize)> = inverted_index
+    let count: u32 = args
+        .first()
+        .and_then(|arg| arg.parse::<u32>().ok())
+        .unwrap_or(10);
+    let mut term_stats: Vec<(&String, u32)> = inverted_index
         .dictionary
         .iter()
-        .map(|(term, postings)| (term, postings.len()))
+        .map(|(term, postings)| (term, postings.df()))
         .collect();
 
-    term_stats.sort_by(|a, b| b.1.cmp(&a.1));
+    term_stats.sort_by_key(|(_term, df)| std::cmp::Reverse(*df));
 
     let n = inverted_index.n;
 
@@ -128,7 +238,7 @@ fn commons(inverted_index: &InvertedIndex, args: Vec<&str>) -> bool {
     println!("+-------------------------+--------------------+----------+");
 
     for (term, df) in term_stats.iter().take(count as usize) {
-        let idf_score = idf(*df as u32, n);
+        let idf_score = idf(*df, n);
         let display_idf = format!("{:.4}", idf_score);
 
         println!("| {:<23} | {:<18} | {:<8} |", term, df, display_idf);
@@ -145,16 +255,25 @@ fn commons(inverted_index: &InvertedIndex, args: Vec<&str>) -> bool {
     true
 }
 
-fn read_doc(args: Vec<&str>) -> bool {
+fn read_doc(session: &Session, args: Vec<&str>) -> bool {
     if args.is_empty() {
-        println!("⚠️ Usage: ::doc <ID>");
+        println!("⚠️ Usage: ::doc <ID> [query]");
         return true;
     }
 
     let doc_id_str = args[0];
+    let inverted_index = session.collection().engine.index();
+    let query = if args.len() > 1 {
+        Some(Query::parse(&args[1..].join(" "), inverted_index))
+    } else {
+        session.last_query.as_ref().map(|last_query| last_query.query.clone())
+    };
+    let query_tokens: HashSet<&str> = query
+        .as_ref()
+        .map(|query| query.tokens().collect())
+        .unwrap_or_default();
 
-    let mut doc_path = PathBuf::from("out/documents");
-    doc_path.push(format!("doc{}", doc_id_str));
+    let doc_path = inverted_index.documents_dir.join(format!("doc{}", doc_id_str));
 
     println!(
         "📚 Attempting to read document from: '{}'",
@@ -174,13 +293,27 @@ fn read_doc(args: Vec<&str>) -> bool {
             let border_line = format!("+{}+", "-".repeat(inner_width));
             let footer_line = format!("+{}+", "-".repeat(inner_width));
 
+            let lines: Vec<&str> = content.lines().collect();
+            let pages = lines.len().div_ceil(DOC_PAGE_LINES).max(1);
+
             println!("\n{}", border_line);
             println!("| {:<width$} |", header_text, width = inner_width - 3);
             println!("{}", border_line);
-            for line in content.lines() {
-                println!("| {:<width$} |", line, width = inner_width - 2);
+            for (page, page_lines) in lines.chunks(DOC_PAGE_LINES).enumerate() {
+                for line in page_lines {
+                    let (highlighted, visible_width) = highlight_line(line, &query_tokens, &inverted_index.analyzer);
+                    let padding = (inner_width - 2).saturating_sub(visible_width);
+                    println!("| {}{} |", highlighted, " ".repeat(padding));
+                }
+                if page + 1 < pages && !prompt_next_page(page + 1, pages) {
+                    break;
+                }
             }
             println!("{}\n", footer_line);
+
+            if let Some(query) = query.as_ref().filter(|query| !query.is_empty()) {
+                display_field_frequencies(&content, query, &inverted_index.analyzer);
+            }
         }
         Err(e) => {
             println!("\n❌ Error reading document {}:", doc_id_str);
@@ -190,10 +323,101 @@ fn read_doc(args: Vec<&str>) -> bool {
     true
 }
 
-fn eval_queries(inverted_index: &InvertedIndex, args: Vec<&str>) -> bool {
+/// Asks whether to show the next page of a document. Returns `false` when
+/// the reader wants to stop.
+fn prompt_next_page(page: usize, pages: usize) -> bool {
+    println!("-- page {}/{}: press Enter for more, 'q' to stop --", page, pages);
+    let mut line = String::new();
+    match std::io::stdin().read_line(&mut line) {
+        Ok(0) | Err(_) => false,
+        Ok(_) => !line.trim().eq_ignore_ascii_case("q"),
+    }
+}
+
+/// Prints how often each query term occurs in every field of the document.
+fn display_field_frequencies(content: &str, query: &Query, analyzer: &Analyzer) {
+    let field_tfs = field_term_frequencies(content, analyzer);
+
+    println!("🔎 Matched query terms per field:");
+    println!("+-------------------------+-------+--------+--------+-------+");
+    println!(
+        "| {:<23} | {:<5} | {:<6} | {:<6} | {:<5} |",
+        "Term (Stem)", "Title", "Author", "Biblio", "Body"
+    );
+    println!("+-------------------------+-------+--------+--------+-------+");
+    for token in query.tokens() {
+        let tf = |field: char| {
+            field_tfs
+                .get(&field)
+                .and_then(|tfs| tfs.get(token))
+                .copied()
+                .unwrap_or(0)
+        };
+        println!(
+            "| {:<23} | {:<5} | {:<6} | {:<6} | {:<5} |",
+            token,
+            tf('T'),
+            tf('A'),
+            tf('B'),
+            tf('W')
+        );
+    }
+    println!("+-------------------------+-------+--------+--------+-------+");
+}
+
+fn eval_queries(engine: &SearchEngine, config: &Config, args: Vec<&str>) -> bool {
+    let (options, args): (Vec<&str>, Vec<&str>) = args.into_iter().partition(|arg| arg.contains('='));
+    let (model_options, options): (Vec<&str>, Vec<&str>) =
+        options.into_iter().partition(|option| option.starts_with("ltr="));
+    let reranker = model_options
+        .last()
+        .map(|option| LinearModel::load(Path::new(&option["ltr=".len()..])))
+        .transpose();
+    let reranker = match reranker {
+        Ok(reranker) => reranker,
+        Err(e) => {
+            display_error(&e);
+            return true;
+        }
+    };
+    let (fusion_options, feedback_options): (Vec<&str>, Vec<&str>) = options.into_iter().partition(|option| {
+        let key = option.split_once('=').map_or(*option, |(key, _value)| key);
+        FUSION_OPTIONS.contains(&key)
+    });
+    let parsed = parse_feedback_options(&feedback_options, None)
+        .and_then(|feedback| Ok((feedback, parse_fusion_options(&fusion_options, None)?)));
+    let (feedback, fusion) = match parsed {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            println!("⚠️ {}", message);
+            return true;
+        }
+    };
+
     println!("🔬 Running evaluation...");
-    let mut queries = extract_queries("in/documents.qry");
-    if args.len() > 0 {
+    if let Some(params) = &feedback {
+        println!("ℹ️ Using pseudo-relevance feedback: {:?}", params);
+    }
+    if let Some(params) = &fusion {
+        println!("ℹ️ Using rank fusion: {}", describe_fusion(params));
+    }
+    if fusion.is_some() && reranker.is_some() {
+        println!("⚠️ ltr= cannot be combined with fusion: models rerank the first-stage ranking they were trained on.");
+        return true;
+    }
+    if let Some(model) = &reranker {
+        println!("ℹ️ Reranking the top {} documents with the learned model.", model.candidates);
+        println!("⚠️ If the model was trained on these queries, this MAP is measured on its training data;");
+        println!("   the cross-validated MAP in the table of ::ltr train estimates unseen queries.");
+    }
+    let mut queries = match extract_queries(&config.queries) {
+        Ok(queries) => queries,
+        Err(e) => {
+            display_error(&e);
+            return true;
+        }
+    };
+    if !args.is_empty() {
         let qids: HashSet<u32> = args
             .iter()
             .filter_map(|s| s.parse::<u32>().ok())
@@ -206,27 +430,969 @@ fn eval_queries(inverted_index: &InvertedIndex, args: Vec<&str>) -> bool {
             qids.iter().collect::<Vec<&u32>>()
         );
 
-        queries = queries
-            .into_iter()
-            .filter(|(query_id, _query_string)| qids.contains(query_id))
-            .collect();
+        queries.retain(|query_id, _query_string| qids.contains(query_id));
     } else {
         println!("ℹ️ Evaluating all queries as no specific IDs were provided.");
     }
-    let map = mean_average_precision(queries, inverted_index);
-    println!("\n🦀 The MAP was calculated to be: {}", map);
+    match engine.evaluate(
+        &queries,
+        &config.qrels,
+        feedback.as_ref(),
+        fusion.as_ref(),
+        reranker.as_ref(),
+    ) {
+        Ok(report) => display_eval_report(&report),
+        Err(e) => display_error(&e),
+    }
+
+    true
+}
+
+fn feedback(session: &mut Session, args: Vec<&str>) -> bool {
+    let model = match args.first().copied() {
+        Some("off") => {
+            session.feedback = None;
+            println!("🔁 Pseudo-relevance feedback disabled.");
+            return true;
+        }
+        Some("rm3") => FeedbackModel::Rm3,
+        Some("rocchio") => FeedbackModel::Rocchio,
+        _ => {
+            println!("⚠️ Usage: ::feedback <rm3|rocchio|off> [fb_docs=N] [fb_terms=N] [orig=W]");
+            return true;
+        }
+    };
+
+    match parse_feedback_options(&args[1..], Some(model)) {
+        Ok(params) => {
+            session.feedback = params;
+            println!("🔁 Pseudo-relevance feedback enabled: {:?}", params.unwrap_or_default());
+        }
+        Err(message) => println!("⚠️ {}", message),
+    }
+    true
+}
+
+/// Reads `fb=<rm3|rocchio>`, `fb_docs=N`, `fb_terms=N` and `orig=W` options.
+/// Feedback is enabled as soon as a model is given, either as `fb=` option
+/// or as `model`.
+fn parse_feedback_options(
+    options: &[&str],
+    model: Option<FeedbackModel>,
+) -> Result<Option<FeedbackParams>, String> {
+    let mut params = FeedbackParams::default();
+    let mut enabled = model.is_some();
+    if let Some(model) = model {
+        params.model = model;
+    }
+
+    for option in options {
+        let Some((key, value)) = option.split_once('=') else {
+            return Err(format!("Expected key=value, got '{}'.", option));
+        };
+        let invalid = || format!("Invalid value '{}' for option '{}'.", value, key);
+        match key {
+            "fb" => {
+                enabled = true;
+                params.model = match value {
+                    "rm3" => FeedbackModel::Rm3,
+                    "rocchio" => FeedbackModel::Rocchio,
+                    _ => return Err(invalid()),
+                };
+            }
+            "fb_docs" => params.fb_docs = value.parse().map_err(|_| invalid())?,
+            "fb_terms" => params.fb_terms = value.parse().map_err(|_| invalid())?,
+            "orig" => {
+                params.original_weight = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|weight| (0.0..=1.0).contains(weight))
+                    .ok_or_else(invalid)?
+            }
+            _ => return Err(format!("Unknown option '{}'.", key)),
+        }
+    }
+
+    Ok(enabled.then_some(params))
+}
+
+fn fusion(session: &mut Session, args: Vec<&str>) -> bool {
+    let method = match args.first().copied() {
+        Some("off") => {
+            session.fusion = None;
+            println!("🔀 Rank fusion disabled, queries are ranked with BM25.");
+            return true;
+        }
+        Some(name) => FusionMethod::parse(name),
+        None => None,
+    };
+    let Some(method) = method else {
+        println!("⚠️ Usage: ::fusion <rrf|combsum|combmnz|off> [runs=bm25,title,body,lm] [depth=N] [rrf_k=K] [mu=M]");
+        return true;
+    };
+
+    if session.reranker.is_some() {
+        println!("⚠️ Disable the learned reranker with ::ltr off first: it reranks the ranking it was trained on.");
+        return true;
+    }
+
+    match parse_fusion_options(&args[1..], Some(method)) {
+        Ok(params) => {
+            session.fusion = params;
+            if let Some(params) = &session.fusion {
+                println!("🔀 Rank fusion enabled: {}", describe_fusion(params));
+            }
+        }
+        Err(message) => println!("⚠️ {}", message),
+    }
+    true
+}
+
+/// Reads `fusion=<rrf|combsum|combmnz>`, `runs=<run,...>`, `depth=N`,
+/// `rrf_k=K` and `mu=M` options. Like `parse_feedback_options`, fusion is
+/// enabled as soon as a method is given.
+fn parse_fusion_options(
+    options: &[&str],
+    method: Option<FusionMethod>,
+) -> Result<Option<FusionParams>, String> {
+    let mut params = FusionParams::default();
+    let mut enabled = method.is_some();
+    if let Some(method) = method {
+        params.method = method;
+    }
+
+    for option in options {
+        let Some((key, value)) = option.split_once('=') else {
+            return Err(format!("Expected key=value, got '{}'.", option));
+        };
+        let invalid = || format!("Invalid value '{}' for option '{}'.", value, key);
+        match key {
+            "fusion" => {
+                enabled = true;
+                params.method = FusionMethod::parse(value).ok_or_else(invalid)?;
+            }
+            "runs" => {
+                params.runs = value
+                    .split(',')
+                    .map(Run::parse)
+                    .collect::<Option<Vec<Run>>>()
+                    .filter(|runs| !runs.is_empty())
+                    .ok_or_else(invalid)?
+            }
+            "depth" => {
+                params.depth = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|depth| *depth > 0)
+                    .ok_or_else(invalid)?
+            }
+            "rrf_k" => {
+                params.rrf_k = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|rrf_k| *rrf_k >= 0.0)
+                    .ok_or_else(invalid)?
+            }
+            "mu" => {
+                params.mu = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|mu| *mu > 0.0)
+                    .ok_or_else(invalid)?
+            }
+            _ => return Err(format!("Unknown option '{}'.", key)),
+        }
+    }
+
+    Ok(enabled.then_some(params))
+}
+
+fn describe_fusion(params: &FusionParams) -> String {
+    let runs: Vec<String> = params.runs.iter().map(Run::to_string).collect();
+    format!(
+        "{:?} over {} (depth {}, rrf_k {}, mu {})",
+        params.method,
+        runs.join(", "),
+        params.depth,
+        params.rrf_k,
+        params.mu
+    )
+}
+
+fn ltr(session: &mut Session, args: Vec<&str>) -> bool {
+    if session.fusion.is_some() && matches!(args.first().copied(), Some("train" | "load")) {
+        println!("⚠️ Disable rank fusion with ::fusion off first: rerankers are trained on single-model candidates.");
+        return true;
+    }
+    match (args.first().copied(), args.get(1)) {
+        (Some("train"), _) => return train_ltr(session, &args[1..]),
+        (Some("load"), Some(path)) => match LinearModel::load(Path::new(path)) {
+            Ok(model) => {
+                println!("🧠 Reranking the top {} documents of every query with '{}'.", model.candidates, path);
+                display_model_weights(&model);
+                session.reranker = Some(model);
+            }
+            Err(e) => display_error(&e),
+        },
+        (Some("off"), _) => {
+            session.reranker = None;
+            println!("🧠 Learned reranking disabled.");
+        }
+        _ => println!("⚠️ Usage: ::ltr train [path] [folds=N] [candidates=N] | ::ltr load <path> | ::ltr off"),
+    }
+    true
+}
+
+/// Trains a reranker on the test queries and qrels of the selected index,
+/// reports the cross-validation, saves the model and enables it.
+fn train_ltr(session: &mut Session, args: &[&str]) -> bool {
+    let mut path = PathBuf::from(DEFAULT_MODEL_PATH);
+    let mut folds = DEFAULT_FOLDS;
+    let mut candidates = DEFAULT_CANDIDATES;
+    for arg in args {
+        let parsed = match arg.split_once('=') {
+            Some(("folds", value)) => value.parse().map(|value| folds = value).ok(),
+            Some(("candidates", value)) => value
+                .parse()
+                .ok()
+                .filter(|value| *value > 0)
+                .map(|value| candidates = value),
+            Some(_) => None,
+            None => {
+                path = PathBuf::from(arg);
+                Some(())
+            }
+        };
+        if parsed.is_none() {
+            println!("⚠️ Invalid option '{}'. Usage: ::ltr train [path] [folds=N] [candidates=N]", arg);
+            return true;
+        }
+    }
+
+    let Collection { config, engine, .. } = session.collection();
+    let queries = match extract_queries(&config.queries) {
+        Ok(queries) => queries,
+        Err(e) => {
+            display_error(&e);
+            return true;
+        }
+    };
+    println!(
+        "🧠 Training a reranker of the top {} first-stage documents with {}-fold cross-validation...",
+        candidates, folds
+    );
+    let report = match train_ranker(&queries, &config.qrels, engine.index(), candidates, folds) {
+        Ok(report) => report,
+        Err(e) => {
+            display_error(&e);
+            return true;
+        }
+    };
+    display_training_report(&report);
+
+    match report.model.save(&path) {
+        Ok(()) => println!(
+            "💾 Saved the model trained on all {} judged queries to {}",
+            report.queries,
+            path.display()
+        ),
+        Err(e) => display_error(&e),
+    }
+    session.reranker = Some(report.model);
+    println!("🧠 Learned reranking enabled; disable it with ::ltr off.");
+    true
+}
+
+fn display_training_report(report: &TrainingReport) {
+    println!("+------+---------+------------+------------+");
+    println!("| {:^4} | {:^7} | {:^10} | {:^10} |", "Fold", "Queries", "BM25 MAP", "LTR MAP");
+    println!("+------+---------+------------+------------+");
+    for fold in &report.folds {
+        println!(
+            "| {:^4} | {:>7} | {:>10.4} | {:>10.4} |",
+            fold.fold, fold.queries, fold.baseline_map, fold.map
+        );
+    }
+    let folds = report.folds.len().max(1) as f32;
+    let baseline_map: f32 = report.folds.iter().map(|fold| fold.baseline_map).sum::<f32>() / folds;
+    let map: f32 = report.folds.iter().map(|fold| fold.map).sum::<f32>() / folds;
+    println!("+------+---------+------------+------------+");
+    println!("| {:^4} | {:>7} | {:>10.4} | {:>10.4} |", "Mean", report.queries, baseline_map, map);
+    println!("+------+---------+------------+------------+");
+    display_model_weights(&report.model);
+}
+
+fn display_model_weights(model: &LinearModel) {
+    let weights: Vec<String> = FEATURE_NAMES
+        .iter()
+        .zip(model.weights)
+        .map(|(name, weight)| format!("{} {:.3}", name, weight))
+        .collect();
+    println!("⚖️ Weights: {}", weights.join(", "));
+}
+
+fn judge(session: &mut Session, args: Vec<&str>, relevant: bool) -> bool {
+    let Some(last_query) = session.last_query.as_mut() else {
+        println!("⚠️ Run a query first, then judge its results.");
+        return true;
+    };
+    if args.is_empty() {
+        println!("⚠️ Usage: ::relevant <IDs> or ::nonrelevant <IDs>");
+        return true;
+    }
+
+    let engine = &session.collections[&session.active].engine;
+    let inverted_index = engine.index();
+    for arg in args {
+        match arg.parse::<DocId>().ok().filter(|doc_id| inverted_index.doc_idx(*doc_id).is_some()) {
+            Some(doc_id) => last_query.judge(doc_id, relevant),
+            None => println!("⚠️ '{}' is not a document ID of the index, skipped.", arg),
+        }
+    }
+
+    println!(
+        "🧑‍⚖️ Judgments for \"{}\": relevant {:?}, non-relevant {:?}",
+        last_query.text, last_query.relevant, last_query.nonrelevant
+    );
+    if last_query.relevant.is_empty() && last_query.nonrelevant.is_empty() {
+        return true;
+    }
+
+    let reformulated = rocchio(
+        &last_query.query,
+        &last_query.relevant,
+        &last_query.nonrelevant,
+        EXPLICIT_FEEDBACK_WEIGHTS,
+        EXPLICIT_FEEDBACK_TERMS,
+        inverted_index,
+    );
+    let results = engine.search_query(reformulated, &SearchOptions::default());
+
+    display_weighted_query("🔁 Reformulated query", &results.query);
+    display_top_results(&results.hits);
+
+    true
+}
+
+fn save_judgments(session: &Session, args: Vec<&str>) -> bool {
+    let Some(last_query) = &session.last_query else {
+        println!("⚠️ There are no judgments to save yet.");
+        return true;
+    };
+    let Some(qid) = args.first().and_then(|arg| arg.parse::<u32>().ok()) else {
+        println!("⚠️ Usage: ::savejudgments <query ID> [qrels path]");
+        return true;
+    };
+    let qrel_path = args
+        .get(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_JUDGMENTS_PATH));
+
+    let judgments: Vec<(DocId, i32)> = last_query
+        .relevant
+        .iter()
+        .map(|doc_id| (*doc_id, RELEVANT_GRADE))
+        .chain(last_query.nonrelevant.iter().map(|doc_id| (*doc_id, NONRELEVANT_GRADE)))
+        .collect();
+
+    match merge_judgments(&qrel_path, qid, &judgments) {
+        Ok(replaced) => {
+            println!(
+                "💾 Saved {} judgments for query {} to '{}' ({} earlier judgments replaced).",
+                judgments.len(),
+                qid,
+                qrel_path.display(),
+                replaced
+            );
+            if qrel_path == session.collection().config.qrels {
+                println!("⚠️ This is the qrels file ::eval measures against.");
+            }
+        }
+        Err(e) => display_error(&e),
+    }
+    true
+}
+
+fn similar(engine: &SearchEngine, args: Vec<&str>) -> bool {
+    let Some(doc_id) = args.first().and_then(|arg| arg.parse::<DocId>().ok()) else {
+        println!("⚠️ Usage: ::similar <ID> [k]");
+        return true;
+    };
+    let k: usize = args
+        .get(1)
+        .and_then(|arg| arg.parse::<usize>().ok())
+        .unwrap_or(10);
+
+    let Some(document) = engine.document(doc_id) else {
+        println!("⚠️ Document {} is not part of the index.", doc_id);
+        return true;
+    };
+
+    println!("🧭 Documents similar to {}: \"{}\"", doc_id, document.title);
+    match engine.similar(doc_id, k) {
+        Some(results) => {
+            display_weighted_query("🧭 Query terms", &results.query);
+            display_top_results(&results.hits);
+        }
+        None => println!("⚠️ Document {} has no indexed terms to compare.", doc_id),
+    }
+
+    true
+}
+
+fn term_vector(inverted_index: &InvertedIndex, args: Vec<&str>) -> bool {
+    let Some(doc_id) = args.first().and_then(|arg| arg.parse::<DocId>().ok()) else {
+        println!("⚠️ Usage: ::termvector <ID>");
+        return true;
+    };
+    let Some(term_vector) = inverted_index.term_vector(doc_id) else {
+        println!("⚠️ Document {} is not part of the index.", doc_id);
+        return true;
+    };
+
+    println!("\n🧮 Term Vector of Document {}", doc_id);
+    println!("+-------------------------+-------+--------------------------------------------+");
+    println!("| {:<23} | {:<5} | {:<42} |", "Term (Stem)", "TF", "Positions");
+    println!("+-------------------------+-------+--------------------------------------------+");
+    for entry in term_vector {
+        let positions: Vec<String> = entry.positions.iter().map(u32::to_string).collect();
+        let positions = positions.join(", ");
+        let display_positions = if positions.len() > 42 {
+            format!("{:.39}...", positions)
+        } else {
+            positions
+        };
+        println!(
+            "| {:<23} | {:<5} | {:<42} |",
+            inverted_index.vocabulary.term(entry.term_id),
+            entry.tf,
+            display_positions
+        );
+    }
+    println!("+-------------------------+-------+--------------------------------------------+");
 
+    let doc_length: u32 = term_vector.iter().map(|entry| entry.tf).sum();
+    println!(
+        "ℹ️ {} distinct terms, {} tokens.",
+        term_vector.len(),
+        doc_length
+    );
+
+    true
+}
+
+fn analyze(engine: &SearchEngine, config: &Config, args: Vec<&str>) -> bool {
+    let Some(query_id) = args.first().and_then(|arg| arg.parse::<u32>().ok()) else {
+        println!("⚠️ Usage: ::analyze <qid>");
+        return true;
+    };
+    let queries = match extract_queries(&config.queries) {
+        Ok(queries) => queries,
+        Err(e) => {
+            display_error(&e);
+            return true;
+        }
+    };
+    let Some(text) = queries.get(&query_id) else {
+        println!("⚠️ There is no test query with ID {}.", query_id);
+        return true;
+    };
+
+    let analysis = match engine.analyze(query_id, text, &config.qrels) {
+        Ok(analysis) => analysis,
+        Err(e) => {
+            display_error(&e);
+            return true;
+        }
+    };
+    println!("\n🩺 Failure analysis of Query ID {}: \"{}\"", query_id, text);
+    display_weighted_query("🩺 Analysed query", &analysis.query);
+    if analysis.relevant.is_empty() {
+        println!("⚠️ The qrels list no relevant documents for this query.");
+        return true;
+    }
+
+    println!("+----------+---------------+----------+--------------------------------+--------------------------------+");
+    println!(
+        "| {:<8} | {:<13} | {:<8} | {:<30} | {:<30} |",
+        "Doc ID", "Rank", "Score", "Matched Terms", "Missed Terms"
+    );
+    println!("+----------+---------------+----------+--------------------------------+--------------------------------+");
+    for doc in &analysis.relevant {
+        let rank = match doc.rank {
+            Some(rank) => format!("#{}", rank),
+            None => "not retrieved".to_string(),
+        };
+        let score = doc.score.map(|score| format!("{:.4}", score)).unwrap_or_default();
+        println!(
+            "| {:<8} | {:<13} | {:<8} | {:<30} | {:<30} |",
+            doc.doc_id,
+            rank,
+            score,
+            truncate_list(&doc.matched, 30),
+            truncate_list(&doc.missed, 30)
+        );
+    }
+    println!("+----------+---------------+----------+--------------------------------+--------------------------------+");
+
+    let retrieved = analysis.relevant.iter().filter(|doc| doc.rank.is_some()).count();
+    println!(
+        "ℹ️ {} of {} relevant documents retrieved.",
+        retrieved,
+        analysis.relevant.len()
+    );
+
+    if !analysis.hints.is_empty() {
+        println!("💡 Top terms of the relevant documents missing from the query:");
+        for hint in analysis.hints.iter().take(10) {
+            println!(
+                "   {:<23} in {} relevant document(s), tf-idf {:.2}",
+                hint.term, hint.doc_count, hint.weight
+            );
+        }
+    }
+
+    true
+}
+
+fn truncate_list(items: &[String], width: usize) -> String {
+    let list = items.join(", ");
+    if list.chars().count() > width {
+        format!("{}...", list.chars().take(width - 3).collect::<String>())
+    } else {
+        list
+    }
+}
+
+fn explain_score(engine: &SearchEngine, args: Vec<&str>) -> bool {
+    let Some(doc_id) = args.first().and_then(|arg| arg.parse::<DocId>().ok()) else {
+        println!("⚠️ Usage: ::explain <ID> <query>");
+        return true;
+    };
+    let text = args[1..].join(" ");
+    let Some(explanation) = engine.explain(doc_id, &text) else {
+        println!("⚠️ Document {} is not part of the index.", doc_id);
+        return true;
+    };
+
+    println!("\n🔬 BM25 score of Document {} for \"{}\"", doc_id, text);
+    println!(
+        "   Document length: {} (avdl {:.2}, ratio {:.2})",
+        explanation.doc_length,
+        explanation.avdl,
+        explanation.doc_length as f32 / explanation.avdl
+    );
+    println!("+-------------------------+--------+--------+--------+-------+--------+--------------+--------+");
+    println!(
+        "| {:<23} | {:<6} | {:<6} | {:<6} | {:<5} | {:<6} | {:<12} | {:<6} |",
+        "Term (Stem)", "Weight", "DF", "IDF", "TF", "BM25tf", "Contribution", "Share"
+    );
+    println!("+-------------------------+--------+--------+--------+-------+--------+--------------+--------+");
+    for term in &explanation.terms {
+        let share = if explanation.total > 0.0 {
+            term.contribution / explanation.total * 100.0
+        } else {
+            0.0
+        };
+        println!(
+            "| {:<23} | {:<6.2} | {:<6} | {:<6.3} | {:<5} | {:<6.3} | {:<12.4} | {:>5.1}% |",
+            term.token, term.weight, term.df, term.idf, term.tf, term.component, term.contribution, share
+        );
+    }
+    println!("+-------------------------+--------+--------+--------+-------+--------+--------------+--------+");
+    println!("   Total score: {:.4}", explanation.total);
+
+    if !explanation.stopwords.is_empty() {
+        println!("✂️ Dropped as stopwords: {}", explanation.stopwords.join(", "));
+    }
+    if !explanation.missing.is_empty() {
+        println!("❓ Not in the dictionary: {}", explanation.missing.join(", "));
+    }
+    if engine.stats().impact_budget.is_some() {
+        println!("ℹ️ The index is in impact mode; rankings use quantized impacts, not these exact scores.");
+    }
+
+    true
+}
+
+fn bench(inverted_index: &InvertedIndex, config: &Config, args: Vec<&str>) -> bool {
+    let runs: usize = args
+        .first()
+        .and_then(|arg| arg.parse::<usize>().ok())
+        .unwrap_or(5)
+        .max(1);
+
+    println!("🏁 Benchmarking query latency ({} runs)...", runs);
+    let queries = match extract_queries(&config.queries) {
+        Ok(queries) => queries,
+        Err(e) => {
+            display_error(&e);
+            return true;
+        }
+    };
+    let report = run_benchmark(&queries, inverted_index, runs, BenchmarkScorer::Accumulators);
+    let baseline = run_benchmark(&queries, inverted_index, runs, BenchmarkScorer::HashMap);
+    let speedup = |current: std::time::Duration, before: std::time::Duration| {
+        before.as_secs_f64() / current.as_secs_f64().max(f64::EPSILON)
+    };
+
+    println!("   Queries:      {}", report.queries);
+    println!("   Executions:   {}", report.queries * report.runs);
+    println!("                 {:>14} {:>14} {:>8}", "Accumulators", "HashMap", "Speedup");
+    for (label, current, before) in [
+        ("Total time:", report.total, baseline.total),
+        ("Mean latency:", report.mean, baseline.mean),
+        ("Median:", report.median, baseline.median),
+        ("95th pct.:", report.p95, baseline.p95),
+        ("Max latency:", report.max, baseline.max),
+    ] {
+        println!(
+            "   {:<13} {:>14} {:>14} {:>7.2}x",
+            label,
+            format!("{:?}", current),
+            format!("{:?}", before),
+            speedup(current, before)
+        );
+    }
+    if inverted_index.impacts.is_some() {
+        println!("ℹ️ The accumulator column evaluates the quantized impacts of this index.");
+    }
+
+    true
+}
+
+fn search_conjunctive(engine: &SearchEngine, args: Vec<&str>) -> bool {
+    if args.is_empty() {
+        println!("⚠️ Usage: ::and <terms>");
+        return true;
+    }
+
+    println!("⏳ Searching for documents containing all terms...");
+    let options = SearchOptions {
+        conjunctive: true,
+        ..SearchOptions::default()
+    };
+    let query = Query::parse(&args.join(" "), engine.index());
+    let results = engine.search_query(query, &options);
+    display_expansions(&results.query);
+
+    if results.hits.is_empty() {
+        println!("🫙 No document contains all of the terms.");
+    } else {
+        display_top_results(&results.hits);
+    }
+    println!("({:?})", results.took);
+
+    true
+}
+
+fn search_phrase(engine: &SearchEngine, args: Vec<&str>) -> bool {
+    if args.is_empty() {
+        println!("⚠️ Usage: ::phrase <words>");
+        return true;
+    }
+
+    println!("⏳ Searching for documents containing the phrase...");
+    let results = engine.search_phrase(&args.join(" "), SearchOptions::default().k);
+
+    if results.hits.is_empty() {
+        println!("🫙 No document contains the phrase.");
+    } else {
+        display_top_results(&results.hits);
+    }
+    println!("({:?})", results.took);
+
+    true
+}
+
+fn prune(engine: &mut SearchEngine, config: &Config, from_corpus: bool, args: Vec<&str>) -> bool {
+    let strategy = match args.first().copied() {
+        Some("term") => PruningStrategy::TermCentric,
+        Some("doc") => PruningStrategy::DocumentCentric,
+        _ => {
+            println!("⚠️ Usage: ::prune <term|doc> <ratio> [output dir]");
+            return true;
+        }
+    };
+    let Some(ratio) = args
+        .get(1)
+        .and_then(|arg| arg.parse::<f32>().ok())
+        .filter(|ratio| (0.0..1.0).contains(ratio))
+    else {
+        println!("⚠️ The pruning ratio has to be a number in [0, 1).");
+        return true;
+    };
+    let out_dir = match args.get(2) {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let mut dir = config.index_dir.clone().into_os_string();
+            dir.push(PRUNED_INDEX_SUFFIX);
+            PathBuf::from(dir)
+        }
+    };
+    if out_dir == config.index_dir {
+        println!("⚠️ The pruned index has to be saved apart from the full index in {}.", config.index_dir.display());
+        return true;
+    }
+
+    println!("🔬 Measuring MAP of the unpruned index...");
+    let measured = extract_queries(&config.queries).and_then(|queries| {
+        let map = mean_average_precision(&queries, &config.qrels, engine.index(), None)?;
+        Ok((queries, map))
+    });
+    let (queries, map_before) = match measured {
+        Ok(measured) => measured,
+        Err(e) => {
+            display_error(&e);
+            return true;
+        }
+    };
+
+    println!("\n✂️ Pruning {:.0}% of the postings ({:?})...", ratio * 100.0, strategy);
+    let report = prune_index(engine.index_mut(), strategy, ratio);
+
+    println!("🔬 Measuring MAP of the pruned index...");
+    let map_after = match mean_average_precision(&queries, &config.qrels, engine.index(), None) {
+        Ok(map) => map,
+        Err(e) => {
+            display_error(&e);
+            return true;
+        }
+    };
+
+    let reduction = |before: usize, after: usize| {
+        100.0 * (before - after) as f32 / before.max(1) as f32
+    };
+
+    println!("\n📉 Pruning Report:");
+    println!(
+        "   Postings: {} -> {} (-{:.1}%)",
+        report.postings_before,
+        report.postings_after,
+        reduction(report.postings_before, report.postings_after)
+    );
+    println!(
+        "   Approx. Size: {:.1} KiB -> {:.1} KiB (-{:.1}%)",
+        report.bytes_before as f32 / 1024.0,
+        report.bytes_after as f32 / 1024.0,
+        reduction(report.bytes_before, report.bytes_after)
+    );
+    println!(
+        "   MAP: {:.4} -> {:.4} ({:+.4})",
+        map_before,
+        map_after,
+        map_after - map_before
+    );
+
+    let achieved = report.achieved_ratio();
+    if achieved + PRUNING_RATIO_TOLERANCE < ratio {
+        println!(
+            "⚠️ Only {:.1}% of the requested {:.0}% could be pruned.",
+            achieved * 100.0,
+            ratio * 100.0
+        );
+        if strategy == PruningStrategy::TermCentric {
+            println!("   The term-centric threshold never exceeds a term's 10th best score,");
+            println!("   so terms with at most 10 postings and the top postings of all others are kept.");
+        }
+    }
+
+    match engine.save_with_documents(&out_dir) {
+        Ok(()) => println!("💾 Saved the pruned index to {}", out_dir.display()),
+        Err(e) => display_error(&e),
+    }
+    if from_corpus {
+        println!("ℹ️ Use ::reindex to restore the full index.");
+    } else {
+        println!(
+            "ℹ️ The full index in {} is unchanged; open it again under a new name to use it.",
+            config.index_dir.display()
+        );
+    }
+
+    true
+}
+
+fn autocorrect(session: &mut Session, args: Vec<&str>) -> bool {
+    match args.first().copied() {
+        Some("on") => session.autocorrect = true,
+        Some("off") => session.autocorrect = false,
+        _ => {
+            println!("⚠️ Usage: ::autocorrect <on|off>");
+            return true;
+        }
+    }
+
+    if session.autocorrect {
+        println!("✏️ Unknown query terms are now replaced by their best correction.");
+    } else {
+        println!("✏️ Unknown query terms are now only reported with a suggestion.");
+    }
+    true
+}
+
+/// Builds a named collection from a corpus file or loads it from a stored
+/// index directory. `key=value` options override the settings of the
+/// default collection.
+fn open_collection(session: &mut Session, args: Vec<&str>) -> bool {
+    let (options, args): (Vec<&str>, Vec<&str>) = args.into_iter().partition(|arg| arg.contains('='));
+    let [name, path] = args.as_slice() else {
+        println!("⚠️ Usage: ::open <name> <corpus file|index dir> [key=value ...]");
+        return true;
+    };
+    if session.collections.contains_key(*name) {
+        println!("⚠️ An index named '{}' is already open.", name);
+        return true;
+    }
+
+    let mut config = session.collections[DEFAULT_COLLECTION].config.clone();
+    let path = Path::new(path);
+    let from_corpus = !path.is_dir();
+    if from_corpus {
+        config.corpus = path.to_path_buf();
+        config.index_dir = config.index_dir.join(name);
+    } else {
+        config.index_dir = path.to_path_buf();
+    }
+    for option in options {
+        let (key, value) = option.split_once('=').unwrap_or((option, ""));
+        if let Err(e) = config.set(key, value) {
+            display_error(&e);
+            return true;
+        }
+    }
+
+    let opened = if from_corpus {
+        println!("> Indexing corpus at path: {}", config.corpus.display());
+        SearchEngine::build(&config, None)
+    } else {
+        println!("> Loading index from: {}", config.index_dir.display());
+        SearchEngine::open(&config)
+    };
+    let engine = match opened {
+        Ok(engine) => engine,
+        Err(e) => {
+            display_error(&e);
+            return true;
+        }
+    };
+
+    let stats = engine.stats();
+    println!(
+        "📂 Opened '{}' with {} documents and {} terms. Select it with ::use {}",
+        name, stats.documents, stats.terms, name
+    );
+    session.collections.insert(
+        name.to_string(),
+        Collection {
+            config,
+            engine,
+            from_corpus,
+        },
+    );
+    true
+}
+
+fn use_collection(session: &mut Session, args: Vec<&str>) -> bool {
+    match args.first().copied() {
+        Some("*") => {
+            session.cross_index = true;
+            println!(
+                "🌐 Queries now run against all {} open indexes; other commands keep using '{}'.",
+                session.collections.len(),
+                session.active
+            );
+        }
+        Some(name) if session.collections.contains_key(name) => {
+            session.active = name.to_string();
+            session.cross_index = false;
+            session.last_query = None;
+            println!("📂 Now using '{}'.", name);
+        }
+        Some(name) => {
+            let names: Vec<&str> = session.collections.keys().map(String::as_str).collect();
+            println!("⚠️ There is no index named '{}'. Open indexes: {}", name, names.join(", "));
+        }
+        None => println!("⚠️ Usage: ::use <name|*>"),
+    }
+    true
+}
+
+/// Sets how cross-index queries are merged; choosing a normalisation also
+/// runs queries against all open indexes.
+fn federated(session: &mut Session, args: Vec<&str>) -> bool {
+    match args.first().copied() {
+        Some("off") => {
+            session.federated = None;
+            println!("🌐 Cross-index queries are listed per index again.");
+        }
+        Some(name) => match Normalization::parse(name) {
+            Ok(normalization) => {
+                session.federated = Some(normalization);
+                session.cross_index = true;
+                println!(
+                    "🌐 Queries now run against all {} open indexes and are merged with {:?} normalisation.",
+                    session.collections.len(),
+                    normalization
+                );
+            }
+            Err(e) => display_error(&e),
+        },
+        None => println!("⚠️ Usage: ::federated <minmax|zscore|cori|off>"),
+    }
+    true
+}
+
+fn list_collections(session: &Session) -> bool {
+    println!("+---+------------------+-----------+--------+------------------------------------------+");
+    println!(
+        "|   | {:<16} | {:>9} | {:>6} | {:<40} |",
+        "Name", "Documents", "Terms", "Source"
+    );
+    println!("+---+------------------+-----------+--------+------------------------------------------+");
+    for (name, collection) in &session.collections {
+        let stats = collection.engine.stats();
+        let source = if collection.from_corpus {
+            &collection.config.corpus
+        } else {
+            &collection.config.index_dir
+        };
+        let marker = if *name == session.active { "*" } else { " " };
+        println!(
+            "| {} | {:<16} | {:>9} | {:>6} | {:<40} |",
+            marker,
+            name,
+            stats.documents,
+            stats.terms,
+            truncate_list(&[source.display().to_string()], 40)
+        );
+    }
+    println!("+---+------------------+-----------+--------+------------------------------------------+");
+    if session.cross_index {
+        println!("🌐 Queries run against all indexes; select a single one with ::use <name>.");
+    }
+    if let Some(normalization) = session.federated {
+        println!("🌐 Results of all indexes are merged with {:?} normalisation.", normalization);
+    }
+    true
+}
+
+fn print_config(config: &Config) -> bool {
+    println!("⚙️ Configuration:");
+    for line in config.to_lines() {
+        println!("   {}", line);
+    }
     true
 }
 
-fn print_tokenized(args: Vec<&str>) -> bool {
+fn print_tokenized(args: Vec<&str>, analyzer: &Analyzer) -> bool {
     let mut tokens: Vec<String> = Vec::new();
     for term in args {
-        if let Some(token) = tokenize(term) {
+        if let Some(token) = analyzer.analyze(term) {
             tokens.push(token);
         }
     }
-    if tokens.len() > 0 {
+    if !tokens.is_empty() {
         println!("🪙 Tokenized sequence: {}", tokens.join(" "));
     } else {
         println!("✂️ Sequence only had stopwords!");
@@ -238,29 +1404,114 @@ fn print_help() -> bool {
     println!("📖 Available commands:");
     println!("   ::help                - Show this help message");
     println!("                           Example:    ::help");
-    println!("");
+    println!();
     println!("   ::exit                - Exit the application");
     println!("                           Example:    ::exit");
-    println!("");
+    println!();
     println!("   ::reindex             - Rebuild the inverted index");
     println!("                           Example:    ::reindex");
-    println!("");
+    println!("                           Add 'impact <budget>' to precompute 8-bit BM25 impacts and");
+    println!("                           stop each query after that share of its postings.");
+    println!("                           Example:    ::reindex impact 0.3");
+    println!();
     println!("   ::stats               - Show statistics about the inverted index");
     println!("                           Example:    ::stats");
-    println!("");
+    println!();
+    println!("   ::config              - Show the paths, analyzer and ranking settings in use");
+    println!("                           Example:    ::config");
+    println!();
+    println!("   ::open <name> <path> [key=value]");
+    println!("                         - Open another index from a corpus file or a stored index");
+    println!("                           directory; options override config keys such as qrels");
+    println!("                           Example:    ::open cacm in/cacm.all queries=in/cacm.qry qrels=in/cacm.qrel");
+    println!();
+    println!("   ::use <name|*>        - Select the index for queries and commands; '*' runs queries");
+    println!("                           against all open indexes");
+    println!("                           Example:    ::use cacm");
+    println!();
+    println!("   ::indexes             - List the open indexes, the selected one is marked with '*'");
+    println!("                           Example:    ::indexes");
+    println!();
+    println!("   ::federated <norm>    - Query all open indexes and merge the results into one ranking,");
+    println!("                           normalising scores with minmax, zscore or cori ('off' lists");
+    println!("                           the results per index)");
+    println!("                           Example:    ::federated cori");
+    println!();
     println!("   ::postings <term>     - Show postings list for a term");
     println!("                           Example:    ::postings computers");
-    println!("");
+    println!();
     println!("   ::commons <n>         - Shows n most common postings");
     println!("                           Example:    ::commons 15");
-    println!("");
-    println!("   ::doc <ID>            - Display the content of a document by its ID");
+    println!();
+    println!("   ::doc <ID> [query]    - Display the content of a document by its ID, highlighting the terms");
+    println!("                           of the given query or else of the last query");
     println!("                           Example:    ::doc 42");
-    println!("");
+    println!("                           Example:    ::doc 42 boundary layer");
+    println!();
     println!("   ::eval <IDs>          - Run predefined test queries with relevance list.");
     println!("                           Param specifies which queries are ran, if left empty all are ran.");
+    println!("                           Add fb=<rm3|rocchio> fb_docs=N fb_terms=N orig=W for feedback.");
     println!("                           Example:    ::eval 1 3 5 10");
-    println!("");
+    println!("                           Add fusion=<rrf|combsum|combmnz> runs=... depth=N rrf_k=K mu=M");
+    println!("                           to rank with fused runs (see ::fusion), ltr=<model file> to rerank.");
+    println!("                           Example:    ::eval fb=rm3 fb_docs=10 fb_terms=20 orig=0.5");
+    println!("                           Example:    ::eval fusion=combmnz runs=bm25,title,body");
+    println!();
+    println!("   ::prune <term|doc> <r> - Statically prune a share r of the postings, term- or document-centric,");
+    println!("                           and compare the MAP before and after. The pruned index is saved to");
+    println!("                           the given directory, by default next to the index as '<dir>-pruned'");
+    println!("                           Example:    ::prune term 0.3");
+    println!("                           Example:    ::prune doc 0.5 out-small");
+    println!();
+    println!("   ::bench <runs>        - Measure query latency over the predefined test queries");
+    println!("                           Example:    ::bench 10");
+    println!();
+    println!("   ::and <terms>         - Only rank documents that contain every term");
+    println!("                           Example:    ::and shock boundary layer");
+    println!();
+    println!("   ::phrase <words>      - Only rank documents that contain the words next to each other");
+    println!("                           Example:    ::phrase boundary layer");
+    println!();
+    println!("   ::autocorrect <on|off> - Rewrite unknown query terms to their closest indexed term");
+    println!("                           Example:    ::autocorrect on");
+    println!();
+    println!("   ::feedback <model>    - Expand every query from its top documents (rm3, rocchio or off)");
+    println!("                           Example:    ::feedback rocchio fb_docs=5 fb_terms=10 orig=0.6");
+    println!();
+    println!("   ::fusion <method>     - Rank every query with several runs fused by rrf, combsum or");
+    println!("                           combmnz ('off' ranks with BM25 alone). Runs: bm25, title");
+    println!("                           (BM25 on titles), body (BM25 without titles), lm (LM-Dirichlet)");
+    println!("                           Example:    ::fusion rrf runs=bm25,title,lm depth=100 rrf_k=60 mu=2000");
+    println!();
+    println!("   ::ltr train [path]    - Learn a reranker of the top BM25 documents from the qrels, report");
+    println!("                           its cross-validation by query and save it (default: ltr.model)");
+    println!("                           Example:    ::ltr train folds=5 candidates=100");
+    println!("   ::ltr load <path>     - Rerank every query with a saved model; '::ltr off' stops it");
+    println!("                           Example:    ::ltr load ltr.model");
+    println!();
+    println!("   ::relevant <IDs>      - Mark results of the last query as relevant and rerun it (Rocchio)");
+    println!("                           Example:    ::relevant 12 184");
+    println!();
+    println!("   ::nonrelevant <IDs>   - Mark results of the last query as not relevant and rerun it");
+    println!("                           Example:    ::nonrelevant 51");
+    println!();
+    println!("   ::savejudgments <qid> - Save the judgments of the last query to a qrels file,");
+    println!("                           replacing earlier grades (default: judgments.qrel)");
+    println!("                           Example:    ::savejudgments 226 my.qrel");
+    println!();
+    println!("   ::similar <ID> <k>    - Find the k documents most similar to a document");
+    println!("                           Example:    ::similar 42 5");
+    println!();
+    println!("   ::termvector <ID>     - Show the stored terms of a document with tf and positions");
+    println!("                           Example:    ::termvector 42");
+    println!();
+    println!("   ::explain <ID> <query> - Break the BM25 score of a document down per query term");
+    println!("                           Example:    ::explain 42 boundary layer");
+    println!();
+    println!("   ::analyze <qid>       - Show rank and matched/missed terms of every relevant document of a");
+    println!("                           test query, plus terms the query lacks");
+    println!("                           Example:    ::analyze 12");
+    println!();
     println!("   ::tokenize <terms>    - Stem a sequence of terms");
     println!("                           Example:    ::tokenize The quick brown fox");
 
@@ -0,0 +1,239 @@
+use std::{
+    fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Error, Result},
+    evaluation::{precision_calculator::DEFAULT_QRELS_PATH, query_extractor::DEFAULT_QUERIES_PATH},
+    preprocessing::{
+        persistence::DEFAULT_INDEX_DIR,
+        splitter::DEFAULT_CORPUS_PATH,
+        tokenizer::{load_stopwords, Analyzer, AnalyzerStep, STOPWORDS_PATH},
+    },
+    querying::{language_model::DEFAULT_MU, score::Bm25Params},
+};
+
+pub use crate::querying::score::ScoringModel;
+
+/// Config file read when neither `--config` nor `MINI_RETRIEVE_CONFIG` name one.
+pub const DEFAULT_CONFIG_PATH: &str = "mini-retrieve.conf";
+/// Environment variable naming the config file.
+pub const CONFIG_ENV: &str = "MINI_RETRIEVE_CONFIG";
+/// Prefix of the environment variables overriding single keys, e.g.
+/// `MINI_RETRIEVE_K1=1.5`.
+pub const ENV_PREFIX: &str = "MINI_RETRIEVE_";
+/// Every key of the config file; also the names of the CLI overrides.
+pub const CONFIG_KEYS: [&str; 10] = [
+    "corpus", "queries", "qrels", "index", "stopwords", "analyzer", "model", "k1", "b", "mu",
+];
+
+/// Paths, analysis and ranking settings shared by all front ends.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub corpus: PathBuf,
+    pub queries: PathBuf,
+    pub qrels: PathBuf,
+    /// Directory holding the stored index and the split documents.
+    pub index_dir: PathBuf,
+    pub stopwords: PathBuf,
+    /// Chain used when building an index; a stored index keeps its own.
+    pub analyzer: Analyzer,
+    /// `bm25` or `lm` (LM-Dirichlet with `mu`); fusion and the reranker
+    /// features keep using their own runs.
+    pub model: ScoringModel,
+    pub bm25: Bm25Params,
+    /// Dirichlet prior of the `lm` model, kept while `bm25` is selected.
+    pub mu: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            corpus: PathBuf::from(DEFAULT_CORPUS_PATH),
+            queries: PathBuf::from(DEFAULT_QUERIES_PATH),
+            qrels: PathBuf::from(DEFAULT_QRELS_PATH),
+            index_dir: PathBuf::from(DEFAULT_INDEX_DIR),
+            stopwords: PathBuf::from(STOPWORDS_PATH),
+            analyzer: Analyzer::default(),
+            model: ScoringModel::Bm25,
+            bm25: Bm25Params::default(),
+            mu: DEFAULT_MU,
+        }
+    }
+}
+
+impl Config {
+    /// Starts from the defaults, applies the config file and then the
+    /// `MINI_RETRIEVE_<KEY>` environment variables. The file is `path` if
+    /// given, else `$MINI_RETRIEVE_CONFIG`, else `mini-retrieve.conf` if it
+    /// exists.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut config = Config::default();
+
+        let env_path = std::env::var_os(CONFIG_ENV).map(PathBuf::from);
+        match path.map(Path::to_path_buf).or(env_path) {
+            Some(path) => config.apply_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).is_file() => {
+                config.apply_file(Path::new(DEFAULT_CONFIG_PATH))?
+            }
+            None => {}
+        }
+
+        for key in CONFIG_KEYS {
+            if let Ok(value) = std::env::var(format!("{}{}", ENV_PREFIX, key.to_uppercase())) {
+                config.set(key, &value)?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// Applies a file of `key = value` lines. Blank lines and lines starting
+    /// with `#` are skipped.
+    pub fn apply_file(&mut self, path: &Path) -> Result<()> {
+        let content = fs::read_to_string(path).map_err(|e| Error::open("config", path, e))?;
+
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(Error::parse(path, Some(number + 1), "expected '<key> = <value>'"));
+            };
+            self.set(key.trim(), value.trim()).map_err(|e| match e {
+                Error::Config(message) => Error::parse(path, Some(number + 1), message),
+                e => e,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Sets one key to a value given as text. `model` and `mu` can be set
+    /// in either order.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "corpus" => self.corpus = PathBuf::from(value),
+            "queries" => self.queries = PathBuf::from(value),
+            "qrels" => self.qrels = PathBuf::from(value),
+            "index" => self.index_dir = PathBuf::from(value),
+            "stopwords" => self.stopwords = PathBuf::from(value),
+            "analyzer" => self.analyzer = Analyzer::parse(value)?,
+            "model" => {
+                self.model = match value {
+                    "bm25" => ScoringModel::Bm25,
+                    "lm" => ScoringModel::LmDirichlet { mu: self.mu },
+                    _ => {
+                        return Err(Error::Config(format!(
+                            "unknown scoring model '{}' (expected bm25 or lm)",
+                            value
+                        )))
+                    }
+                }
+            }
+            "k1" => self.bm25.k1 = parse_param(key, value, 0.0..=f32::MAX)?,
+            "b" => self.bm25.b = parse_param(key, value, 0.0..=1.0)?,
+            "mu" => {
+                self.mu = parse_param(key, value, f32::MIN_POSITIVE..=f32::MAX)?;
+                if let ScoringModel::LmDirichlet { mu } = &mut self.model {
+                    *mu = self.mu;
+                }
+            }
+            _ => return Err(Error::Config(format!("unknown key '{}'", key))),
+        }
+        Ok(())
+    }
+
+    /// The configured analyzer chain with the stopword list of `stopwords`,
+    /// which is only read if the chain has a stopwords step.
+    pub fn build_analyzer(&self) -> Result<Analyzer> {
+        if !self.analyzer.has_step(AnalyzerStep::Stopwords) {
+            return Ok(self.analyzer.clone());
+        }
+        Ok(self.analyzer.clone().with_stopwords(load_stopwords(&self.stopwords)?))
+    }
+
+    /// The settings as `key = value` lines, in the format of the config file.
+    pub fn to_lines(&self) -> Vec<String> {
+        let model = match self.model {
+            ScoringModel::Bm25 => "bm25",
+            ScoringModel::LmDirichlet { .. } => "lm",
+        };
+        vec![
+            format!("corpus = {}", self.corpus.display()),
+            format!("queries = {}", self.queries.display()),
+            format!("qrels = {}", self.qrels.display()),
+            format!("index = {}", self.index_dir.display()),
+            format!("stopwords = {}", self.stopwords.display()),
+            format!("analyzer = {}", self.analyzer),
+            format!("model = {}", model),
+            format!("k1 = {}", self.bm25.k1),
+            format!("b = {}", self.bm25.b),
+            format!("mu = {}", self.mu),
+        ]
+    }
+}
+
+fn parse_param(key: &str, value: &str, range: RangeInclusive<f32>) -> Result<f32> {
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|parsed| range.contains(parsed))
+        .ok_or_else(|| Error::Config(format!("'{}' is not a valid value for {}", value, key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mu_applies_to_the_lm_model_in_either_order() {
+        let mut config = Config::default();
+        config.set("mu", "500").unwrap();
+        assert_eq!(config.model, ScoringModel::Bm25);
+        config.set("model", "lm").unwrap();
+        assert_eq!(config.model, ScoringModel::LmDirichlet { mu: 500.0 });
+        config.set("mu", "1000").unwrap();
+        assert_eq!(config.model, ScoringModel::LmDirichlet { mu: 1000.0 });
+
+        assert!(config.set("mu", "0").is_err());
+        assert!(config.set("mu", "-5").is_err());
+        assert!(config.set("model", "tfidf").is_err());
+        assert!(config.to_lines().contains(&"mu = 1000".to_string()));
+    }
+
+    /// Environment variables are process wide, so every case touching them
+    /// runs in this one test.
+    #[test]
+    fn the_environment_overrides_the_config_file() {
+        let dir = std::env::temp_dir().join(format!("mini-retrieve-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.conf");
+        fs::write(&path, "# ranking\nmodel = lm\nmu = 800\n\nk1 = 1.5\nb = 0.5\n").unwrap();
+
+        let config = Config::load(Some(&path)).unwrap();
+        assert_eq!(config.model, ScoringModel::LmDirichlet { mu: 800.0 });
+        assert_eq!(config.bm25, Bm25Params { k1: 1.5, b: 0.5 });
+        assert_eq!(config.index_dir, PathBuf::from(DEFAULT_INDEX_DIR));
+
+        std::env::set_var("MINI_RETRIEVE_K1", "2");
+        std::env::set_var("MINI_RETRIEVE_MU", "300");
+        let overridden = Config::load(Some(&path));
+        std::env::remove_var("MINI_RETRIEVE_K1");
+        std::env::remove_var("MINI_RETRIEVE_MU");
+        let mut config = overridden.unwrap();
+        assert_eq!(config.bm25, Bm25Params { k1: 2.0, b: 0.5 });
+        assert_eq!(config.model, ScoringModel::LmDirichlet { mu: 300.0 });
+
+        // Options of the subcommands are applied last, see `cli::load_config`.
+        config.set("mu", "100").unwrap();
+        assert_eq!(config.model, ScoringModel::LmDirichlet { mu: 100.0 });
+
+        fs::write(&path, "k1 = 1.5\nmystery = 1\n").unwrap();
+        let error = Config::load(Some(&path)).unwrap_err().to_string();
+        assert!(error.contains(":2"), "{}", error);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,426 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::Config,
+    error::Result,
+    evaluation::{
+        failure_analysis::{analyze_query, QueryAnalysis},
+        precision_calculator::{evaluate, EvalReport},
+    },
+    preprocessing::{
+        impacts::build_impact_index,
+        indexer::create_inverted_index,
+        persistence::{copy_documents, load_index, save_index, DOCUMENTS_DIR_NAME},
+        splitter::split_documents,
+        tokenizer::{load_stopwords, AnalyzerStep},
+    },
+    querying::{
+        explain::{explain, Explanation},
+        feedback::{expand_query, FeedbackParams},
+        fusion::{score_fused, FusionParams},
+        ltr::LinearModel,
+        phrase::score_phrase,
+        query::Query,
+        score::{score, score_conjunctive},
+        similar::more_like_this,
+        snippet::{query_biased_snippet, Snippet},
+        spelling::{check_query, Suggestion},
+    },
+    types::{DocId, InvertedIndex},
+    utils::read_document,
+};
+
+/// How `SearchEngine::search` ranks a query.
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub k: usize,
+    /// Only rank documents containing every query term.
+    pub conjunctive: bool,
+    /// Replace unknown query terms with their best spelling correction.
+    pub autocorrect: bool,
+    pub feedback: Option<FeedbackParams>,
+    /// Rank with several runs fused into one ranking instead of BM25 alone.
+    pub fusion: Option<FusionParams>,
+    /// Rerank the top candidates of the first-stage ranking with a learned
+    /// model. Models are trained on the candidates of `score`, so they
+    /// always rerank those and `fusion` is ignored while one is set.
+    pub reranker: Option<LinearModel>,
+    /// Attach a query-biased snippet to every hit.
+    pub snippets: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            k: 10,
+            conjunctive: false,
+            autocorrect: false,
+            feedback: None,
+            fusion: None,
+            reranker: None,
+            snippets: true,
+        }
+    }
+}
+
+/// One ranked document.
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub rank: usize,
+    pub doc_id: DocId,
+    pub title: String,
+    pub score: f32,
+    pub snippet: Option<Snippet>,
+}
+
+/// The outcome of a search: the analysed query, the query that was
+/// actually scored if feedback reformulated it, spelling suggestions for
+/// unknown words and the ranked hits.
+#[derive(Debug, Clone)]
+pub struct SearchResults {
+    pub query: Query,
+    pub reformulated: Option<Query>,
+    pub suggestions: Vec<Suggestion>,
+    pub hits: Vec<Hit>,
+    /// Time spent analysing and scoring, without snippet generation.
+    pub took: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexStats {
+    pub documents: u32,
+    pub terms: usize,
+    pub avdl: f32,
+    /// Postings budget of impact-ordered query processing, if enabled.
+    pub impact_budget: Option<f32>,
+}
+
+/// A stored document with its raw content.
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub doc_id: DocId,
+    pub title: String,
+    pub length: u32,
+    pub content: String,
+}
+
+/// Entry point for embedding the engine: owns an index and answers
+/// searches, document lookups and evaluations with plain values instead of
+/// printing them.
+#[derive(Debug, Default)]
+pub struct SearchEngine {
+    inverted_index: InvertedIndex,
+}
+
+impl SearchEngine {
+    pub fn new(inverted_index: InvertedIndex) -> Self {
+        SearchEngine { inverted_index }
+    }
+
+    /// Splits the configured corpus into `<index_dir>/documents` and indexes
+    /// it with the configured analyzer. With an `impact_budget`, quantized
+    /// impacts are precomputed as well.
+    pub fn build(config: &Config, impact_budget: Option<f32>) -> Result<Self> {
+        let documents_dir = config.index_dir.join(DOCUMENTS_DIR_NAME);
+        split_documents(&config.corpus, &documents_dir)?;
+        let analyzer = config.build_analyzer()?;
+        let mut inverted_index = create_inverted_index(&documents_dir, analyzer)?;
+        inverted_index.model = config.model;
+        inverted_index.bm25 = config.bm25;
+        if let Some(budget) = impact_budget {
+            inverted_index.impacts = Some(build_impact_index(&inverted_index, budget));
+        }
+        Ok(SearchEngine::new(inverted_index))
+    }
+
+    /// Loads the index stored with `save` in the configured index directory
+    /// and ranks with the configured BM25 parameters. Indexes stored without
+    /// their stopword list get the configured one.
+    pub fn open(config: &Config) -> Result<Self> {
+        let mut inverted_index = load_index(&config.index_dir)?;
+        let analyzer = &inverted_index.analyzer;
+        if analyzer.has_step(AnalyzerStep::Stopwords) && analyzer.stopwords().is_empty() {
+            inverted_index.analyzer = analyzer.clone().with_stopwords(load_stopwords(&config.stopwords)?);
+        }
+        inverted_index.model = config.model;
+        inverted_index.bm25 = config.bm25;
+        Ok(SearchEngine::new(inverted_index))
+    }
+
+    pub fn save(&self, index_dir: &Path) -> Result<()> {
+        save_index(&self.inverted_index, index_dir)
+    }
+
+    /// Saves the index to `index_dir` together with a copy of its documents,
+    /// so that it can be opened from there without the directory it was
+    /// loaded from.
+    pub fn save_with_documents(&self, index_dir: &Path) -> Result<()> {
+        copy_documents(&self.inverted_index, index_dir)?;
+        save_index(&self.inverted_index, index_dir)
+    }
+
+    pub fn index(&self) -> &InvertedIndex {
+        &self.inverted_index
+    }
+
+    pub fn index_mut(&mut self) -> &mut InvertedIndex {
+        &mut self.inverted_index
+    }
+
+    /// Analyses `text` (wildcards, fuzzy terms, spelling) and ranks it.
+    pub fn search(&self, text: &str, options: &SearchOptions) -> SearchResults {
+        let start_time = Instant::now();
+        let mut query = Query::parse(text, &self.inverted_index);
+        let suggestions = check_query(text, &self.inverted_index);
+        if options.autocorrect {
+            for suggestion in &suggestions {
+                if let Some(correction) = &suggestion.correction {
+                    query.replace_token(&suggestion.token, correction.clone());
+                }
+            }
+        }
+
+        let analysis_time = start_time.elapsed();
+
+        let mut results = self.search_query(query, options);
+        results.suggestions = suggestions;
+        results.took += analysis_time;
+        results
+    }
+
+    /// Ranks an already analysed query.
+    pub fn search_query(&self, query: Query, options: &SearchOptions) -> SearchResults {
+        let start_time = Instant::now();
+        let (ranked_results, reformulated) = if options.conjunctive {
+            (score_conjunctive(&query, &self.inverted_index, options.k), None)
+        } else if let Some(params) = &options.feedback {
+            let expanded = expand_query(&query, &self.inverted_index, params);
+            (self.rank(&expanded, options), Some(expanded))
+        } else {
+            (self.rank(&query, options), None)
+        };
+        let took = start_time.elapsed();
+
+        let snippet_query = options
+            .snippets
+            .then(|| reformulated.as_ref().unwrap_or(&query));
+        let hits = self.hits(&ranked_results, snippet_query);
+        SearchResults {
+            query,
+            reformulated,
+            suggestions: Vec::new(),
+            hits,
+            took,
+        }
+    }
+
+    /// Reranks the candidates of `score` if a model is given, else ranks
+    /// with the fused runs if enabled, else with `score`.
+    fn rank(&self, query: &Query, options: &SearchOptions) -> Vec<(DocId, f32)> {
+        match (&options.reranker, &options.fusion) {
+            (Some(model), _) => {
+                let candidates = score(query, &self.inverted_index, model.candidates.max(options.k));
+                let mut reranked = model.rerank(query, &candidates, &self.inverted_index);
+                reranked.truncate(options.k);
+                reranked
+            }
+            (None, Some(params)) => score_fused(query, &self.inverted_index, options.k, params),
+            (None, None) => score(query, &self.inverted_index, options.k),
+        }
+    }
+
+    /// Ranks the documents that contain the words of `text` next to each
+    /// other, see `match_phrase`.
+    pub fn search_phrase(&self, text: &str, k: usize) -> SearchResults {
+        let start_time = Instant::now();
+        let (query, ranked_results) = score_phrase(text, &self.inverted_index, k);
+        let took = start_time.elapsed();
+
+        let hits = self.hits(&ranked_results, Some(&query));
+        SearchResults {
+            query,
+            reformulated: None,
+            suggestions: Vec::new(),
+            hits,
+            took,
+        }
+    }
+
+    /// The `k` documents most similar to `doc_id` together with the query
+    /// built from it, or `None` if the document has no indexed terms.
+    pub fn similar(&self, doc_id: DocId, k: usize) -> Option<SearchResults> {
+        let start_time = Instant::now();
+        let (query, ranked_results) = more_like_this(doc_id, &self.inverted_index, k)?;
+        let took = start_time.elapsed();
+
+        let hits = self.hits(&ranked_results, Some(&query));
+        Some(SearchResults {
+            query,
+            reformulated: None,
+            suggestions: Vec::new(),
+            hits,
+            took,
+        })
+    }
+
+    pub fn stats(&self) -> IndexStats {
+        IndexStats {
+            documents: self.inverted_index.n,
+            terms: self.inverted_index.dictionary.len(),
+            avdl: self.inverted_index.avdl,
+            impact_budget: self.inverted_index.impacts.as_ref().map(|impacts| impacts.budget),
+        }
+    }
+
+    /// The document with its raw content, or `None` if it is not indexed or
+    /// its file cannot be read.
+    pub fn document(&self, doc_id: DocId) -> Option<Document> {
+        let doc_idx = self.inverted_index.doc_idx(doc_id)?;
+        let content = read_document(&self.inverted_index, doc_id)?;
+        Some(Document {
+            doc_id,
+            title: self.inverted_index.title(doc_idx).to_string(),
+            length: self.inverted_index.doc_lengths[doc_idx as usize],
+            content,
+        })
+    }
+
+    pub fn evaluate(
+        &self,
+        queries: &HashMap<u32, String>,
+        qrels_path: &Path,
+        feedback: Option<&FeedbackParams>,
+        fusion: Option<&FusionParams>,
+        reranker: Option<&LinearModel>,
+    ) -> Result<EvalReport> {
+        evaluate(queries, qrels_path, &self.inverted_index, feedback, fusion, reranker)
+    }
+
+    pub fn explain(&self, doc_id: DocId, text: &str) -> Option<Explanation> {
+        explain(doc_id, text, &self.inverted_index)
+    }
+
+    pub fn analyze(&self, query_id: u32, text: &str, qrels_path: &Path) -> Result<QueryAnalysis> {
+        analyze_query(query_id, text, qrels_path, &self.inverted_index)
+    }
+
+    /// The passage of `doc_id` that best matches `query`.
+    pub fn snippet(&self, doc_id: DocId, query: &Query) -> Option<Snippet> {
+        let content = read_document(&self.inverted_index, doc_id)?;
+        query_biased_snippet(&content, query, &self.inverted_index)
+    }
+
+    /// Turns a ranking into hits with titles and, given a query, snippets.
+    pub fn hits(&self, ranked_results: &[(DocId, f32)], snippet_query: Option<&Query>) -> Vec<Hit> {
+        ranked_results
+            .iter()
+            .enumerate()
+            .map(|(rank, &(doc_id, score))| {
+                let title = self
+                    .inverted_index
+                    .doc_idx(doc_id)
+                    .map(|doc_idx| self.inverted_index.title(doc_idx))
+                    .unwrap_or("Title Missing")
+                    .to_string();
+                let snippet = snippet_query.and_then(|query| self.snippet(doc_id, query));
+                Hit {
+                    rank: rank + 1,
+                    doc_id,
+                    title,
+                    score,
+                    snippet,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preprocessing::tokenizer::{Analyzer, AnalyzerStep},
+        types::{DocIdx, Posting},
+    };
+
+    /// An engine over documents `1..` with a title and terms that occur
+    /// once each.
+    fn engine(documents: &[(&str, &[&str])]) -> SearchEngine {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        for (doc_idx, (title, terms)) in documents.iter().enumerate() {
+            inverted_index.add_document(doc_idx as DocId + 1, title.to_string(), terms.len() as u32, 0);
+            for term in *terms {
+                inverted_index
+                    .add_posting(term.to_string(), Posting::new(doc_idx as DocIdx, 1))
+                    .unwrap();
+            }
+        }
+        inverted_index.n = documents.len() as u32;
+        inverted_index.avdl = inverted_index.doc_lengths.iter().sum::<u32>() as f32 / documents.len() as f32;
+        inverted_index.rebuild_vocabulary();
+        SearchEngine::new(inverted_index)
+    }
+
+    fn doc_ids(results: &SearchResults) -> Vec<DocId> {
+        results.hits.iter().map(|hit| hit.doc_id).collect()
+    }
+
+    #[test]
+    fn searches_return_ranked_hits_with_titles_and_suggestions() {
+        let engine = engine(&[
+            ("shock tubes", &["shock", "tube"]),
+            ("flow past a wedge", &["shock", "flow", "wedge"]),
+            ("laminar flow", &["flow", "laminar"]),
+            ("plate heating", &["plate", "heat"]),
+            ("swept wings", &["wing"]),
+            ("delta wings", &["wing", "delta"]),
+        ]);
+        let options = SearchOptions {
+            snippets: false,
+            ..SearchOptions::default()
+        };
+
+        let results = engine.search("shock flwo", &options);
+        assert_eq!(doc_ids(&results), vec![1, 2]);
+        assert_eq!(results.suggestions.len(), 1);
+        assert_eq!(results.suggestions[0].correction.as_deref(), Some("flow"));
+
+        let autocorrect = SearchOptions {
+            autocorrect: true,
+            ..options.clone()
+        };
+        let results = engine.search("shock flwo", &autocorrect);
+        assert_eq!(doc_ids(&results)[0], 2);
+        assert_eq!(results.hits.len(), 3);
+        assert_eq!(results.hits[0].rank, 1);
+        assert_eq!(results.hits[0].title, "flow past a wedge");
+        assert!(results.hits.iter().all(|hit| hit.snippet.is_none()));
+
+        let conjunctive = SearchOptions {
+            conjunctive: true,
+            ..autocorrect
+        };
+        assert_eq!(doc_ids(&engine.search("shock flwo", &conjunctive)), vec![2]);
+    }
+
+    #[test]
+    fn stats_and_lookups_describe_the_index() {
+        let engine = engine(&[("shock tubes", &["shock", "tube"]), ("laminar flow", &["flow"])]);
+
+        let stats = engine.stats();
+        assert_eq!(stats.documents, 2);
+        assert_eq!(stats.terms, 3);
+        assert_eq!(stats.avdl, 1.5);
+        assert_eq!(stats.impact_budget, None);
+
+        let hits = engine.hits(&[(2, 1.0), (9, 0.5)], None);
+        let titles: Vec<&str> = hits.iter().map(|hit| hit.title.as_str()).collect();
+        assert_eq!(titles, vec!["laminar flow", "Title Missing"]);
+        assert!(engine.document(9).is_none());
+    }
+}
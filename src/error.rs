@@ -0,0 +1,79 @@
+use std::{fmt, io, path::{Path, PathBuf}};
+
+/// Everything that can go wrong while building, loading or querying an index.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// A file has the wrong format. `line` is 1-based where it applies.
+    Parse {
+        path: PathBuf,
+        line: Option<usize>,
+        message: String,
+    },
+    /// Invalid settings, e.g. an unknown option value.
+    Config(String),
+    /// A file the engine depends on (corpus, stopwords, queries, qrels,
+    /// index) does not exist.
+    MissingResource { resource: &'static str, path: PathBuf },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub(crate) fn parse(path: &Path, line: Option<usize>, message: impl Into<String>) -> Self {
+        Error::Parse {
+            path: path.to_path_buf(),
+            line,
+            message: message.into(),
+        }
+    }
+
+    /// Wraps an error from opening `path`, reporting a missing file as a
+    /// missing `resource`.
+    pub(crate) fn open(resource: &'static str, path: &Path, e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => Error::MissingResource {
+                resource,
+                path: path.to_path_buf(),
+            },
+            _ => Error::Io(e),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Parse {
+                path,
+                line: Some(line),
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            Error::Parse {
+                path,
+                line: None,
+                message,
+            } => write!(f, "{}: {}", path.display(), message),
+            Error::Config(message) => write!(f, "invalid configuration: {}", message),
+            Error::MissingResource { resource, path } => {
+                write!(f, "the {} file '{}' does not exist", resource, path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    querying::{
+        query::Query,
+        score::{idf, score_bm25, score_component},
+    },
+    types::{DocId, DocIdx, InvertedIndex},
+};
+
+const BENCHMARK_TOP_K: usize = 10;
+
+/// Implementation of BM25 a benchmark measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BenchmarkScorer {
+    /// `score_bm25` with its reused dense accumulators and top-k heap.
+    Accumulators,
+    /// The earlier scorer: a fresh `HashMap` per query and a full sort.
+    HashMap,
+}
+
+#[derive(Debug)]
+pub struct BenchmarkReport {
+    pub queries: usize,
+    pub runs: usize,
+    pub total: Duration,
+    pub mean: Duration,
+    pub median: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+/// Runs every query `runs` times through `scorer` and collects the latency
+/// of each single execution.
+pub fn run_benchmark(
+    queries: &HashMap<u32, String>,
+    inverted_index: &InvertedIndex,
+    runs: usize,
+    scorer: BenchmarkScorer,
+) -> BenchmarkReport {
+    let mut latencies: Vec<Duration> = Vec::with_capacity(queries.len() * runs);
+
+    for _ in 0..runs {
+        for query in queries.values() {
+            let start_time = Instant::now();
+            let parsed_query = Query::parse(query, inverted_index);
+            let ranked_results = match scorer {
+                BenchmarkScorer::Accumulators => score_bm25(&parsed_query, inverted_index, BENCHMARK_TOP_K),
+                BenchmarkScorer::HashMap => score_with_hash_map(&parsed_query, inverted_index, BENCHMARK_TOP_K),
+            };
+            latencies.push(start_time.elapsed());
+            std::hint::black_box(ranked_results);
+        }
+    }
+
+    latencies.sort();
+    let total: Duration = latencies.iter().sum();
+    let mean = total
+        .checked_div(latencies.len().max(1) as u32)
+        .unwrap_or_default();
+
+    BenchmarkReport {
+        queries: queries.len(),
+        runs,
+        total,
+        mean,
+        median: percentile(&latencies, 0.5),
+        p95: percentile(&latencies, 0.95),
+        max: latencies.last().copied().unwrap_or_default(),
+    }
+}
+
+/// BM25 the way it was computed before dense accumulators: scores are
+/// collected in a `HashMap` and every scored document is sorted. Kept as
+/// the baseline of the benchmark; it ranks like `score_bm25` without impacts.
+fn score_with_hash_map(query: &Query, inverted_index: &InvertedIndex, k: usize) -> Vec<(DocId, f32)> {
+    let mut scores: HashMap<DocIdx, f32> = HashMap::new();
+    for term in &query.terms {
+        let Some(postings) = inverted_index.dictionary.get(&term.token) else {
+            continue;
+        };
+        let idf_j = idf(postings.df(), inverted_index.n) * term.weight;
+        for posting in postings {
+            let l_di = inverted_index.doc_lengths[posting.doc_idx as usize] as f32;
+            let score = idf_j * score_component(posting.tf as f32, l_di, inverted_index.avdl, inverted_index.bm25);
+            *scores.entry(posting.doc_idx).or_insert(0.0) += score;
+        }
+    }
+
+    let mut ranked: Vec<(DocIdx, f32)> = scores.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked
+        .into_iter()
+        .take(k)
+        .map(|(doc_idx, score)| (inverted_index.doc_id(doc_idx), score))
+        .collect()
+}
+
+pub(crate) fn percentile(sorted: &[Duration], p: f32) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((sorted.len() - 1) as f32 * p).round() as usize;
+    sorted[rank]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preprocessing::tokenizer::{Analyzer, AnalyzerStep},
+        types::Posting,
+    };
+
+    #[test]
+    fn hash_map_scorer_ranks_like_the_accumulators() {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        let words = ["flow", "wing", "shock", "layer", "mach"];
+        for doc_idx in 0..200u32 {
+            inverted_index.add_document(doc_idx + 1, String::new(), 1 + doc_idx % 17, 0);
+            for (i, term) in words.iter().skip(doc_idx as usize % 3).take(1 + doc_idx as usize % 5).enumerate() {
+                let tf = 1 + (doc_idx + i as u32) % 4;
+                inverted_index.add_posting(term.to_string(), Posting::new(doc_idx, tf)).unwrap();
+            }
+        }
+        inverted_index.n = 200;
+        inverted_index.avdl = inverted_index.doc_lengths.iter().sum::<u32>() as f32 / 200.0;
+        inverted_index.rebuild_vocabulary();
+
+        for text in ["flow", "wing shock", "mach layer flow", "missing"] {
+            let query = Query::parse(text, &inverted_index);
+            assert_eq!(
+                score_with_hash_map(&query, &inverted_index, 25),
+                score_bm25(&query, &inverted_index, 25),
+                "{}",
+                text
+            );
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use crate::{
+    error::Result,
+    evaluation::precision_calculator::get_relevance_set,
+    querying::{query::Query, score::{idf, score}},
+    types::{DocId, InvertedIndex},
+};
+
+/// Number of tf-idf-heaviest terms per relevant document that are checked
+/// against the query for vocabulary mismatches.
+pub const MISMATCH_TERMS_PER_DOC: usize = 10;
+
+/// Where one relevant document ended up for a query and which query terms
+/// it contains.
+#[derive(Debug, Clone)]
+pub struct RelevantDocAnalysis {
+    pub doc_id: DocId,
+    pub rank: Option<usize>,
+    pub score: Option<f32>,
+    pub matched: Vec<String>,
+    pub missed: Vec<String>,
+}
+
+/// A term that ranks among the top terms of relevant documents but is not
+/// part of the query.
+#[derive(Debug, Clone)]
+pub struct MismatchHint {
+    pub term: String,
+    pub doc_count: usize,
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryAnalysis {
+    pub query: Query,
+    pub relevant: Vec<RelevantDocAnalysis>,
+    pub hints: Vec<MismatchHint>,
+}
+
+/// Ranks the whole collection for `text` and reports, for every document
+/// judged relevant to `query_id`, its 1-based rank (if retrieved) and the
+/// query terms it matches and misses. Hints list the top tf-idf terms of the
+/// relevant documents that the query lacks, most widespread first.
+pub fn analyze_query(
+    query_id: u32,
+    text: &str,
+    qrels_path: &Path,
+    inverted_index: &InvertedIndex,
+) -> Result<QueryAnalysis> {
+    let query = Query::parse(text, inverted_index);
+    let ranked_results = score(&query, inverted_index, inverted_index.n as usize);
+    let ranks: HashMap<DocId, (usize, f32)> = ranked_results
+        .into_iter()
+        .enumerate()
+        .map(|(rank, (doc_id, score))| (doc_id, (rank + 1, score)))
+        .collect();
+
+    let (relevance_set, _relevant_count) = get_relevance_set(query_id, qrels_path)?;
+    let mut relevant_docs: Vec<DocId> = relevance_set.into_iter().collect();
+    relevant_docs.sort_unstable();
+
+    let query_tokens: HashSet<&str> = query.tokens().collect();
+    let mut relevant = Vec::new();
+    let mut hints: HashMap<&str, (usize, f32)> = HashMap::new();
+
+    for doc_id in relevant_docs {
+        let doc_terms: HashMap<&str, u32> = inverted_index
+            .document_terms(doc_id)
+            .map(|(terms, _doc_length)| terms.into_iter().collect())
+            .unwrap_or_default();
+        let (matched, missed) = query
+            .tokens()
+            .map(str::to_string)
+            .partition(|token| doc_terms.contains_key(token.as_str()));
+
+        let mut weighted: Vec<(&str, f32)> = doc_terms
+            .iter()
+            .filter_map(|(&token, &tf)| {
+                let df = inverted_index.dictionary.get(token)?.df();
+                Some((token, tf as f32 * idf(df, inverted_index.n)))
+            })
+            .filter(|(_token, weight)| *weight > 0.0)
+            .collect();
+        weighted.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        for (token, weight) in weighted.into_iter().take(MISMATCH_TERMS_PER_DOC) {
+            if !query_tokens.contains(token) {
+                let hint = hints.entry(token).or_default();
+                hint.0 += 1;
+                hint.1 += weight;
+            }
+        }
+
+        let (rank, score) = ranks.get(&doc_id).copied().unzip();
+        relevant.push(RelevantDocAnalysis {
+            doc_id,
+            rank,
+            score,
+            matched,
+            missed,
+        });
+    }
+
+    let mut hints: Vec<MismatchHint> = hints
+        .into_iter()
+        .map(|(term, (doc_count, weight))| MismatchHint {
+            term: term.to_string(),
+            doc_count,
+            weight,
+        })
+        .collect();
+    hints.sort_by(|a, b| {
+        b.doc_count
+            .cmp(&a.doc_count)
+            .then_with(|| b.weight.total_cmp(&a.weight))
+            .then_with(|| a.term.cmp(&b.term))
+    });
+
+    Ok(QueryAnalysis {
+        query,
+        relevant,
+        hints,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preprocessing::tokenizer::{Analyzer, AnalyzerStep},
+        types::{DocIdx, Posting},
+    };
+
+    /// An index over documents `1..` with the given term frequencies and
+    /// matching term vectors.
+    fn index(documents: &[&[(&str, u32)]]) -> InvertedIndex {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        for (doc_idx, terms) in documents.iter().enumerate() {
+            let doc_length = terms.iter().map(|(_term, tf)| tf).sum();
+            inverted_index.add_document(doc_idx as DocId + 1, String::new(), doc_length, 0);
+            for (term, tf) in *terms {
+                inverted_index
+                    .add_posting(term.to_string(), Posting::new(doc_idx as DocIdx, *tf))
+                    .unwrap();
+            }
+        }
+        inverted_index.n = documents.len() as u32;
+        inverted_index.avdl = inverted_index.doc_lengths.iter().sum::<u32>() as f32 / documents.len() as f32;
+        inverted_index.rebuild_vocabulary();
+        for (doc_idx, terms) in documents.iter().enumerate() {
+            let positions = terms.iter().map(|(term, tf)| (term.to_string(), (0..*tf).collect())).collect();
+            inverted_index.set_term_vector(doc_idx as DocIdx, positions);
+        }
+        inverted_index
+    }
+
+    #[test]
+    fn relevant_documents_report_their_rank_and_missing_terms() {
+        let inverted_index = index(&[
+            &[("shock", 3), ("flow", 1)],
+            &[("shock", 1), ("wave", 2)],
+            &[("flow", 2), ("heat", 1)],
+            &[("heat", 2), ("plate", 3)],
+            &[("plate", 1), ("wave", 1)],
+        ]);
+        let path = std::env::temp_dir().join(format!("mini-retrieve-failures-{}", std::process::id()));
+        std::fs::write(&path, "1 2 1\n1 4 2\n1 5 -1\n2 1 1\n").unwrap();
+
+        let analysis = analyze_query(1, "shock flow", &path, &inverted_index).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let ranks: Vec<(DocId, Option<usize>)> = analysis.relevant.iter().map(|doc| (doc.doc_id, doc.rank)).collect();
+        assert_eq!(ranks, vec![(2, Some(3)), (4, None)]);
+        assert_eq!(analysis.relevant[0].matched, vec!["shock"]);
+        assert_eq!(analysis.relevant[0].missed, vec!["flow"]);
+        assert!(analysis.relevant[1].matched.is_empty());
+        assert_eq!(analysis.relevant[1].missed, vec!["shock", "flow"]);
+
+        let hints: Vec<&str> = analysis.hints.iter().map(|hint| hint.term.as_str()).collect();
+        // heat and wave carry the same weight, so the term order decides.
+        assert_eq!(hints, vec!["plate", "heat", "wave"]);
+        assert!(analysis.hints.iter().all(|hint| hint.doc_count == 1));
+    }
+}
@@ -0,0 +1,247 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    error::{Error, Result},
+    evaluation::precision_calculator::get_relevance_set,
+    querying::{
+        features::{normalize_features, FeatureExtractor, FeatureVector, FEATURE_COUNT},
+        language_model::DEFAULT_MU,
+        ltr::LinearModel,
+        query::Query,
+        score::score,
+    },
+    types::InvertedIndex,
+};
+
+/// Number of cross-validation folds when none is given.
+pub const DEFAULT_FOLDS: usize = 5;
+/// Coordinate ascent stops after this many rounds over all features, or
+/// earlier once a round brings no improvement.
+const MAX_ROUNDS: usize = 10;
+/// Changes tried on a single weight, in both directions.
+const STEP_SIZES: [f32; 5] = [0.01, 0.05, 0.1, 0.5, 1.0];
+/// Smallest MAP gain that counts as an improvement.
+const MIN_GAIN: f32 = 1e-6;
+
+/// A judged query prepared for training: the normalised features of its
+/// first-stage candidates, which of them are relevant, and how many
+/// relevant documents the qrels list in total.
+struct TrainingQuery {
+    features: Vec<FeatureVector>,
+    relevant: Vec<bool>,
+    relevant_count: u32,
+}
+
+/// MAP of BM25 and of the model trained on the other folds, measured on the
+/// queries of one fold.
+#[derive(Debug, Clone)]
+pub struct FoldReport {
+    pub fold: usize,
+    pub queries: usize,
+    pub baseline_map: f32,
+    pub map: f32,
+}
+
+/// Outcome of `train_ranker`: the cross-validation folds and the model
+/// trained on all judged queries.
+#[derive(Debug, Clone)]
+pub struct TrainingReport {
+    pub folds: Vec<FoldReport>,
+    pub model: LinearModel,
+    /// Judged queries the model was trained on.
+    pub queries: usize,
+}
+
+/// Trains a linear reranker of the top `candidates` documents of `score`,
+/// the first stage it reranks at search time, by coordinate ascent on MAP
+/// (Metzler and Croft). Queries without relevant
+/// documents are skipped. The judged queries, ordered by id, are dealt into
+/// `folds` folds; every fold is evaluated with a model trained on the others
+/// before the final model is trained on all of them.
+pub fn train_ranker(
+    queries: &HashMap<u32, String>,
+    qrels_path: &Path,
+    inverted_index: &InvertedIndex,
+    candidates: usize,
+    folds: usize,
+) -> Result<TrainingReport> {
+    let mut query_ids: Vec<u32> = queries.keys().copied().collect();
+    query_ids.sort_unstable();
+
+    let mut training_queries: Vec<TrainingQuery> = Vec::new();
+    for query_id in query_ids {
+        let (relevance_set, relevant_count) = get_relevance_set(query_id, qrels_path)?;
+        if relevant_count == 0 {
+            continue;
+        }
+        let query = Query::parse(&queries[&query_id], inverted_index);
+        let ranked_results = score(&query, inverted_index, candidates);
+        let extractor = FeatureExtractor::new(&query, inverted_index, DEFAULT_MU);
+
+        let mut features = Vec::with_capacity(ranked_results.len());
+        let mut relevant = Vec::with_capacity(ranked_results.len());
+        for (doc_id, _score) in ranked_results {
+            if let Some(doc_idx) = inverted_index.doc_idx(doc_id) {
+                features.push(extractor.features(doc_idx));
+                relevant.push(relevance_set.contains(&doc_id));
+            }
+        }
+        normalize_features(&mut features);
+        training_queries.push(TrainingQuery {
+            features,
+            relevant,
+            relevant_count,
+        });
+    }
+
+    if folds < 2 || folds > training_queries.len() {
+        return Err(Error::Config(format!(
+            "cannot split {} judged queries into {} folds",
+            training_queries.len(),
+            folds
+        )));
+    }
+
+    let baseline = LinearModel {
+        candidates,
+        ..LinearModel::default()
+    };
+    let fold_reports = (0..folds)
+        .map(|fold| {
+            let (test, train) = split_fold(&training_queries, folds, fold);
+            let model = coordinate_ascent(&train, candidates);
+            FoldReport {
+                fold: fold + 1,
+                queries: test.len(),
+                baseline_map: mean_average_precision(&baseline, &test),
+                map: mean_average_precision(&model, &test),
+            }
+        })
+        .collect();
+
+    let all: Vec<&TrainingQuery> = training_queries.iter().collect();
+    Ok(TrainingReport {
+        folds: fold_reports,
+        model: coordinate_ascent(&all, candidates),
+        queries: training_queries.len(),
+    })
+}
+
+/// Deals `items` round robin into `folds` folds and returns the items of
+/// `fold` and those of all other folds.
+fn split_fold<T>(items: &[T], folds: usize, fold: usize) -> (Vec<&T>, Vec<&T>) {
+    let (test, train): (Vec<_>, Vec<_>) = items.iter().enumerate().partition(|(i, _item)| i % folds == fold);
+    (
+        test.into_iter().map(|(_i, item)| item).collect(),
+        train.into_iter().map(|(_i, item)| item).collect(),
+    )
+}
+
+/// Starts from BM25 alone and repeatedly moves single weights by the step
+/// that improves the training MAP most. Weights are kept at an L1 norm of 1.
+fn coordinate_ascent(queries: &[&TrainingQuery], candidates: usize) -> LinearModel {
+    let mut model = LinearModel {
+        candidates,
+        ..LinearModel::default()
+    };
+    let mut best_map = mean_average_precision(&model, queries);
+
+    for _ in 0..MAX_ROUNDS {
+        let mut improved = false;
+        for feature in 0..FEATURE_COUNT {
+            for step in STEP_SIZES.iter().flat_map(|step| [*step, -step]) {
+                let mut weights = model.weights;
+                weights[feature] += step;
+                let norm: f32 = weights.iter().map(|weight| weight.abs()).sum();
+                if norm == 0.0 {
+                    continue;
+                }
+                weights.iter_mut().for_each(|weight| *weight /= norm);
+
+                let candidate = LinearModel { weights, candidates };
+                let map = mean_average_precision(&candidate, queries);
+                if map > best_map + MIN_GAIN {
+                    best_map = map;
+                    model = candidate;
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+    model
+}
+
+fn mean_average_precision(model: &LinearModel, queries: &[&TrainingQuery]) -> f32 {
+    let sum: f32 = queries.iter().map(|query| average_precision(model, query)).sum();
+    sum / queries.len().max(1) as f32
+}
+
+/// Average precision of the candidates reranked by `model`. Relevant
+/// documents outside the candidates count as not retrieved.
+fn average_precision(model: &LinearModel, query: &TrainingQuery) -> f32 {
+    let scores: Vec<f32> = query.features.iter().map(|features| model.score(features)).collect();
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+
+    let mut found = 0;
+    let mut running_sum = 0.0;
+    for (rank, candidate) in order.into_iter().enumerate() {
+        if query.relevant[candidate] {
+            found += 1;
+            running_sum += found as f32 / (rank + 1) as f32;
+        }
+    }
+    running_sum / query.relevant_count as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_query_is_tested_in_exactly_one_fold_and_never_trained_on_there() {
+        let query_ids: Vec<u32> = (1..=12).collect();
+        let mut tested: Vec<u32> = Vec::new();
+
+        for fold in 0..5 {
+            let (test, train) = split_fold(&query_ids, 5, fold);
+            assert!(test.iter().all(|query_id| !train.contains(query_id)));
+            assert_eq!(test.len() + train.len(), query_ids.len());
+            assert!(test.len() == 2 || test.len() == 3);
+            tested.extend(test);
+        }
+        tested.sort_unstable();
+        assert_eq!(tested, query_ids);
+    }
+
+    #[test]
+    fn coordinate_ascent_moves_weight_to_a_feature_that_finds_the_relevant_documents() {
+        // BM25 ranks the relevant candidate last, coverage ranks it first.
+        let query = |relevant_at: usize| {
+            let mut features = vec![[0.0; FEATURE_COUNT]; 4];
+            for (i, vector) in features.iter_mut().enumerate() {
+                vector[0] = 1.0 - i as f32 / 3.0;
+                vector[4] = if i == relevant_at { 1.0 } else { 0.0 };
+            }
+            TrainingQuery {
+                features,
+                relevant: (0..4).map(|i| i == relevant_at).collect(),
+                relevant_count: 1,
+            }
+        };
+        let queries = [query(3), query(2)];
+        let queries: Vec<&TrainingQuery> = queries.iter().collect();
+
+        let baseline = LinearModel::default();
+        assert!(mean_average_precision(&baseline, &queries) < 0.5);
+
+        let model = coordinate_ascent(&queries, 4);
+        assert_eq!(mean_average_precision(&model, &queries), 1.0);
+        assert!(model.weights[4] > model.weights[0]);
+        assert!((model.weights.iter().map(|weight| weight.abs()).sum::<f32>() - 1.0).abs() < 1e-5);
+        assert_eq!(model.candidates, 4);
+    }
+}
@@ -1,2 +1,5 @@
 pub mod query_extractor;
 pub mod precision_calculator;
+pub mod benchmark;
+pub mod failure_analysis;
+pub mod ltr_training;
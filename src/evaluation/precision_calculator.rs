@@ -1,40 +1,131 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
 
 use crate::{
-    querying::score::score,
+    error::{Error, Result},
+    querying::{
+        feedback::{expand_query, FeedbackParams},
+        fusion::{score_fused, FusionParams},
+        ltr::LinearModel,
+        query::Query,
+        score::score,
+    },
     types::{DocId, InvertedIndex},
 };
 
-pub fn mean_average_precision(queries: HashMap<u32, String>, inverted_index: &InvertedIndex) -> f32 {
-    let mut running_sum: f32 = 0.;
-    let qry_count = queries.len() as f32;
+/// Relevance judgments used when no other qrels file is given.
+pub const DEFAULT_QRELS_PATH: &str = "in/qrel";
 
-    for query in queries {
-        let scores = score(query.1, inverted_index);
-        running_sum += average_precision(query.0, scores);
-    }
+/// A relevant document found in a ranking, at its 1-based rank.
+#[derive(Debug, Clone)]
+pub struct RelevantHit {
+    pub rank: usize,
+    pub doc_id: DocId,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryEvaluation {
+    pub query_id: u32,
+    pub relevant_count: u32,
+    pub hits: Vec<RelevantHit>,
+    pub average_precision: f32,
+}
+
+/// Average precision of every query (ordered by query id) and their mean.
+#[derive(Debug, Clone)]
+pub struct EvalReport {
+    pub queries: Vec<QueryEvaluation>,
+    pub map: f32,
+}
+
+pub fn mean_average_precision(
+    queries: &HashMap<u32, String>,
+    qrels_path: &Path,
+    inverted_index: &InvertedIndex,
+    feedback: Option<&FeedbackParams>,
+) -> Result<f32> {
+    Ok(evaluate(queries, qrels_path, inverted_index, feedback, None, None)?.map)
+}
 
-    running_sum / qry_count
+/// Ranks the whole collection for every query and measures it against the
+/// judgments in `qrels_path`. Queries without relevant documents count with
+/// an average precision of 0. With `fusion`, the (expanded) query is ranked
+/// by the fused runs instead of `score`. A `reranker` reorders the top of
+/// the `score` ranking it was trained on and cuts it to its candidates; it
+/// cannot be combined with `fusion`.
+pub fn evaluate(
+    queries: &HashMap<u32, String>,
+    qrels_path: &Path,
+    inverted_index: &InvertedIndex,
+    feedback: Option<&FeedbackParams>,
+    fusion: Option<&FusionParams>,
+    reranker: Option<&LinearModel>,
+) -> Result<EvalReport> {
+    if fusion.is_some() && reranker.is_some() {
+        return Err(Error::Config(
+            "a reranker reranks the ranking it was trained on and cannot be combined with fusion".to_string(),
+        ));
+    }
+    let mut query_ids: Vec<u32> = queries.keys().copied().collect();
+    query_ids.sort_unstable();
+
+    let evaluations: Vec<QueryEvaluation> = query_ids
+        .into_iter()
+        .map(|query_id| {
+            let parsed_query = Query::parse(&queries[&query_id], inverted_index);
+            let k = inverted_index.n as usize;
+            let parsed_query = match feedback {
+                Some(params) => expand_query(&parsed_query, inverted_index, params),
+                None => parsed_query,
+            };
+            let ranked_results = match fusion {
+                Some(params) => score_fused(&parsed_query, inverted_index, k, params),
+                None => score(&parsed_query, inverted_index, k),
+            };
+            let ranked_results = match reranker {
+                Some(model) => model.rerank(&parsed_query, &ranked_results, inverted_index),
+                None => ranked_results,
+            };
+            average_precision(query_id, ranked_results, qrels_path)
+        })
+        .collect::<Result<_>>()?;
+
+    let running_sum: f32 = evaluations
+        .iter()
+        .map(|evaluation| evaluation.average_precision)
+        .sum();
+    Ok(EvalReport {
+        map: running_sum / evaluations.len() as f32,
+        queries: evaluations,
+    })
 }
 
-fn average_precision(query: DocId, scores: HashMap<DocId, f32>) -> f32 {
-    let (relevance_set, relevant_count) = get_relevance_set(query);
+fn average_precision(query: DocId, ranked_results: Vec<(DocId, f32)>, qrels_path: &Path) -> Result<QueryEvaluation> {
+    let (relevance_set, relevant_count) = get_relevance_set(query, qrels_path)?;
+    let mut evaluation = QueryEvaluation {
+        query_id: query,
+        relevant_count,
+        hits: Vec::new(),
+        average_precision: 0.,
+    };
 
     if relevant_count == 0 {
-        return 0.;
+        return Ok(evaluation);
     }
 
     let mut found = 0;
     let mut running_sum: f32 = 0.;
 
-    let mut ranked_results: Vec<(DocId, f32)> = scores.into_iter().collect();
-
-    ranked_results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-
-    println!("\nEvaluating Query ID {}:", query);
     for (rank, (doc_id, score)) in ranked_results.iter().enumerate() {
         if relevance_set.contains(doc_id) {
-            println!("      Hit: Rank #{} ID {} with score {}", rank, doc_id, score);
+            evaluation.hits.push(RelevantHit {
+                rank: rank + 1,
+                doc_id: *doc_id,
+                score: *score,
+            });
             found += 1;
             running_sum += found as f32 / (rank + 1) as f32;
 
@@ -44,42 +135,114 @@ fn average_precision(query: DocId, scores: HashMap<DocId, f32>) -> f32 {
         }
     }
 
-    running_sum / relevant_count as f32
+    evaluation.average_precision = running_sum / relevant_count as f32;
+    Ok(evaluation)
 }
 
-fn get_relevance_set(query: DocId) -> (HashSet<DocId>, u32) {
-    let qrel_path = "in/qrel";
-    let file_content = std::fs::read_to_string(qrel_path).expect("Unable to read qrel file");
-
-    let mut relevant_docs: HashSet<DocId> = HashSet::new();
-    let mut relevant_count: u32 = 0;
-    let query_str = query.to_string();
+pub(crate) fn get_relevance_set(query: DocId, qrels_path: &Path) -> Result<(HashSet<DocId>, u32)> {
+    let file_content = std::fs::read_to_string(qrels_path)
+        .map_err(|e| Error::open("qrels", qrels_path, e))?;
 
-    for line in file_content.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
+    let mut grades: HashMap<DocId, i32> = HashMap::new();
+    for (query_id, doc_id, grade) in file_content.lines().filter_map(parse_judgment) {
+        if query_id == query {
+            grades.insert(doc_id, grade);
+        }
+    }
 
-        let (curr_query_id_str, doc_id_str, grade_str) = match parts.as_slice() {
-            [q, d, g] => (q, d, g),
-            _ => continue,
-        };
+    let relevant_docs: HashSet<DocId> = grades
+        .into_iter()
+        .filter(|(_doc_id, grade)| (1..=3).contains(grade))
+        .map(|(doc_id, _grade)| doc_id)
+        .collect();
+    let relevant_count = relevant_docs.len() as u32;
+    Ok((relevant_docs, relevant_count))
+}
 
-        if *curr_query_id_str != query_str {
-            continue;
+/// Writes the judgments of `query` into the qrels file at `qrels_path`. A
+/// document judged before gets its new grade in place of the old line; all
+/// other lines are kept. Returns how many earlier judgments were replaced.
+pub fn merge_judgments(qrels_path: &Path, query: u32, judgments: &[(DocId, i32)]) -> Result<usize> {
+    let existing = match std::fs::read_to_string(qrels_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(Error::open("qrels", qrels_path, e)),
+    };
+
+    let judged: HashSet<DocId> = judgments.iter().map(|(doc_id, _grade)| *doc_id).collect();
+    let mut replaced = 0;
+    let mut content = String::new();
+    for line in existing.lines() {
+        match parse_judgment(line) {
+            Some((query_id, doc_id, _grade)) if query_id == query && judged.contains(&doc_id) => replaced += 1,
+            _ => {
+                content.push_str(line);
+                content.push('\n');
+            }
         }
+    }
+    for (doc_id, grade) in judgments {
+        content.push_str(&format!("{} {} {}\n", query, doc_id, grade));
+    }
 
-        let doc_id: u32 = match doc_id_str.parse::<u32>() {
-            Ok(d) => d,
-            Err(_) => continue,
-        };
-        let grade: i32 = match grade_str.parse::<i32>() {
-            Ok(g) => g,
-            Err(_) => continue,
-        };
-
-        if grade >= 1 && grade <= 3 {
-            relevant_count += 1;
-            relevant_docs.insert(doc_id);
-        }
+    std::fs::write(qrels_path, content)?;
+    Ok(replaced)
+}
+
+/// Parses a `<query> <doc> <grade>` line; other lines are ignored.
+fn parse_judgment(line: &str) -> Option<(u32, DocId, i32)> {
+    match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [query_id, doc_id, grade] => Some((query_id.parse().ok()?, doc_id.parse().ok()?, grade.parse().ok()?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merged_judgments_replace_earlier_grades() {
+        let path = std::env::temp_dir().join(format!("mini-retrieve-qrels-{}", std::process::id()));
+        std::fs::write(&path, "1 10 1\n1 11 2\n2 10 1\n").unwrap();
+
+        assert_eq!(merge_judgments(&path, 1, &[(10, -1), (12, 1)]).unwrap(), 1);
+        assert_eq!(merge_judgments(&path, 1, &[(12, 1)]).unwrap(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "1 11 2\n2 10 1\n1 10 -1\n1 12 1\n"
+        );
+
+        let (relevant, count) = get_relevance_set(1, &path).unwrap();
+        assert_eq!(relevant, HashSet::from([11, 12]));
+        assert_eq!(count, 2);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn duplicate_judgments_count_once() {
+        let path = std::env::temp_dir().join(format!("mini-retrieve-duplicates-{}", std::process::id()));
+        std::fs::write(&path, "1 10 1\n1 10 1\n1 11 1\n1 11 -1\n").unwrap();
+
+        let (relevant, count) = get_relevance_set(1, &path).unwrap();
+        assert_eq!(relevant, HashSet::from([10]));
+        assert_eq!(count, 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rerankers_are_not_combined_with_fusion() {
+        let inverted_index = InvertedIndex::new();
+        let report = evaluate(
+            &HashMap::new(),
+            Path::new("missing.qrel"),
+            &inverted_index,
+            None,
+            Some(&FusionParams::default()),
+            Some(&LinearModel::default()),
+        );
+        assert!(matches!(report, Err(Error::Config(_))));
     }
-    (relevant_docs, relevant_count)
 }
@@ -1,11 +1,19 @@
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::path::Path;
 
+use crate::error::{Error, Result};
 use crate::types::DocId;
 
-pub fn extract_queries(query_path: &str) -> HashMap<DocId, String> {
-    let file = File::open(query_path).expect("Unable to open query file");
+/// Test queries used when no other query file is given.
+pub const DEFAULT_QUERIES_PATH: &str = "in/documents.qry";
+
+/// Reads the queries of a Cranfield query file. As in the Cranfield qrels,
+/// queries are numbered by their position in the file (from 1), not by the
+/// id on their `.I` line.
+pub fn extract_queries(query_path: &Path) -> Result<HashMap<DocId, String>> {
+    let file = File::open(query_path).map_err(|e| Error::open("queries", query_path, e))?;
     let reader = BufReader::new(file);
 
     let mut queries: HashMap<DocId, String> = HashMap::new();
@@ -14,22 +22,41 @@ pub fn extract_queries(query_path: &str) -> HashMap<DocId, String> {
     let mut is_first_query = true;
 
     for line in reader.lines() {
-        let parsed_line = line.unwrap_or(String::new());
+        let parsed_line = line?;
         if parsed_line.starts_with(".I") {
-            current_query_id += 1;
-
             if !is_first_query {
                 queries.insert(current_query_id, current_query.trim().to_string());
                 current_query.clear();
             } else {
                 is_first_query = false;
             }
+
+            current_query_id += 1;
         } else if !parsed_line.starts_with(".W") {
-            current_query.push_str(" ");
+            current_query.push(' ');
             current_query.push_str(&parsed_line);
         }
     }
 
     queries.insert(current_query_id, current_query.trim().to_string());
-    queries
+    Ok(queries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_are_numbered_by_their_position() {
+        let path = std::env::temp_dir().join(format!("mini-retrieve-queries-{}.qry", std::process::id()));
+        std::fs::write(&path, ".I 001\n.W\nfirst query\n.I 004\n.W\nsecond\nquery\n.I 009\n.W\nthird\n").unwrap();
+
+        let queries = extract_queries(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(queries.len(), 3);
+        assert_eq!(queries[&1], "first query");
+        assert_eq!(queries[&2], "second query");
+        assert_eq!(queries[&3], "third");
+    }
 }
@@ -0,0 +1,265 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    engine::{Hit, SearchEngine, SearchOptions, SearchResults},
+    error::{Error, Result},
+    querying::fusion::min_max,
+};
+
+/// Number of hits retrieved from every collection; normalisation statistics
+/// are taken over these before the merged list is cut to `k`.
+pub const FEDERATION_DEPTH: usize = 100;
+/// Belief of a term that does not occur in a collection (CORI's `b`).
+const CORI_DEFAULT_BELIEF: f32 = 0.4;
+
+/// How the scores of the per-collection result lists are made comparable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// `(s - min) / (max - min)` per list.
+    MinMax,
+    /// `(s - mean) / stddev` per list.
+    ZScore,
+    /// Min-max document scores combined with a CORI collection weight.
+    Cori,
+}
+
+impl Normalization {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "minmax" | "min-max" => Ok(Normalization::MinMax),
+            "zscore" | "z-score" => Ok(Normalization::ZScore),
+            "cori" => Ok(Normalization::Cori),
+            _ => Err(Error::Config(format!(
+                "unknown normalisation '{}', expected minmax, zscore or cori",
+                name
+            ))),
+        }
+    }
+}
+
+/// A hit of the merged list. `hit.score` and `hit.rank` refer to the merged
+/// ranking; `raw_score` is the score within the source collection.
+#[derive(Debug, Clone)]
+pub struct FederatedHit {
+    pub source: String,
+    pub raw_score: f32,
+    pub hit: Hit,
+}
+
+#[derive(Debug, Clone)]
+pub struct FederatedResults {
+    pub normalization: Normalization,
+    pub hits: Vec<FederatedHit>,
+    /// Normalised CORI weight of every collection; empty for the other
+    /// normalisations.
+    pub collection_weights: Vec<(String, f32)>,
+    /// Time spent searching and merging, without snippet generation.
+    pub took: Duration,
+}
+
+/// Sends `text` to every named collection, normalises each result list and
+/// merges them into a single ranking of `options.k` hits.
+pub fn federated_search(
+    sources: &[(&str, &SearchEngine)],
+    text: &str,
+    options: &SearchOptions,
+    normalization: Normalization,
+) -> FederatedResults {
+    let start_time = Instant::now();
+    let source_options = SearchOptions {
+        k: options.k.max(FEDERATION_DEPTH),
+        snippets: false,
+        ..options.clone()
+    };
+    let results: Vec<SearchResults> = sources
+        .iter()
+        .map(|(_, engine)| engine.search(text, &source_options))
+        .collect();
+
+    let weights = match normalization {
+        Normalization::Cori => cori_weights(sources, &results),
+        _ => Vec::new(),
+    };
+
+    let mut merged: Vec<(usize, &Hit, f32)> = Vec::new();
+    for (source, result) in results.iter().enumerate() {
+        let scores: Vec<f32> = result.hits.iter().map(|hit| hit.score).collect();
+        let normalized = match normalization {
+            Normalization::MinMax => min_max(&scores),
+            Normalization::ZScore => z_scores(&scores),
+            Normalization::Cori => min_max(&scores)
+                .into_iter()
+                .map(|score| (score + CORI_DEFAULT_BELIEF * score * weights[source]) / 1.4)
+                .collect(),
+        };
+        merged.extend(result.hits.iter().zip(normalized).map(|(hit, score)| (source, hit, score)));
+    }
+    merged.sort_by(|a, b| {
+        b.2.total_cmp(&a.2)
+            .then(a.0.cmp(&b.0))
+            .then(a.1.rank.cmp(&b.1.rank))
+    });
+    merged.truncate(options.k);
+    let took = start_time.elapsed();
+
+    let hits = merged
+        .into_iter()
+        .enumerate()
+        .map(|(rank, (source, hit, score))| {
+            let (name, engine) = sources[source];
+            let result = &results[source];
+            let snippet = if options.snippets {
+                engine.snippet(hit.doc_id, result.reformulated.as_ref().unwrap_or(&result.query))
+            } else {
+                None
+            };
+            FederatedHit {
+                source: name.to_string(),
+                raw_score: hit.score,
+                hit: Hit {
+                    rank: rank + 1,
+                    score,
+                    snippet,
+                    ..hit.clone()
+                },
+            }
+        })
+        .collect();
+
+    FederatedResults {
+        normalization,
+        hits,
+        collection_weights: sources
+            .iter()
+            .zip(weights)
+            .map(|((name, _), weight)| (name.to_string(), weight))
+            .collect(),
+        took,
+    }
+}
+
+/// Standardises scores to zero mean and unit variance. A list whose scores
+/// are all equal maps to 0.
+fn z_scores(scores: &[f32]) -> Vec<f32> {
+    let n = scores.len().max(1) as f32;
+    let mean = scores.iter().sum::<f32>() / n;
+    let stddev = (scores.iter().map(|score| (score - mean).powi(2)).sum::<f32>() / n).sqrt();
+    scores
+        .iter()
+        .map(|score| if stddev > 0.0 { (score - mean) / stddev } else { 0.0 })
+        .collect()
+}
+
+/// CORI collection ranking (Callan et al.): the belief `C` of every
+/// collection in the query, averaged over the query terms, is normalised to
+/// `(C - Cmin) / (Cmax - Cmin)` with the beliefs the query would reach if
+/// every term had `T = 0` (`Cmin`, the default belief) or `T = 1` (`Cmax`)
+/// in a collection. The bounds do not depend on the other collections'
+/// beliefs, so a collection only gets 0 if it contains none of the terms.
+fn cori_weights(sources: &[(&str, &SearchEngine)], results: &[SearchResults]) -> Vec<f32> {
+    let collection_words: Vec<f32> = sources
+        .iter()
+        .map(|(_, engine)| {
+            let index = engine.index();
+            index.n as f32 * index.avdl
+        })
+        .collect();
+    let average_words = collection_words.iter().sum::<f32>() / sources.len().max(1) as f32;
+    let collections = sources.len() as f32;
+
+    sources
+        .iter()
+        .zip(results)
+        .zip(&collection_words)
+        .map(|(((_, engine), result), &words)| {
+            let query = result.reformulated.as_ref().unwrap_or(&result.query);
+            let (mut belief, mut max_belief, mut terms) = (0.0, 0.0, 0);
+            for token in query.tokens() {
+                let df = engine.index().dictionary.get(token).map_or(0, |postings| postings.df()) as f32;
+                let cf = sources
+                    .iter()
+                    .filter(|(_, other)| other.index().dictionary.contains_key(token))
+                    .count()
+                    .max(1) as f32;
+                let t = df / (df + 50.0 + 150.0 * words / average_words.max(1.0));
+                let i = ((collections + 0.5) / cf).ln() / (collections + 1.0).ln();
+                belief += CORI_DEFAULT_BELIEF + (1.0 - CORI_DEFAULT_BELIEF) * t * i;
+                max_belief += CORI_DEFAULT_BELIEF + (1.0 - CORI_DEFAULT_BELIEF) * i;
+                terms += 1;
+            }
+            let min_belief = CORI_DEFAULT_BELIEF * terms as f32;
+            if max_belief > min_belief {
+                (belief - min_belief) / (max_belief - min_belief)
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preprocessing::tokenizer::{Analyzer, AnalyzerStep},
+        types::{DocId, DocIdx, InvertedIndex, Posting},
+    };
+
+    /// An engine over documents holding the given terms once each.
+    fn engine(documents: &[&[&str]]) -> SearchEngine {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        for (doc_idx, terms) in documents.iter().enumerate() {
+            inverted_index.add_document(doc_idx as DocId + 1, String::new(), terms.len() as u32, 0);
+            for term in *terms {
+                inverted_index
+                    .add_posting(term.to_string(), Posting::new(doc_idx as DocIdx, 1))
+                    .unwrap();
+            }
+        }
+        inverted_index.n = documents.len() as u32;
+        inverted_index.avdl = inverted_index.doc_lengths.iter().sum::<u32>() as f32 / documents.len() as f32;
+        inverted_index.rebuild_vocabulary();
+        SearchEngine::new(inverted_index)
+    }
+
+    #[test]
+    fn z_scores_of_constant_and_single_hit_lists_are_zero() {
+        assert_eq!(z_scores(&[3.0, 3.0, 3.0]), vec![0.0, 0.0, 0.0]);
+        assert_eq!(z_scores(&[7.5]), vec![0.0]);
+        assert!(z_scores(&[]).is_empty());
+
+        let scores = z_scores(&[1.0, 2.0, 3.0]);
+        assert!((scores.iter().sum::<f32>()).abs() < 1e-6);
+        assert!((scores[2] - 1.224_745).abs() < 1e-5);
+    }
+
+    #[test]
+    fn min_max_of_constant_and_single_hit_lists_is_one() {
+        assert_eq!(min_max(&[3.0, 3.0]), vec![1.0, 1.0]);
+        assert_eq!(min_max(&[7.5]), vec![1.0]);
+        assert!(min_max(&[]).is_empty());
+        assert_eq!(min_max(&[1.0, 3.0, 2.0]), vec![0.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn cori_keeps_the_weaker_of_two_collections() {
+        let strong = engine(&[&["flow"], &["flow", "wing"], &["flow"], &["wing"]]);
+        let weak = engine(&[&["flow"], &["wing"], &["wing"], &["wing"]]);
+        let empty = engine(&[&["wing"], &["wing"]]);
+        let sources = [("strong", &strong), ("weak", &weak), ("empty", &empty)];
+        let results: Vec<SearchResults> = sources
+            .iter()
+            .map(|(_, engine)| engine.search("flow", &SearchOptions::default()))
+            .collect();
+
+        let weights = cori_weights(&sources, &results);
+        assert!(weights[0] > weights[1]);
+        assert!(weights[1] > 0.0);
+        assert_eq!(weights[2], 0.0);
+        assert!(weights.iter().all(|weight| (0.0..=1.0).contains(weight)));
+
+        let pair = cori_weights(&sources[..2], &results[..2]);
+        assert!(pair[0] > pair[1] && pair[1] > 0.0);
+    }
+}
@@ -3,5 +3,13 @@ pub mod querying;
 pub mod utils;
 pub mod commander;
 pub mod evaluation;
+pub mod cli;
+pub mod engine;
+pub mod federated;
+pub mod config;
+pub mod server;
+pub mod error;
+
+pub use error::{Error, Result};
 
 pub mod types;
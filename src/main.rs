@@ -1,20 +1,48 @@
-use std::time::Instant;
-
 use mini_retrieve::{
-    commander::commander, querying::score::score, types::InvertedIndex, utils::display_top_results
+    cli,
+    commander::commander,
+    config::Config,
+    engine::SearchOptions,
+    federated::federated_search,
+    preprocessing::tokenizer::{load_stopwords, Analyzer, AnalyzerStep},
+    types::{LastQuery, Session},
+    utils::{display_error, display_federated_results, display_search_results},
 };
 
+const RESULTS_SHOWN: usize = 10;
+
 fn main() {
-    let mut inverted_index = InvertedIndex::new();
-    commander("reindex".to_string(), Vec::new(), &mut inverted_index);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args));
+    }
+
+    let mut config = Config::load(None).unwrap_or_else(|e| {
+        display_error(&e);
+        println!("ℹ️ Continuing with the default configuration.");
+        Config::default()
+    });
+    if config.analyzer.has_step(AnalyzerStep::Stopwords) {
+        if let Err(e) = load_stopwords(&config.stopwords) {
+            display_error(&e);
+            println!("ℹ️ Continuing without stopword removal.");
+            let steps = config.analyzer.steps().iter().copied().filter(|step| *step != AnalyzerStep::Stopwords);
+            config.analyzer = Analyzer::new(steps.collect());
+        }
+    }
+
+    let mut session = Session::new(config);
+    commander("reindex".to_string(), Vec::new(), &mut session);
 
     loop {
-        println!("");
+        println!();
         println!("🔍 Enter your Query:");
         let query = {
             let mut line = String::new();
-            std::io::stdin().read_line(&mut line).unwrap();
-            line.trim().to_string()
+            match std::io::stdin().read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => line.trim().to_string(),
+            }
         };
         if query.starts_with("::") {
             let command_line = query.strip_prefix("::").unwrap_or("help");
@@ -22,21 +50,52 @@ fn main() {
             let command = parts.next().unwrap_or("help");
             let args: Vec<&str> = parts.collect();
 
-            let should_continue = commander(command.to_lowercase(), args, &mut inverted_index);
+            let should_continue = commander(command.to_lowercase(), args, &mut session);
             if !should_continue {
                 break;
             }
             continue;
         }
-        println!("⏳ Searching...");
-        let start_time = Instant::now();
+        run_query(&query, &mut session);
+    }
+}
 
-        let scores = score(query, &inverted_index);
+fn run_query(query: &str, session: &mut Session) {
+    println!("⏳ Searching...");
 
-        let end_time = Instant::now();
-        let duration = end_time.duration_since(start_time);
+    let options = SearchOptions {
+        k: RESULTS_SHOWN,
+        autocorrect: session.autocorrect,
+        feedback: session.feedback,
+        fusion: session.fusion.clone(),
+        reranker: session.reranker.clone(),
+        ..SearchOptions::default()
+    };
+    if !session.cross_index {
+        let results = session.collection().engine.search(query, &options);
+        session.last_query = Some(LastQuery::new(query.to_string(), results.query.clone()));
+        display_search_results(&results, session.autocorrect);
+        return;
+    }
 
-        display_top_results(scores, &inverted_index);
-        println!("({:?})", duration);
+    if let Some(normalization) = session.federated {
+        let sources: Vec<(&str, &_)> = session
+            .collections
+            .iter()
+            .map(|(name, collection)| (name.as_str(), &collection.engine))
+            .collect();
+        let results = federated_search(&sources, query, &options, normalization);
+        session.last_query = None;
+        display_federated_results(&results);
+        return;
+    }
+
+    for (name, collection) in &session.collections {
+        println!("\n📚 Index '{}':", name);
+        let results = collection.engine.search(query, &options);
+        if *name == session.active {
+            session.last_query = Some(LastQuery::new(query.to_string(), results.query.clone()));
+        }
+        display_search_results(&results, session.autocorrect);
     }
 }
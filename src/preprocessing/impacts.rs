@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    querying::score::{idf, score_component},
+    types::{DocIdx, ImpactIndex, ImpactSegment, InvertedIndex},
+};
+
+const IMPACT_LEVELS: f32 = u8::MAX as f32;
+
+/// Computes the BM25 contribution of every posting, quantizes it linearly to
+/// `1..=255` against the largest contribution in the index and regroups each
+/// postings list into segments of descending impact.
+pub fn build_impact_index(inverted_index: &InvertedIndex, budget: f32) -> ImpactIndex {
+    let n = inverted_index.n;
+    let avdl = inverted_index.avdl;
+    let doc_lengths = &inverted_index.doc_lengths;
+
+    let posting_score = |idf_j: f32, doc_idx: DocIdx, tf: u32| {
+        let l_di = doc_lengths[doc_idx as usize] as f32;
+        idf_j * score_component(tf as f32, l_di, avdl, inverted_index.bm25)
+    };
+
+    let max_score = inverted_index
+        .dictionary
+        .values()
+        .flat_map(|postings| {
+            let idf_j = idf(postings.df(), n);
+            postings
+                .iter()
+                .map(move |posting| posting_score(idf_j, posting.doc_idx, posting.tf))
+        })
+        .fold(0.0_f32, f32::max);
+
+    let scale = if max_score > 0.0 {
+        max_score / IMPACT_LEVELS
+    } else {
+        1.0
+    };
+
+    let mut impact_index = ImpactIndex {
+        scale,
+        budget,
+        ..ImpactIndex::default()
+    };
+
+    for (token, postings) in &inverted_index.dictionary {
+        let idf_j = idf(postings.df(), n);
+
+        let mut by_impact: BTreeMap<u8, Vec<DocIdx>> = BTreeMap::new();
+        for posting in postings {
+            let score = posting_score(idf_j, posting.doc_idx, posting.tf);
+            if score <= 0.0 {
+                continue;
+            }
+            let impact = (score / scale).ceil().clamp(1.0, IMPACT_LEVELS) as u8;
+            by_impact.entry(impact).or_default().push(posting.doc_idx);
+        }
+
+        if by_impact.is_empty() {
+            continue;
+        }
+
+        let segments = by_impact
+            .into_iter()
+            .rev()
+            .map(|(impact, docs)| ImpactSegment { impact, docs })
+            .collect();
+        impact_index.segments.insert(token.clone(), segments);
+    }
+
+    impact_index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Posting;
+
+    /// Ten documents of equal length holding `a` 1 to 10 times, filled up
+    /// with `z`, and twenty documents without either term.
+    fn graded_index() -> InvertedIndex {
+        let mut inverted_index = InvertedIndex::new();
+        for doc_idx in 0..30 {
+            inverted_index.add_document(doc_idx + 1, String::new(), 11, 0);
+            let terms = if doc_idx < 10 {
+                vec![("a", doc_idx + 1), ("z", 10 - doc_idx)]
+            } else {
+                vec![("other", 11)]
+            };
+            for (term, tf) in terms {
+                inverted_index.add_posting(term.to_string(), Posting::new(doc_idx, tf)).unwrap();
+            }
+        }
+        inverted_index.n = 30;
+        inverted_index.avdl = 11.0;
+        inverted_index
+    }
+
+    #[test]
+    fn impacts_are_quantized_to_eight_bits_in_descending_segments() {
+        let inverted_index = graded_index();
+        let impacts = build_impact_index(&inverted_index, 1.0);
+
+        let all: Vec<u8> = impacts
+            .segments
+            .values()
+            .flat_map(|segments| segments.iter().map(|segment| segment.impact))
+            .collect();
+        assert!(all.iter().all(|&impact| impact >= 1));
+        assert_eq!(all.iter().max(), Some(&u8::MAX));
+
+        for segments in impacts.segments.values() {
+            assert!(segments.windows(2).all(|pair| pair[0].impact > pair[1].impact));
+        }
+        // Terms in two thirds of the collection have no positive idf.
+        assert!(!impacts.segments.contains_key("other"));
+
+        let a = &inverted_index.dictionary["a"];
+        let idf_a = idf(a.df(), inverted_index.n);
+        for posting in a {
+            let exact = idf_a * score_component(posting.tf as f32, 11.0, 11.0, inverted_index.bm25);
+            let segment = impacts.segments["a"]
+                .iter()
+                .find(|segment| segment.docs.contains(&posting.doc_idx))
+                .unwrap();
+            let quantized = segment.impact as f32 * impacts.scale;
+            assert!(quantized >= exact && quantized - exact <= impacts.scale);
+        }
+    }
+}
@@ -1,84 +1,97 @@
-use std::{collections::HashMap, fs, path::Path};
+use std::{fs, path::{Path, PathBuf}};
 
 use crate::{
-    types::{DocId, InvertedIndex, Posting, Term},
-    utils::{calculate_document_tf, extract_title_from_content},
+    error::{Error, Result},
+    preprocessing::tokenizer::Analyzer,
+    types::{DocId, DocIdx, InvertedIndex, Posting, Term},
+    utils::{calculate_document_positions, extract_title_from_content},
 };
 
-pub fn create_inverted_index() -> InvertedIndex {
-    println!("> Creating inverted index");
+/// The token positions of one document, grouped by term.
+type TermPositions = Vec<(Term, Vec<u32>)>;
 
+/// Builds the index over the split documents in `documents_dir`, analysing
+/// them with `analyzer`.
+pub fn create_inverted_index(documents_dir: &Path, analyzer: Analyzer) -> Result<InvertedIndex> {
     let mut inverted_index = InvertedIndex::new();
-    println!("  > Inverted index created");
+    inverted_index.documents_dir = documents_dir.to_path_buf();
+    inverted_index.analyzer = analyzer;
 
-    let mut doc_lengths: HashMap<DocId, u32> = HashMap::new();
-    let mut doc_titles: HashMap<DocId, String> = HashMap::new();
-    let mut doc_count: u32 = 0;
     let mut term_count: u32 = 0;
+    let mut document_positions: Vec<(DocIdx, TermPositions)> = Vec::new();
 
-    println!("   > Filling inverted index");
-    let documents_dir = Path::new("out/documents");
-    match fs::read_dir(documents_dir) {
-        Ok(entries) => {
-            for entry in entries {
-                let entry = entry.unwrap_or_else(|_e| {
-                    panic!("ERROR: Could not read document.");
-                });
-
-                let path = entry.path();
-                if path.is_file() && path.file_name().is_some() {
-                    let filename = path
-                        .file_name()
-                        .and_then(|name| name.to_str())
-                        .unwrap_or("");
-
-                    if !filename.starts_with("doc") {
-                        continue;
-                    }
-
-                    doc_count += 1;
-                    let doc_id = extract_doc_id(filename);
-
-                    let content = fs::read_to_string(&path)
-                        .unwrap_or_else(|_e| panic!("ERROR: Could not read document."));
-
-                    let title = extract_title_from_content(&content);
-                    doc_titles.insert(doc_id, title);
-
-                    let (tf_map, doc_length) = calculate_document_tf(&content);
-                    term_count += doc_length;
-                    doc_lengths.insert(doc_id, doc_length);
-                    update_inverted_index(&mut inverted_index, doc_id, tf_map);
-                }
-            }
-        }
-        Err(e) => {
-            panic!("FATAL: Failed to read documents directory. Error: {}", e);
-        }
+    let documents = collect_documents(documents_dir)?;
+
+    for (doc_id, path) in documents {
+        let content = fs::read_to_string(&path)?;
+
+        let title = extract_title_from_content(&content);
+        let (positions, doc_length, title_length) = calculate_document_positions(&content, &inverted_index.analyzer);
+        term_count += doc_length;
+
+        let doc_idx = inverted_index.add_document(doc_id, title, doc_length, title_length);
+        let positions: TermPositions = positions.into_iter().collect();
+        update_inverted_index(&mut inverted_index, doc_idx, &positions)
+            .map_err(|_posting| Error::parse(&path, None, "document was indexed twice"))?;
+        document_positions.push((doc_idx, positions));
     }
 
+    let doc_count = inverted_index.doc_ids.len() as u32;
     inverted_index.n = doc_count;
     inverted_index.avdl = term_count as f32 / doc_count as f32;
-    inverted_index.doc_lengths = doc_lengths;
-    inverted_index.doc_titles = doc_titles;
-    println!("  > Inverted index filled");
+    inverted_index.rebuild_vocabulary();
+
+    for (doc_idx, positions) in document_positions {
+        inverted_index.set_term_vector(doc_idx, positions);
+    }
+
+    Ok(inverted_index)
+}
+
+/// Lists the split documents sorted by `DocId`, so that dense indices are
+/// handed out in id order and every postings list ends up sorted.
+fn collect_documents(documents_dir: &Path) -> Result<Vec<(DocId, PathBuf)>> {
+    let entries = fs::read_dir(documents_dir)
+        .map_err(|e| Error::open("documents directory", documents_dir, e))?;
+
+    let mut documents: Vec<(DocId, PathBuf)> = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+
+        let path = entry.path();
+        if path.is_file() && path.file_name().is_some() {
+            let filename = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("");
+
+            if !filename.starts_with("doc") {
+                continue;
+            }
+
+            let doc_id = extract_doc_id(filename)
+                .ok_or_else(|| Error::parse(&path, None, "file name does not end in a numeric DocId"))?;
+            documents.push((doc_id, path));
+        }
+    }
 
-    inverted_index
+    documents.sort_by_key(|(doc_id, _path)| *doc_id);
+    Ok(documents)
 }
 
-fn extract_doc_id(filename: &str) -> DocId {
+fn extract_doc_id(filename: &str) -> Option<DocId> {
     filename
         .strip_prefix("doc")
         .and_then(|s| s.parse::<DocId>().ok())
-        .unwrap_or_else(|| panic!("FATAL: Could not parse DocId from filename: {:?}", filename))
 }
 
 fn update_inverted_index(
     inverted_index: &mut InvertedIndex,
-    doc_id: DocId,
-    tf_map: HashMap<Term, u32>,
-) {
-    tf_map.into_iter().for_each(|(token, tf)| {
-        inverted_index.add_posting(token, Posting::new(doc_id, tf));
-    });
+    doc_idx: DocIdx,
+    positions: &[(Term, Vec<u32>)],
+) -> std::result::Result<(), Posting> {
+    positions.iter().try_for_each(|(token, positions)| {
+        let tf = positions.len() as u32;
+        inverted_index.add_posting(token.clone(), Posting::new(doc_idx, tf))
+    })
 }
@@ -1,3 +1,6 @@
 pub mod splitter;
 pub mod indexer;
 pub mod tokenizer;
+pub mod impacts;
+pub mod pruning;
+pub mod persistence;
@@ -0,0 +1,315 @@
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    error::{Error, Result},
+    preprocessing::tokenizer::Analyzer,
+    types::{InvertedIndex, Posting, TermVectorEntry},
+};
+
+/// Index directory used when none is given.
+pub const DEFAULT_INDEX_DIR: &str = "out";
+/// Name of the index file inside an index directory.
+pub const INDEX_FILE_NAME: &str = "index.txt";
+/// Name of the directory holding the split documents inside an index directory.
+pub const DOCUMENTS_DIR_NAME: &str = "documents";
+const HEADER: &str = "mini-retrieve index v2";
+/// Header of indexes written before title lengths were stored.
+const HEADER_V1: &str = "mini-retrieve index v1";
+
+/// Writes `inverted_index` to `<index_dir>/index.txt` as plain text: a
+/// header, the analyzer chain and its stopwords, one line per document (`doc_id`, length, title
+/// length, title), one line per
+/// term with its `doc_idx:tf` postings (the term followed by its unpruned df
+/// if the list was pruned) and one line per term vector with
+/// `term_id:tf:positions` entries. Impacts are not stored.
+pub fn save_index(inverted_index: &InvertedIndex, index_dir: &Path) -> Result<()> {
+    fs::create_dir_all(index_dir)?;
+    let mut out = BufWriter::new(File::create(index_dir.join(INDEX_FILE_NAME))?);
+
+    writeln!(out, "{}", HEADER)?;
+    writeln!(out, "analyzer {}", inverted_index.analyzer)?;
+    let mut stopwords: Vec<&String> = inverted_index.analyzer.stopwords().iter().collect();
+    stopwords.sort();
+    writeln!(out, "stopwords {}", stopwords.into_iter().cloned().collect::<Vec<_>>().join(" "))?;
+    writeln!(out, "documents {} {}", inverted_index.doc_ids.len(), inverted_index.avdl)?;
+    for (doc_idx, doc_id) in inverted_index.doc_ids.iter().enumerate() {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            doc_id,
+            inverted_index.doc_lengths[doc_idx],
+            inverted_index.title_lengths[doc_idx],
+            inverted_index.doc_titles[doc_idx].replace(['\t', '\n'], " ")
+        )?;
+    }
+
+    writeln!(out, "terms {}", inverted_index.dictionary.len())?;
+    for (term, postings) in &inverted_index.dictionary {
+        write!(out, "{}", term)?;
+        if postings.df() as usize != postings.len() {
+            write!(out, " {}", postings.df())?;
+        }
+        for (i, posting) in postings.iter().enumerate() {
+            let separator = if i == 0 { '\t' } else { ' ' };
+            write!(out, "{}{}:{}", separator, posting.doc_idx, posting.tf)?;
+        }
+        writeln!(out)?;
+    }
+
+    writeln!(out, "vectors {}", inverted_index.term_vectors.len())?;
+    for term_vector in &inverted_index.term_vectors {
+        let entries: Vec<String> = term_vector
+            .iter()
+            .map(|entry| {
+                let positions: Vec<String> = entry.positions.iter().map(u32::to_string).collect();
+                format!("{}:{}:{}", entry.term_id, entry.tf, positions.join(","))
+            })
+            .collect();
+        writeln!(out, "{}", entries.join(" "))?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Copies the split documents of `inverted_index` to `<index_dir>/documents`,
+/// so that an index saved to `index_dir` can be loaded from there.
+pub fn copy_documents(inverted_index: &InvertedIndex, index_dir: &Path) -> Result<()> {
+    let target = index_dir.join(DOCUMENTS_DIR_NAME);
+    if target == inverted_index.documents_dir {
+        return Ok(());
+    }
+    fs::create_dir_all(&target)?;
+
+    let source = &inverted_index.documents_dir;
+    let entries = fs::read_dir(source).map_err(|e| Error::open("documents directory", source, e))?;
+    for entry in entries {
+        let path = entry?.path();
+        if let (true, Some(file_name)) = (path.is_file(), path.file_name()) {
+            fs::copy(&path, target.join(file_name))?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads an index written by `save_index`. The split documents are expected
+/// in `<index_dir>/documents`. Indexes without an analyzer line were built
+/// with the default chain, those without a stopwords line leave the list
+/// empty; v1 indexes get title lengths estimated from the stored title.
+pub fn load_index(index_dir: &Path) -> Result<InvertedIndex> {
+    let path = index_dir.join(INDEX_FILE_NAME);
+    let file = File::open(&path).map_err(|e| Error::open("index", &path, e))?;
+    let path = path.as_path();
+    let mut lines = BufReader::new(file).lines().enumerate();
+    let mut next_line = || -> Result<(usize, String)> {
+        match lines.next() {
+            Some((number, line)) => Ok((number + 1, line?)),
+            None => Err(Error::parse(path, None, "unexpected end of file")),
+        }
+    };
+
+    let (number, header) = next_line()?;
+    let with_title_lengths = match header.as_str() {
+        HEADER => true,
+        HEADER_V1 => false,
+        _ => return Err(invalid_data(path, number, "not a mini-retrieve index")),
+    };
+
+    let mut inverted_index = InvertedIndex::new();
+    inverted_index.documents_dir = index_dir.join(DOCUMENTS_DIR_NAME);
+
+    let (mut number, mut line) = next_line()?;
+    if let Some(spec) = line.strip_prefix("analyzer ") {
+        inverted_index.analyzer = Analyzer::parse(spec).map_err(|e| invalid_data(path, number, &e.to_string()))?;
+        (number, line) = next_line()?;
+    }
+    let stopwords_line = line.strip_prefix("stopwords").filter(|rest| rest.is_empty() || rest.starts_with(' '));
+    if let Some(stopwords) = stopwords_line {
+        let stopwords = stopwords.split_whitespace().map(str::to_string).collect();
+        inverted_index.analyzer = std::mem::take(&mut inverted_index.analyzer).with_stopwords(stopwords);
+        (number, line) = next_line()?;
+    }
+    let (doc_count, avdl) = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["documents", count, avdl] => (
+            parse_field::<usize>(path, number, count)?,
+            parse_field::<f32>(path, number, avdl)?,
+        ),
+        _ => return Err(invalid_data(path, number, "expected 'documents <count> <avdl>'")),
+    };
+    for _ in 0..doc_count {
+        let (number, line) = next_line()?;
+        let mut fields = line.splitn(if with_title_lengths { 4 } else { 3 }, '\t');
+        let doc_id = parse_field(path, number, fields.next().unwrap_or(""))?;
+        let length = parse_field(path, number, fields.next().unwrap_or(""))?;
+        let title_length: Option<u32> = if with_title_lengths {
+            Some(parse_field(path, number, fields.next().unwrap_or(""))?)
+        } else {
+            None
+        };
+        let title = fields.next().unwrap_or("").to_string();
+        // v1 indexes only stored the first title line; its tokens are the
+        // best estimate of the title length left.
+        let title_length = title_length.unwrap_or_else(|| {
+            let tokens = title.split_whitespace().filter_map(|word| inverted_index.analyzer.analyze(word));
+            (tokens.count() as u32).min(length)
+        });
+        inverted_index.add_document(doc_id, title, length, title_length);
+    }
+    inverted_index.n = doc_count as u32;
+    inverted_index.avdl = avdl;
+
+    let (number, line) = next_line()?;
+    let term_count = section_count(path, number, &line, "terms")?;
+    for _ in 0..term_count {
+        let (number, line) = next_line()?;
+        let (term, postings) = line
+            .split_once('\t')
+            .ok_or_else(|| invalid_data(path, number, "expected '<term>\\t<postings>'"))?;
+        let (term, df) = match term.split_once(' ') {
+            Some((term, df)) => (term, Some(parse_field::<u32>(path, number, df)?)),
+            None => (term, None),
+        };
+        for posting in postings.split(' ') {
+            let (doc_idx, tf) = posting
+                .split_once(':')
+                .ok_or_else(|| invalid_data(path, number, "expected '<doc_idx>:<tf>'"))?;
+            let doc_idx = parse_field(path, number, doc_idx)?;
+            if doc_idx as usize >= doc_count {
+                return Err(invalid_data(path, number, "posting refers to an unknown document"));
+            }
+            inverted_index
+                .add_posting(term.to_string(), Posting::new(doc_idx, parse_field(path, number, tf)?))
+                .map_err(|_posting| invalid_data(path, number, "postings have to be sorted by document"))?;
+        }
+        if let (Some(df), Some(postings)) = (df, inverted_index.dictionary.get_mut(term)) {
+            *postings = std::mem::take(postings).with_df(df);
+        }
+    }
+    inverted_index.rebuild_vocabulary();
+
+    let (number, line) = next_line()?;
+    let vector_count = section_count(path, number, &line, "vectors")?;
+    let term_count = inverted_index.vocabulary.terms.len();
+    for _ in 0..vector_count {
+        let (number, line) = next_line()?;
+        let mut term_vector = Vec::new();
+        for entry in line.split(' ').filter(|entry| !entry.is_empty()) {
+            let mut fields = entry.splitn(3, ':');
+            let term_id: u32 = parse_field(path, number, fields.next().unwrap_or(""))?;
+            let tf = parse_field(path, number, fields.next().unwrap_or(""))?;
+            let positions = fields
+                .next()
+                .unwrap_or("")
+                .split(',')
+                .filter(|position| !position.is_empty())
+                .map(|position| parse_field(path, number, position))
+                .collect::<Result<Vec<u32>>>()?;
+            if term_id as usize >= term_count {
+                return Err(invalid_data(path, number, "term vector refers to an unknown term"));
+            }
+            term_vector.push(TermVectorEntry {
+                term_id,
+                tf,
+                positions,
+            });
+        }
+        inverted_index.term_vectors.push(term_vector);
+    }
+
+    Ok(inverted_index)
+}
+
+fn section_count(path: &Path, number: usize, line: &str, name: &str) -> Result<usize> {
+    match line.split_once(' ') {
+        Some((section, count)) if section == name => parse_field(path, number, count),
+        _ => Err(invalid_data(path, number, &format!("expected '{} <count>'", name))),
+    }
+}
+
+fn parse_field<T: std::str::FromStr>(path: &Path, number: usize, field: &str) -> Result<T> {
+    field
+        .parse()
+        .map_err(|_e| invalid_data(path, number, &format!("invalid value '{}'", field)))
+}
+
+fn invalid_data(path: &Path, number: usize, message: &str) -> Error {
+    Error::parse(path, Some(number), message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preprocessing::{indexer::create_inverted_index, splitter::split_documents, tokenizer::AnalyzerStep};
+
+    const CORPUS: &str = ".I 1\n.T\nshock waves\n.W\nshock waves meet the boundary layer .\n\
+                          .I 2\n.T\nheat transfer\n.W\nheat transfer in a boundary layer flow .\n";
+
+    #[test]
+    fn term_vectors_hold_positions_and_survive_a_round_trip() {
+        let dir = std::env::temp_dir().join(format!("mini-retrieve-persistence-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("corpus.all"), CORPUS).unwrap();
+        let documents_dir = dir.join(DOCUMENTS_DIR_NAME);
+        split_documents(&dir.join("corpus.all"), &documents_dir).unwrap();
+        let analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase, AnalyzerStep::Specials]);
+        let inverted_index = create_inverted_index(&documents_dir, analyzer).unwrap();
+
+        // The title opens the token stream: "shock waves shock waves meet ...".
+        let term_vector = inverted_index.term_vector(1).unwrap();
+        let shock = inverted_index.vocabulary.term_id("shock").unwrap();
+        let entry = term_vector.iter().find(|entry| entry.term_id == shock).unwrap();
+        assert_eq!((entry.tf, entry.positions.clone()), (2, vec![0, 2]));
+        assert!(term_vector.windows(2).all(|pair| pair[0].term_id < pair[1].term_id));
+
+        let (terms, doc_length) = inverted_index.document_terms(2).unwrap();
+        assert_eq!(terms.iter().map(|(_term, tf)| tf).sum::<u32>(), doc_length);
+        assert!(terms.contains(&("heat", 2)));
+
+        save_index(&inverted_index, &dir).unwrap();
+        let loaded = load_index(&dir).unwrap();
+        for doc_id in [1, 2] {
+            assert_eq!(loaded.document_terms(doc_id), inverted_index.document_terms(doc_id));
+            let positions = |index: &InvertedIndex| -> Vec<Vec<u32>> {
+                index.term_vector(doc_id).unwrap().iter().map(|entry| entry.positions.clone()).collect()
+            };
+            assert_eq!(positions(&loaded), positions(&inverted_index));
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn broken_indexes_report_the_file_and_line() {
+        let dir = std::env::temp_dir().join(format!("mini-retrieve-broken-{}", std::process::id()));
+        let missing = load_index(&dir).unwrap_err();
+        assert!(
+            matches!(&missing, Error::MissingResource { resource: "index", path } if path == &dir.join(INDEX_FILE_NAME)),
+            "{}",
+            missing
+        );
+
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(INDEX_FILE_NAME);
+        let cases = [
+            ("an inverted index\n".to_string(), Some(1)),
+            (format!("{}\ndocuments two 1.5\n", HEADER), Some(2)),
+            (format!("{}\nstopwords the\ndocuments 2 1.5\n1\t3\t1\tShock\n", HEADER), None),
+        ];
+        for (content, expected_line) in cases {
+            fs::write(&path, &content).unwrap();
+            match load_index(&dir) {
+                Err(Error::Parse { path: error_path, line, .. }) => {
+                    assert_eq!(error_path, path);
+                    assert_eq!(line, expected_line, "{}", content);
+                }
+                other => panic!("expected a parse error for {:?}, got {:?}", content, other.map(|_index| ())),
+            }
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,358 @@
+use std::{collections::HashMap, mem::size_of_val};
+
+use crate::{
+    preprocessing::impacts::build_impact_index,
+    querying::score::{idf, score_component},
+    types::{DocIdx, InvertedIndex, Posting, PostingsList},
+};
+
+/// Number of top postings per term whose scores the term-centric method
+/// preserves (the `k` of Carmel et al.).
+const TERM_CENTRIC_TOP_K: usize = 10;
+const EPSILON_SEARCH_STEPS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruningStrategy {
+    /// Carmel et al.: per term, drop postings scoring below
+    /// `epsilon * z_t`, where `z_t` is the term's k-th best score.
+    TermCentric,
+    /// Büttcher & Clarke: per document, keep only the terms contributing
+    /// most to it, ranked by their KL-divergence contribution.
+    DocumentCentric,
+}
+
+#[derive(Debug)]
+pub struct PruningReport {
+    pub postings_before: usize,
+    pub postings_after: usize,
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+}
+
+impl PruningReport {
+    /// Share of the postings that was actually removed. It can fall short of
+    /// the requested ratio, see `prune_index`.
+    pub fn achieved_ratio(&self) -> f32 {
+        (self.postings_before - self.postings_after) as f32 / self.postings_before.max(1) as f32
+    }
+}
+
+/// Prunes up to `ratio` (0..1) of all postings in place. Global statistics
+/// (`n`, `avdl`, document lengths and every term's df) keep describing the
+/// full collection; impacts are recomputed if the index carries them. Pruned
+/// postings are removed from the term vectors too, so that field and
+/// proximity features, feedback and more-like-this see the pruned index.
+///
+/// The term-centric method caps `epsilon` at 1, as Carmel et al. do, so it
+/// never removes a term's top `TERM_CENTRIC_TOP_K` postings and leaves terms
+/// with fewer postings untouched; large ratios can therefore not be reached.
+/// `PruningReport::achieved_ratio` tells what was removed.
+pub fn prune_index(
+    inverted_index: &mut InvertedIndex,
+    strategy: PruningStrategy,
+    ratio: f32,
+) -> PruningReport {
+    let postings_before = count_postings(inverted_index);
+    let bytes_before = estimate_size(inverted_index);
+
+    let keep = match strategy {
+        PruningStrategy::TermCentric => term_centric_keep(inverted_index, ratio),
+        PruningStrategy::DocumentCentric => document_centric_keep(inverted_index, ratio),
+    };
+
+    inverted_index.dictionary = std::mem::take(&mut inverted_index.dictionary)
+        .into_iter()
+        .filter_map(|(token, postings)| {
+            let flags = keep.get(&token)?;
+            let pruned: PostingsList = postings
+                .iter()
+                .zip(flags)
+                .filter(|(_posting, keep)| **keep)
+                .map(|(posting, _keep)| posting.clone())
+                .collect::<PostingsList>()
+                .with_df(postings.df());
+            (!pruned.is_empty()).then_some((token, pruned))
+        })
+        .collect();
+    inverted_index.rebuild_vocabulary();
+    prune_term_vectors(inverted_index);
+
+    if let Some(budget) = inverted_index.impacts.as_ref().map(|impacts| impacts.budget) {
+        inverted_index.impacts = Some(build_impact_index(inverted_index, budget));
+    }
+
+    PruningReport {
+        postings_before,
+        postings_after: count_postings(inverted_index),
+        bytes_before,
+        bytes_after: estimate_size(inverted_index),
+    }
+}
+
+/// Drops the term vector entries whose posting was pruned. Terms that left
+/// the dictionary are already gone after `rebuild_vocabulary`.
+fn prune_term_vectors(inverted_index: &mut InvertedIndex) {
+    let InvertedIndex {
+        dictionary,
+        vocabulary,
+        term_vectors,
+        ..
+    } = inverted_index;
+
+    for (doc_idx, term_vector) in term_vectors.iter_mut().enumerate() {
+        let doc_idx = doc_idx as DocIdx;
+        term_vector.retain(|entry| {
+            dictionary
+                .get(vocabulary.term(entry.term_id))
+                .and_then(|postings| postings.cursor().advance_to(doc_idx))
+                .is_some_and(|posting| posting.doc_idx == doc_idx)
+        });
+    }
+}
+
+fn posting_scores(inverted_index: &InvertedIndex, postings: &PostingsList) -> Vec<f32> {
+    let idf_j = idf(postings.df(), inverted_index.n);
+    postings
+        .iter()
+        .map(|posting| {
+            let l_di = inverted_index.doc_lengths[posting.doc_idx as usize] as f32;
+            idf_j * score_component(posting.tf as f32, l_di, inverted_index.avdl, inverted_index.bm25)
+        })
+        .collect()
+}
+
+/// Searches the `epsilon` for which the Carmel et al. threshold removes
+/// `ratio` of all postings, then marks the surviving postings.
+fn term_centric_keep(inverted_index: &InvertedIndex, ratio: f32) -> HashMap<String, Vec<bool>> {
+    let scored: Vec<(&String, Vec<f32>, f32)> = inverted_index
+        .dictionary
+        .iter()
+        .map(|(token, postings)| {
+            let scores = posting_scores(inverted_index, postings);
+            let mut sorted = scores.clone();
+            sorted.sort_by(|a, b| b.total_cmp(a));
+            let z_t = sorted
+                .get(TERM_CENTRIC_TOP_K - 1)
+                .or(sorted.last())
+                .copied()
+                .unwrap_or(0.0);
+            (token, scores, z_t)
+        })
+        .collect();
+
+    let total: usize = scored.iter().map(|(_token, scores, _z_t)| scores.len()).sum();
+    let target = (total as f32 * ratio) as usize;
+
+    let pruned_at = |epsilon: f32| -> usize {
+        scored
+            .iter()
+            .map(|(_token, scores, z_t)| {
+                let threshold = epsilon * z_t;
+                scores.iter().filter(|&&score| score < threshold).count()
+            })
+            .sum()
+    };
+
+    let (mut low, mut high) = (0.0_f32, 1.0_f32);
+    for _ in 0..EPSILON_SEARCH_STEPS {
+        let mid = (low + high) / 2.0;
+        if pruned_at(mid) < target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    let epsilon = if pruned_at(high) <= target { high } else { low };
+
+    scored
+        .into_iter()
+        .map(|(token, scores, z_t)| {
+            let threshold = epsilon * z_t;
+            let flags = scores.iter().map(|&score| score >= threshold).collect();
+            (token.clone(), flags)
+        })
+        .collect()
+}
+
+/// Keeps the top `1 - ratio` share of every document's terms, ranked by
+/// `P(t|d) * ln(P(t|d) / P(t|C))`.
+fn document_centric_keep(inverted_index: &InvertedIndex, ratio: f32) -> HashMap<String, Vec<bool>> {
+    let collection_length: f32 = inverted_index.doc_lengths.iter().map(|&l| l as f32).sum();
+
+    let mut per_document: Vec<Vec<(f32, &String, usize)>> =
+        vec![Vec::new(); inverted_index.doc_lengths.len()];
+
+    for (token, postings) in &inverted_index.dictionary {
+        let collection_tf: u32 = postings.iter().map(|posting| posting.tf).sum();
+        let p_collection = collection_tf as f32 / collection_length;
+
+        for (position, posting) in postings.iter().enumerate() {
+            let Posting { doc_idx, tf } = *posting;
+            let doc_length = inverted_index.doc_lengths[doc_idx as usize].max(1) as f32;
+            let p_document = tf as f32 / doc_length;
+            let contribution = p_document * (p_document / p_collection).ln();
+            per_document[doc_idx as usize].push((contribution, token, position));
+        }
+    }
+
+    let mut keep: HashMap<String, Vec<bool>> = inverted_index
+        .dictionary
+        .iter()
+        .map(|(token, postings)| (token.clone(), vec![false; postings.len()]))
+        .collect();
+
+    for mut terms in per_document {
+        terms.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let retained = ((terms.len() as f32 * (1.0 - ratio)).ceil() as usize).max(1);
+
+        for (_contribution, token, position) in terms.into_iter().take(retained) {
+            if let Some(flags) = keep.get_mut(token) {
+                flags[position] = true;
+            }
+        }
+    }
+
+    keep
+}
+
+fn count_postings(inverted_index: &InvertedIndex) -> usize {
+    inverted_index.dictionary.values().map(|postings| postings.len()).sum()
+}
+
+/// Approximate in-memory size of the dictionary: term bytes, postings and
+/// skip entries.
+fn estimate_size(inverted_index: &InvertedIndex) -> usize {
+    inverted_index
+        .dictionary
+        .iter()
+        .map(|(token, postings)| {
+            token.len() + size_of_val(postings.postings()) + size_of_val(postings.skips())
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An index over documents `1..` with the given term frequencies and
+    /// term vectors that match them.
+    fn index(documents: &[Vec<(&str, u32)>]) -> InvertedIndex {
+        let mut inverted_index = InvertedIndex::new();
+        for (doc_idx, terms) in documents.iter().enumerate() {
+            let doc_length = terms.iter().map(|(_term, tf)| tf).sum();
+            inverted_index.add_document(doc_idx as u32 + 1, String::new(), doc_length, 0);
+            for (term, tf) in terms {
+                inverted_index
+                    .add_posting(term.to_string(), Posting::new(doc_idx as DocIdx, *tf))
+                    .unwrap();
+            }
+        }
+        inverted_index.n = documents.len() as u32;
+        inverted_index.avdl = inverted_index.doc_lengths.iter().sum::<u32>() as f32 / documents.len() as f32;
+        inverted_index.rebuild_vocabulary();
+        for (doc_idx, terms) in documents.iter().enumerate() {
+            let positions = terms
+                .iter()
+                .map(|(term, tf)| (term.to_string(), (0..*tf).collect()))
+                .collect();
+            inverted_index.set_term_vector(doc_idx as DocIdx, positions);
+        }
+        inverted_index
+    }
+
+    /// 30 documents holding the terms `a`, `b` and `c` `i + 1` times each,
+    /// filled up to the same length with `filler`, so that the scores of a
+    /// term grow with the document index. 60 further documents make these
+    /// terms rare enough for a positive idf.
+    fn graded_index() -> InvertedIndex {
+        let documents: Vec<Vec<(&str, u32)>> = (0..30)
+            .map(|i| vec![("a", i + 1), ("b", i + 1), ("c", i + 1), ("filler", 3 * (30 - i))])
+            .chain((0..60).map(|_i| vec![("p", 1), ("q", 1), ("r", 1), ("s", 1)]))
+            .collect();
+        index(&documents)
+    }
+
+    fn doc_idxs(inverted_index: &InvertedIndex, term: &str) -> Vec<DocIdx> {
+        inverted_index.dictionary[term].iter().map(|posting| posting.doc_idx).collect()
+    }
+
+    #[test]
+    fn achieved_ratio_is_close_to_the_requested_one() {
+        // Every document holds four terms, so document-centric pruning can
+        // only remove quarters of them.
+        for (strategy, ratio) in [
+            (PruningStrategy::TermCentric, 0.2),
+            (PruningStrategy::DocumentCentric, 0.25),
+        ] {
+            let mut inverted_index = graded_index();
+            let report = prune_index(&mut inverted_index, strategy, ratio);
+
+            assert!(
+                (report.achieved_ratio() - ratio).abs() <= 0.02,
+                "{:?} pruned {}",
+                strategy,
+                report.achieved_ratio()
+            );
+            assert!(report.bytes_after < report.bytes_before);
+        }
+    }
+
+    #[test]
+    fn pruned_lists_keep_the_unpruned_df() {
+        let mut inverted_index = graded_index();
+        prune_index(&mut inverted_index, PruningStrategy::TermCentric, 0.3);
+
+        let postings = &inverted_index.dictionary["a"];
+        assert!(postings.len() < 30);
+        assert_eq!(postings.df(), 30);
+    }
+
+    #[test]
+    fn term_centric_pruning_keeps_the_top_postings_of_every_term() {
+        let mut inverted_index = graded_index();
+        let report = prune_index(&mut inverted_index, PruningStrategy::TermCentric, 0.9);
+
+        let top: Vec<DocIdx> = (30 - TERM_CENTRIC_TOP_K as DocIdx..30).collect();
+        for term in ["a", "b", "c"] {
+            assert_eq!(doc_idxs(&inverted_index, term), top);
+        }
+        assert!(report.achieved_ratio() < 0.9);
+    }
+
+    #[test]
+    fn document_centric_pruning_drops_the_lowest_kl_postings_first() {
+        // "the" is as likely in every document as in the collection, so it
+        // contributes nothing; the rare terms are kept.
+        let mut inverted_index = index(&[
+            vec![("the", 1), ("wing", 1)],
+            vec![("the", 1), ("flow", 1)],
+            vec![("the", 1), ("heat", 1)],
+            vec![("the", 1), ("shock", 1)],
+        ]);
+        let report = prune_index(&mut inverted_index, PruningStrategy::DocumentCentric, 0.5);
+
+        assert!(!inverted_index.dictionary.contains_key("the"));
+        for term in ["wing", "flow", "heat", "shock"] {
+            assert_eq!(inverted_index.dictionary[term].len(), 1);
+        }
+        assert_eq!(report.achieved_ratio(), 0.5);
+    }
+
+    #[test]
+    fn pruned_postings_leave_the_term_vectors() {
+        let mut inverted_index = graded_index();
+        prune_index(&mut inverted_index, PruningStrategy::TermCentric, 0.3);
+
+        for (doc_idx, term_vector) in inverted_index.term_vectors.iter().enumerate() {
+            let terms: Vec<&str> = term_vector
+                .iter()
+                .map(|entry| inverted_index.vocabulary.term(entry.term_id))
+                .collect();
+            for term in ["a", "b", "c", "filler", "p"] {
+                let indexed = doc_idxs(&inverted_index, term).contains(&(doc_idx as DocIdx));
+                assert_eq!(terms.contains(&term), indexed, "{} in document {}", term, doc_idx);
+            }
+        }
+    }
+}
@@ -1,55 +1,104 @@
 use std::{
     fs::{self, File},
     io::{BufRead, BufReader},
+    path::Path,
 };
 
-use crate::types::DocId;
+use crate::{
+    error::{Error, Result},
+    types::DocId,
+};
 
-pub fn split_documents(doc_path: &str) {
-    println!("> Splitting documents at path: {}", doc_path);
-    let out_path = "out/documents";
+/// Corpus file used when none is given.
+pub const DEFAULT_CORPUS_PATH: &str = "in/documents.all";
 
-    let file = File::open(doc_path).unwrap();
-    println!("  > Found infile");
+/// Splits the corpus at `doc_path` into one file per document under
+/// `out_path` and returns the number of documents written. Fails with a
+/// parse error on a `.I` line without a numeric id.
+///
+/// The documents are written to a sibling directory first, which then
+/// replaces `out_path`, so no files of an earlier corpus are left behind and
+/// a failed split keeps the previous documents.
+pub fn split_documents(doc_path: &Path, out_path: &Path) -> Result<u32> {
+    let staging_path = out_path.with_extension("partial");
+    if staging_path.exists() {
+        fs::remove_dir_all(&staging_path)?;
+    }
+    let count = split_into(doc_path, &staging_path).inspect_err(|_e| {
+        let _cleanup = fs::remove_dir_all(&staging_path);
+    })?;
+
+    if out_path.exists() {
+        fs::remove_dir_all(out_path)?;
+    }
+    fs::rename(&staging_path, out_path)?;
+    Ok(count)
+}
+
+fn split_into(doc_path: &Path, out_path: &Path) -> Result<u32> {
+    let file = File::open(doc_path).map_err(|e| Error::open("corpus", doc_path, e))?;
+    fs::create_dir_all(out_path)?;
 
     let mut file_buffer: Vec<String> = vec![];
     let mut current_id: DocId = 0;
     let mut count: u32 = 0;
 
     let reader = BufReader::new(file);
-    for line in reader.lines() {
-        let text_line = line.unwrap();
+    for (number, line) in reader.lines().enumerate() {
+        let text_line = line?;
 
         if text_line.starts_with(".I") {
-            if file_buffer.len() > 0 {
-                write_file(out_path, &file_buffer, current_id);
+            if !file_buffer.is_empty() {
+                write_file(out_path, &file_buffer, current_id)?;
                 file_buffer.clear();
             }
 
-            let parsed_id_result = text_line
+            current_id = text_line
                 .split_whitespace()
                 .nth(1)
-                .and_then(|s| s.parse::<DocId>().ok());
-            if let Some(doc_id) = parsed_id_result {
-                current_id = doc_id;
-                count += 1;
-            } else {
-                eprintln!("WARNING: Failed to exctract a valid Doc ID. Line will be skipped.")
-            }
+                .and_then(|s| s.parse::<DocId>().ok())
+                .ok_or_else(|| Error::parse(doc_path, Some(number + 1), "expected '.I <DocId>'"))?;
+            count += 1;
         }
         file_buffer.push(text_line);
     }
-    write_file(out_path, &file_buffer, current_id);
-    println!("  > Document splitting completed");
-    println!("  > Created {} documents under {}", count, out_path);
+    write_file(out_path, &file_buffer, current_id)?;
+    Ok(count)
 }
 
-fn write_file(out_path: &str, file_buffer: &Vec<String>, current_id: DocId) {
+fn write_file(out_path: &Path, file_buffer: &[String], current_id: DocId) -> Result<()> {
     let content = file_buffer.join("\n");
-    let out_path = format!("{}/doc{}", out_path, current_id);
+    fs::write(out_path.join(format!("doc{}", current_id)), content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splitting_replaces_stale_documents() {
+        let dir = std::env::temp_dir().join(format!("mini-retrieve-splitter-{}", std::process::id()));
+        let corpus = dir.join("corpus.all");
+        let out = dir.join("documents");
+        fs::create_dir_all(&out).unwrap();
+        fs::write(out.join("doc999"), ".I 999\n.T\nstale\n").unwrap();
+        fs::write(&corpus, ".I 1\n.T\nfirst\n.I 2\n.T\nsecond\n").unwrap();
 
-    fs::write(out_path, content).unwrap_or_else(|e| {
-        eprintln!("ERROR: Failed to write document {}", current_id);
-        eprintln!("I/O Error details: {}", e);
-    });
+        assert_eq!(split_documents(&corpus, &out).unwrap(), 2);
+        let mut files: Vec<String> = fs::read_dir(&out)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["doc1", "doc2"]);
+        assert!(!out.with_extension("partial").exists());
+
+        fs::write(&corpus, ".I one\n").unwrap();
+        assert!(split_documents(&corpus, &out).is_err());
+        assert!(out.join("doc1").exists());
+        assert!(!out.with_extension("partial").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
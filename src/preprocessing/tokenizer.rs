@@ -1,26 +1,145 @@
-use std::{collections::HashSet, sync::OnceLock};
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use crate::error::{Error, Result};
+
+/// Stopword list used when none is given.
+pub const STOPWORDS_PATH: &str = "stopwords.txt";
+
+/// One step of an analyzer chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalyzerStep {
+    Lowercase,
+    /// Drops every character that is not alphanumeric.
+    Specials,
+    /// Rejects tokens of the stopword list.
+    Stopwords,
+    Stem,
+}
 
-static STOPWORDS_SET: OnceLock<HashSet<String>> = OnceLock::new();
-const STOPWORDS_PATH: &str = "stopwords.txt";
+impl AnalyzerStep {
+    fn name(self) -> &'static str {
+        match self {
+            AnalyzerStep::Lowercase => "lowercase",
+            AnalyzerStep::Specials => "specials",
+            AnalyzerStep::Stopwords => "stopwords",
+            AnalyzerStep::Stem => "stem",
+        }
+    }
+}
 
-pub fn tokenize<T: AsRef<str>>(term: T) -> Option<String> {
-    let mut token = term.as_ref().to_lowercase();
-    token = remove_specials(token);
+/// The chain of steps that turns a word into an index term, together with
+/// the stopword list its `Stopwords` step rejects. Documents and queries have
+/// to go through the same chain, so both are stored with the index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analyzer {
+    steps: Vec<AnalyzerStep>,
+    stopwords: Arc<HashSet<String>>,
+}
+
+impl Default for Analyzer {
+    /// Lowercase, specials, stopwords and stem, with an empty stopword list
+    /// until one is set with `with_stopwords`.
+    fn default() -> Self {
+        Analyzer::new(vec![
+            AnalyzerStep::Lowercase,
+            AnalyzerStep::Specials,
+            AnalyzerStep::Stopwords,
+            AnalyzerStep::Stem,
+        ])
+    }
+}
+
+impl Analyzer {
+    pub fn new(steps: Vec<AnalyzerStep>) -> Self {
+        Analyzer {
+            steps,
+            stopwords: Arc::default(),
+        }
+    }
+
+    /// The same chain rejecting `stopwords` in its `Stopwords` step.
+    pub fn with_stopwords(self, stopwords: HashSet<String>) -> Self {
+        Analyzer {
+            stopwords: Arc::new(stopwords),
+            ..self
+        }
+    }
+
+    pub fn stopwords(&self) -> &HashSet<String> {
+        &self.stopwords
+    }
+
+    pub fn has_step(&self, step: AnalyzerStep) -> bool {
+        self.steps.contains(&step)
+    }
 
-    if token.is_empty() {
-        return None;
+    /// Parses a comma-separated chain such as `lowercase,specials,stem`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let steps = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| match name {
+                "lowercase" => Ok(AnalyzerStep::Lowercase),
+                "specials" => Ok(AnalyzerStep::Specials),
+                "stopwords" => Ok(AnalyzerStep::Stopwords),
+                "stem" => Ok(AnalyzerStep::Stem),
+                _ => Err(Error::Config(format!(
+                    "unknown analyzer step '{}' (expected lowercase, specials, stopwords or stem)",
+                    name
+                ))),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Analyzer::new(steps))
     }
 
-    if is_stopword(&token) {
-        return None;
+    pub fn steps(&self) -> &[AnalyzerStep] {
+        &self.steps
     }
 
-    token = stem(token);
-    Some(token)
+    /// Runs `term` through the chain; `None` if it ends up empty or is a
+    /// stopword.
+    pub fn analyze<T: AsRef<str>>(&self, term: T) -> Option<String> {
+        let mut token = term.as_ref().to_string();
+        for step in &self.steps {
+            token = match step {
+                AnalyzerStep::Lowercase => token.to_lowercase(),
+                AnalyzerStep::Specials => remove_specials(token),
+                AnalyzerStep::Stopwords if self.stopwords.contains(&token) => return None,
+                AnalyzerStep::Stopwords => token,
+                AnalyzerStep::Stem => stem(token),
+            };
+            if token.is_empty() {
+                return None;
+            }
+        }
+        (!token.is_empty()).then_some(token)
+    }
 }
 
-fn is_stopword(token: &String) -> bool {
-    get_stopwords_set().contains(token)
+impl fmt::Display for Analyzer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.steps.iter().map(|step| step.name()).collect();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+
+/// Analyses `term` with the default chain and the stopwords of
+/// `stopwords.txt`, if it exists. Indexes analyse with their own `Analyzer`.
+pub fn tokenize<T: AsRef<str>>(term: T) -> Option<String> {
+    static DEFAULT_ANALYZER: OnceLock<Analyzer> = OnceLock::new();
+    DEFAULT_ANALYZER
+        .get_or_init(|| {
+            let stopwords = load_stopwords(Path::new(STOPWORDS_PATH)).unwrap_or_default();
+            Analyzer::default().with_stopwords(stopwords)
+        })
+        .analyze(term)
 }
 
 fn stem(token: String) -> String {
@@ -68,29 +187,56 @@ fn remove_affix(token: String) -> String {
 }
 
 fn remove_double_letters(token: String) -> String {
-    let len = token.len();
-    if len >= 2 {
-        let last_char = token.chars().last().unwrap();
-        let second_to_last_char = token.chars().nth(len - 2).unwrap();
-
+    let mut chars = token.char_indices().rev();
+    if let (Some((last_start, last_char)), Some((_, second_to_last_char))) = (chars.next(), chars.next()) {
         if last_char == second_to_last_char {
-            return token[0..len - 1].to_owned();
+            return token[..last_start].to_owned();
         }
     }
 
     token
 }
 
-fn get_stopwords_set() -> &'static HashSet<String> {
-    STOPWORDS_SET.get_or_init(|| {
-        let contents = std::fs::read_to_string(STOPWORDS_PATH)
-            .expect("Failed to read stopwords file. Maybe the file is missing?");
-
-        contents
-            .lines()
-            .filter(|line| !line.trim().is_empty())
-            .map(|line| line.trim().to_lowercase())
-            .collect()
-    })
+/// Reads a stopword list with one word per line.
+pub fn load_stopwords(path: &Path) -> Result<HashSet<String>> {
+    let contents = fs::read_to_string(path).map_err(|e| Error::open("stopwords", path, e))?;
+
+    Ok(contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.trim().to_lowercase())
+        .collect())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_double_letters() {
+        assert_eq!(remove_double_letters("runn".to_string()), "run");
+        assert_eq!(remove_double_letters("run".to_string()), "run");
+        assert_eq!(remove_double_letters("a".to_string()), "a");
+    }
+
+    #[test]
+    fn handles_non_ascii_tokens() {
+        assert_eq!(remove_double_letters("éé".to_string()), "é");
+        assert_eq!(remove_double_letters("caféé".to_string()), "café");
+        assert_eq!(remove_double_letters("aé".to_string()), "aé");
+        let analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase, AnalyzerStep::Specials, AnalyzerStep::Stem]);
+        assert_eq!(analyzer.analyze("ÉÉ").as_deref(), Some("é"));
+        assert_eq!(analyzer.analyze("naïveté").as_deref(), Some("naïveté"));
+    }
+
+    #[test]
+    fn stopwords_belong_to_the_analyzer() {
+        let plain = Analyzer::default();
+        let filtering = Analyzer::default().with_stopwords(HashSet::from(["the".to_string()]));
+
+        assert_eq!(plain.analyze("The").as_deref(), Some("the"));
+        assert_eq!(filtering.analyze("The"), None);
+        assert_eq!(filtering.analyze("flow").as_deref(), Some("flow"));
+        assert_ne!(plain, filtering);
+    }
+}
@@ -0,0 +1,140 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    querying::{query::Query, score::score},
+    types::{DocId, InvertedIndex},
+};
+
+#[derive(Debug, Clone)]
+pub struct BatchQuery {
+    pub qid: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    pub qid: String,
+    pub text: String,
+    pub ranked_results: Vec<(DocId, f32)>,
+    pub latency: Duration,
+}
+
+/// Reads one query per non-empty line. A line of the form `qid<TAB>query`
+/// carries its own id, other lines are numbered by their line number.
+pub fn parse_batch(input: &str) -> Vec<BatchQuery> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_number, line)| !line.trim().is_empty())
+        .map(|(number, line)| match line.split_once('\t') {
+            Some((qid, text)) => BatchQuery {
+                qid: qid.trim().to_string(),
+                text: text.trim().to_string(),
+            },
+            None => BatchQuery {
+                qid: (number + 1).to_string(),
+                text: line.trim().to_string(),
+            },
+        })
+        .collect()
+}
+
+/// Scores every query for its top `k` documents on up to `threads` threads.
+/// Results come back in input order, each with the latency of its own
+/// parse and score.
+pub fn run_batch(
+    queries: &[BatchQuery],
+    inverted_index: &InvertedIndex,
+    k: usize,
+    threads: usize,
+) -> Vec<BatchResult> {
+    let chunk_size = queries.len().div_ceil(threads.max(1)).max(1);
+
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = queries
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || run_chunk(chunk, inverted_index, k)))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("batch worker panicked"))
+            .collect()
+    })
+}
+
+fn run_chunk(queries: &[BatchQuery], inverted_index: &InvertedIndex, k: usize) -> Vec<BatchResult> {
+    queries
+        .iter()
+        .map(|query| {
+            let start_time = Instant::now();
+            let parsed_query = Query::parse(&query.text, inverted_index);
+            let ranked_results = score(&parsed_query, inverted_index, k);
+            BatchResult {
+                qid: query.qid.clone(),
+                text: query.text.clone(),
+                ranked_results,
+                latency: start_time.elapsed(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preprocessing::tokenizer::{Analyzer, AnalyzerStep},
+        types::{DocIdx, Posting},
+    };
+
+    /// An index over documents `1..` holding the given terms once each.
+    fn index(documents: &[&[&str]]) -> InvertedIndex {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        for (doc_idx, terms) in documents.iter().enumerate() {
+            inverted_index.add_document(doc_idx as DocId + 1, String::new(), terms.len() as u32, 0);
+            for term in *terms {
+                inverted_index
+                    .add_posting(term.to_string(), Posting::new(doc_idx as DocIdx, 1))
+                    .unwrap();
+            }
+        }
+        inverted_index.n = documents.len() as u32;
+        inverted_index.avdl = inverted_index.doc_lengths.iter().sum::<u32>() as f32 / documents.len() as f32;
+        inverted_index.rebuild_vocabulary();
+        inverted_index
+    }
+
+    #[test]
+    fn lines_carry_their_own_id_or_their_line_number() {
+        let queries = parse_batch("q7\tshock  waves \n\n  flow over wings\n");
+
+        let parsed: Vec<(&str, &str)> = queries.iter().map(|query| (query.qid.as_str(), query.text.as_str())).collect();
+        assert_eq!(parsed, vec![("q7", "shock  waves"), ("3", "flow over wings")]);
+    }
+
+    #[test]
+    fn threads_return_the_results_in_input_order() {
+        let inverted_index = index(&[&["shock", "wave"], &["flow"], &["wing", "flow"], &["heat"]]);
+        let words = ["shock", "flow", "wing", "heat", "missing"];
+        let queries: Vec<BatchQuery> = (0..23)
+            .map(|i| BatchQuery {
+                qid: i.to_string(),
+                text: words[i % words.len()].to_string(),
+            })
+            .collect();
+
+        let sequential = run_batch(&queries, &inverted_index, 10, 1);
+        let parallel = run_batch(&queries, &inverted_index, 10, 4);
+
+        assert_eq!(parallel.len(), queries.len());
+        for (sequential, parallel) in sequential.iter().zip(&parallel) {
+            assert_eq!(sequential.qid, parallel.qid);
+            assert_eq!(sequential.ranked_results, parallel.ranked_results);
+        }
+        let qids: Vec<&str> = parallel.iter().map(|result| result.qid.as_str()).collect();
+        assert_eq!(qids, queries.iter().map(|query| query.qid.as_str()).collect::<Vec<_>>());
+        assert_eq!(parallel[1].ranked_results.len(), 2);
+        assert!(parallel[4].ranked_results.is_empty());
+    }
+}
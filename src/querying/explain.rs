@@ -0,0 +1,155 @@
+use crate::{
+    querying::{
+        query::{is_wildcard, parse_fuzzy, Query},
+        score::{idf, score_component},
+    },
+    types::{DocId, InvertedIndex},
+};
+
+/// How a single query token contributes to the BM25 score of a document.
+#[derive(Debug, Clone)]
+pub struct TermExplanation {
+    pub token: String,
+    pub weight: f32,
+    pub df: u32,
+    pub idf: f32,
+    pub tf: u32,
+    pub component: f32,
+    pub contribution: f32,
+}
+
+/// The BM25 score of one document for a query, broken down per query token.
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub doc_id: DocId,
+    pub doc_length: u32,
+    pub avdl: f32,
+    pub terms: Vec<TermExplanation>,
+    pub stopwords: Vec<String>,
+    pub missing: Vec<String>,
+    pub total: f32,
+}
+
+/// Recomputes the exhaustive BM25 score of `doc_id` for `text` term by term.
+/// Words the analyser drops as stopwords and tokens without a postings list
+/// are listed separately. Returns `None` if the document is not indexed.
+pub fn explain(doc_id: DocId, text: &str, inverted_index: &InvertedIndex) -> Option<Explanation> {
+    let doc_idx = inverted_index.doc_idx(doc_id)?;
+    let doc_length = inverted_index.doc_lengths[doc_idx as usize];
+    let avdl = inverted_index.avdl;
+    let query = Query::parse(text, inverted_index);
+
+    let stopwords = text
+        .split_whitespace()
+        .filter(|word| parse_fuzzy(word).is_none() && !is_wildcard(word))
+        .filter(|word| word.chars().any(char::is_alphanumeric) && inverted_index.analyzer.analyze(word).is_none())
+        .map(str::to_string)
+        .collect();
+
+    let mut missing: Vec<String> = query
+        .expansions
+        .iter()
+        .filter(|expansion| expansion.terms.is_empty())
+        .map(|expansion| expansion.pattern.clone())
+        .collect();
+    let mut terms = Vec::new();
+
+    for term in &query.terms {
+        let Some(postings) = inverted_index.dictionary.get(&term.token) else {
+            missing.push(term.token.clone());
+            continue;
+        };
+        let df = postings.df();
+        let idf = idf(df, inverted_index.n);
+        let tf = postings
+            .binary_search_by_key(&doc_idx, |posting| posting.doc_idx)
+            .map(|position| postings[position].tf)
+            .unwrap_or(0);
+        let component = if tf > 0 {
+            score_component(tf as f32, doc_length as f32, avdl, inverted_index.bm25)
+        } else {
+            0.0
+        };
+
+        terms.push(TermExplanation {
+            token: term.token.clone(),
+            weight: term.weight,
+            df,
+            idf,
+            tf,
+            component,
+            contribution: term.weight * idf * component,
+        });
+    }
+
+    let total = terms.iter().map(|term| term.contribution).sum();
+    Some(Explanation {
+        doc_id,
+        doc_length,
+        avdl,
+        terms,
+        stopwords,
+        missing,
+        total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{
+        preprocessing::tokenizer::{Analyzer, AnalyzerStep},
+        querying::score::score_bm25,
+        types::{DocIdx, Posting},
+    };
+
+    /// An index over documents `1..` with the given term frequencies and
+    /// "the" as its only stopword.
+    fn index(documents: &[&[(&str, u32)]]) -> InvertedIndex {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase, AnalyzerStep::Stopwords])
+            .with_stopwords(HashSet::from(["the".to_string()]));
+        for (doc_idx, terms) in documents.iter().enumerate() {
+            let doc_length = terms.iter().map(|(_term, tf)| tf).sum();
+            inverted_index.add_document(doc_idx as DocId + 1, String::new(), doc_length, 0);
+            for (term, tf) in *terms {
+                inverted_index
+                    .add_posting(term.to_string(), Posting::new(doc_idx as DocIdx, *tf))
+                    .unwrap();
+            }
+        }
+        inverted_index.n = documents.len() as u32;
+        inverted_index.avdl = inverted_index.doc_lengths.iter().sum::<u32>() as f32 / documents.len() as f32;
+        inverted_index.rebuild_vocabulary();
+        inverted_index
+    }
+
+    #[test]
+    fn explanations_add_up_to_the_bm25_score() {
+        let inverted_index = index(&[
+            &[("shock", 3), ("wave", 1), ("flow", 2)],
+            &[("shock", 1), ("heat", 4)],
+            &[("flow", 1)],
+            &[("plate", 2)],
+            &[("wing", 1)],
+        ]);
+        let text = "the shock wave turbulence";
+        let ranked_results = score_bm25(&Query::parse(text, &inverted_index), &inverted_index, 10);
+
+        for (doc_id, score) in ranked_results {
+            let explanation = explain(doc_id, text, &inverted_index).unwrap();
+            assert!((explanation.total - score).abs() < 1e-5, "document {}", doc_id);
+            assert_eq!(explanation.stopwords, vec!["the"]);
+            assert_eq!(explanation.missing, vec!["turbulence"]);
+        }
+
+        let explanation = explain(2, text, &inverted_index).unwrap();
+        let wave = explanation.terms.iter().find(|term| term.token == "wave").unwrap();
+        assert_eq!((wave.df, wave.tf, wave.contribution), (1, 0, 0.0));
+        let shock = explanation.terms.iter().find(|term| term.token == "shock").unwrap();
+        assert_eq!((shock.df, shock.tf), (2, 1));
+        assert!(explain(99, text, &inverted_index).is_none());
+    }
+}
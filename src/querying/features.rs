@@ -0,0 +1,277 @@
+use crate::{
+    querying::{
+        fields::{average_field_length, field_length, field_postings, Field},
+        query::Query,
+        score::{idf, score_component},
+    },
+    types::{DocIdx, InvertedIndex},
+};
+
+/// Number of features describing a query-document pair.
+pub const FEATURE_COUNT: usize = 9;
+/// Names of the features in the order of a feature vector.
+pub const FEATURE_NAMES: [&str; FEATURE_COUNT] = [
+    "bm25",
+    "bm25_title",
+    "bm25_body",
+    "lm_dirichlet",
+    "coverage",
+    "proximity",
+    "log_length",
+    "mean_idf",
+    "max_idf",
+];
+
+pub type FeatureVector = [f32; FEATURE_COUNT];
+
+/// Collection statistics of one query term.
+#[derive(Debug, Clone)]
+struct TermStats {
+    term_id: u32,
+    weight: f32,
+    idf: f32,
+    title_idf: f32,
+    body_idf: f32,
+    /// Probability of the term in the collection language model.
+    p_collection: f32,
+}
+
+/// Computes the features of documents for one query. The statistics that
+/// only depend on the query are gathered once, the rest is read from the
+/// documents' term vectors.
+#[derive(Debug)]
+pub struct FeatureExtractor<'a> {
+    inverted_index: &'a InvertedIndex,
+    terms: Vec<TermStats>,
+    query_weight: f32,
+    avg_title_length: f32,
+    avg_body_length: f32,
+    mu: f32,
+}
+
+impl<'a> FeatureExtractor<'a> {
+    /// `mu` is the Dirichlet prior of the language model feature.
+    pub fn new(query: &Query, inverted_index: &'a InvertedIndex, mu: f32) -> Self {
+        let n = inverted_index.n;
+        let collection_length: f32 = inverted_index.doc_lengths.iter().map(|&length| length as f32).sum();
+        let terms = query
+            .terms
+            .iter()
+            .filter_map(|term| {
+                let postings = inverted_index.dictionary.get(&term.token)?;
+                let term_id = inverted_index.vocabulary.term_id(&term.token)?;
+                let title_df = field_postings(inverted_index, postings, term_id, Field::Title).len();
+                let body_df = field_postings(inverted_index, postings, term_id, Field::Body).len();
+                let cf: u32 = postings.iter().map(|posting| posting.tf).sum();
+                Some(TermStats {
+                    term_id,
+                    weight: term.weight,
+                    idf: idf(postings.df(), n),
+                    title_idf: idf(title_df as u32, n),
+                    body_idf: idf(body_df as u32, n),
+                    p_collection: cf as f32 / collection_length.max(1.0),
+                })
+            })
+            .collect();
+
+        FeatureExtractor {
+            inverted_index,
+            terms,
+            query_weight: query.terms.iter().map(|term| term.weight).sum(),
+            avg_title_length: average_field_length(inverted_index, Field::Title),
+            avg_body_length: average_field_length(inverted_index, Field::Body),
+            mu,
+        }
+    }
+
+    /// The features of `doc_idx`, see `FEATURE_NAMES`.
+    pub fn features(&self, doc_idx: DocIdx) -> FeatureVector {
+        let index = self.inverted_index;
+        let params = index.bm25;
+        let doc_length = index.doc_lengths[doc_idx as usize];
+        let title_length = field_length(index, doc_idx, Field::Title);
+        let body_length = field_length(index, doc_idx, Field::Body);
+        let term_vector = index.term_vectors.get(doc_idx as usize).map_or(&[][..], Vec::as_slice);
+
+        let mut features = [0.0; FEATURE_COUNT];
+        let mut matched_weight = 0.0;
+        let mut matched_idfs: Vec<f32> = Vec::new();
+        let mut matched_positions: Vec<&[u32]> = Vec::new();
+        for term in &self.terms {
+            let Ok(position) = term_vector.binary_search_by_key(&term.term_id, |entry| entry.term_id) else {
+                continue;
+            };
+            let entry = &term_vector[position];
+            let tf = entry.tf as f32;
+            let title_tf = entry.positions.iter().filter(|&&position| position < title_length).count() as f32;
+            let body_tf = tf - title_tf;
+
+            features[0] += term.weight * term.idf * score_component(tf, doc_length as f32, index.avdl, params);
+            if title_tf > 0.0 {
+                features[1] += term.weight
+                    * term.title_idf
+                    * score_component(title_tf, title_length as f32, self.avg_title_length, params);
+            }
+            if body_tf > 0.0 {
+                features[2] += term.weight
+                    * term.body_idf
+                    * score_component(body_tf, body_length as f32, self.avg_body_length, params);
+            }
+            features[3] += term.weight * (1.0 + tf / (self.mu * term.p_collection)).ln();
+
+            matched_weight += term.weight;
+            matched_idfs.push(term.idf);
+            matched_positions.push(&entry.positions);
+        }
+
+        features[3] += self.query_weight * (self.mu / (doc_length as f32 + self.mu)).ln();
+        if self.query_weight > 0.0 {
+            features[4] = matched_weight / self.query_weight;
+        }
+        features[5] = proximity(&matched_positions);
+        features[6] = (1.0 + doc_length as f32).ln();
+        if !matched_idfs.is_empty() {
+            features[7] = matched_idfs.iter().sum::<f32>() / matched_idfs.len() as f32;
+            features[8] = matched_idfs.iter().copied().fold(0.0, f32::max);
+        }
+        features
+    }
+}
+
+/// Number of matched terms divided by the length of the shortest window
+/// containing all of them: 1 for adjacent terms, 0 for fewer than two.
+fn proximity(matched_positions: &[&[u32]]) -> f32 {
+    if matched_positions.len() < 2 {
+        return 0.0;
+    }
+
+    let mut occurrences: Vec<(u32, usize)> = matched_positions
+        .iter()
+        .enumerate()
+        .flat_map(|(term, positions)| positions.iter().map(move |&position| (position, term)))
+        .collect();
+    occurrences.sort_unstable();
+
+    let term_count = matched_positions.len();
+    let mut counts = vec![0usize; term_count];
+    let mut covered = 0;
+    let mut start = 0;
+    let mut shortest = u32::MAX;
+    for end in 0..occurrences.len() {
+        let (end_position, term) = occurrences[end];
+        if counts[term] == 0 {
+            covered += 1;
+        }
+        counts[term] += 1;
+
+        while covered == term_count {
+            let (start_position, start_term) = occurrences[start];
+            shortest = shortest.min(end_position - start_position + 1);
+            counts[start_term] -= 1;
+            if counts[start_term] == 0 {
+                covered -= 1;
+            }
+            start += 1;
+        }
+    }
+
+    if shortest == u32::MAX {
+        0.0
+    } else {
+        term_count as f32 / shortest as f32
+    }
+}
+
+/// Scales every feature to `[0, 1]` over the candidates of one query, so
+/// that weights mean the same across queries.
+pub fn normalize_features(features: &mut [FeatureVector]) {
+    for feature in 0..FEATURE_COUNT {
+        let min = features.iter().map(|vector| vector[feature]).fold(f32::INFINITY, f32::min);
+        let max = features.iter().map(|vector| vector[feature]).fold(f32::NEG_INFINITY, f32::max);
+        for vector in features.iter_mut() {
+            vector[feature] = if max > min { (vector[feature] - min) / (max - min) } else { 0.0 };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preprocessing::tokenizer::{Analyzer, AnalyzerStep},
+        types::{DocId, Posting},
+    };
+
+    /// An index over documents `1..` given as token streams, with term
+    /// vectors and no title.
+    fn index(documents: &[&[&str]]) -> InvertedIndex {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        let mut term_vectors = Vec::new();
+        for (doc_idx, tokens) in documents.iter().enumerate() {
+            inverted_index.add_document(doc_idx as DocId + 1, String::new(), tokens.len() as u32, 0);
+            let mut positions: Vec<(String, Vec<u32>)> = Vec::new();
+            for (position, token) in tokens.iter().enumerate() {
+                match positions.iter_mut().find(|(term, _positions)| term == token) {
+                    Some((_term, positions)) => positions.push(position as u32),
+                    None => positions.push((token.to_string(), vec![position as u32])),
+                }
+            }
+            for (term, term_positions) in &positions {
+                inverted_index
+                    .add_posting(term.clone(), Posting::new(doc_idx as DocIdx, term_positions.len() as u32))
+                    .unwrap();
+            }
+            term_vectors.push(positions);
+        }
+        inverted_index.n = documents.len() as u32;
+        inverted_index.avdl = inverted_index.doc_lengths.iter().sum::<u32>() as f32 / documents.len() as f32;
+        inverted_index.rebuild_vocabulary();
+        for (doc_idx, positions) in term_vectors.into_iter().enumerate() {
+            inverted_index.set_term_vector(doc_idx as DocIdx, positions);
+        }
+        inverted_index
+    }
+
+    #[test]
+    fn proximity_is_the_term_count_over_the_shortest_window() {
+        assert_eq!(proximity(&[&[3]]), 0.0);
+        assert_eq!(proximity(&[&[0, 9], &[4, 10]]), 1.0);
+        assert_eq!(proximity(&[&[0], &[5], &[2]]), 0.5);
+    }
+
+    #[test]
+    fn features_describe_coverage_proximity_and_length() {
+        let inverted_index = index(&[
+            &["shock", "wave", "flow"],
+            &["shock", "in", "a", "laminar", "flow", "tube"],
+            &["plate", "heat"],
+            &["wing"],
+            &["delta", "wing"],
+            &["swept"],
+        ]);
+        let query = Query::parse("shock flow wing", &inverted_index);
+        let extractor = FeatureExtractor::new(&query, &inverted_index, 100.0);
+
+        let close = extractor.features(0);
+        let apart = extractor.features(1);
+        let unmatched = extractor.features(2);
+        let feature = |name: &str| FEATURE_NAMES.iter().position(|feature| *feature == name).unwrap();
+
+        assert!((close[feature("coverage")] - 2.0 / 3.0).abs() < 1e-6);
+        assert_eq!(close[feature("proximity")], 2.0 / 3.0);
+        assert_eq!(apart[feature("proximity")], 2.0 / 5.0);
+        assert!(close[feature("bm25")] > apart[feature("bm25")]);
+        assert_eq!(apart[feature("log_length")], 7.0f32.ln());
+        assert_eq!(unmatched[feature("coverage")], 0.0);
+        assert_eq!(unmatched[feature("bm25")], 0.0);
+        assert_eq!(unmatched[feature("max_idf")], 0.0);
+
+        let mut features = vec![close, apart, unmatched];
+        normalize_features(&mut features);
+        assert_eq!(features[0][feature("coverage")], 1.0);
+        assert_eq!(features[2][feature("coverage")], 0.0);
+        assert_eq!(features[1][feature("mean_idf")], features[0][feature("mean_idf")]);
+        assert_eq!(features[2][feature("mean_idf")], 0.0);
+    }
+}
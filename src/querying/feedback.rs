@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+
+use crate::{
+    querying::{
+        query::{Query, QueryTerm},
+        score::{idf, score},
+    },
+    types::{DocId, InvertedIndex, Term},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedbackModel {
+    Rm3,
+    Rocchio,
+}
+
+/// Parameters of the pseudo-relevance feedback stage: the `fb_docs` best
+/// documents of the first run contribute up to `fb_terms` expansion terms,
+/// and the original query keeps `original_weight` of the total weight.
+#[derive(Debug, Clone, Copy)]
+pub struct FeedbackParams {
+    pub model: FeedbackModel,
+    pub fb_docs: usize,
+    pub fb_terms: usize,
+    pub original_weight: f32,
+}
+
+impl Default for FeedbackParams {
+    fn default() -> Self {
+        FeedbackParams {
+            model: FeedbackModel::Rm3,
+            fb_docs: 10,
+            fb_terms: 20,
+            original_weight: 0.5,
+        }
+    }
+}
+
+/// Runs `query`, treats its top `fb_docs` documents as relevant, expands the
+/// query from them and returns the ranking of the expanded query together
+/// with the expanded query itself.
+pub fn score_with_feedback(
+    query: &Query,
+    inverted_index: &InvertedIndex,
+    k: usize,
+    params: &FeedbackParams,
+) -> (Vec<(DocId, f32)>, Query) {
+    let expanded = expand_query(query, inverted_index, params);
+    (score(&expanded, inverted_index, k), expanded)
+}
+
+/// Expands `query` from its top `fb_docs` documents without ranking the
+/// expanded query.
+pub fn expand_query(query: &Query, inverted_index: &InvertedIndex, params: &FeedbackParams) -> Query {
+    let initial = score(query, inverted_index, params.fb_docs);
+    match params.model {
+        FeedbackModel::Rm3 => rm3(query, &initial, inverted_index, params),
+        FeedbackModel::Rocchio => {
+            let relevant: Vec<DocId> = initial.iter().map(|(doc_id, _score)| *doc_id).collect();
+            rocchio(
+                query,
+                &relevant,
+                &[],
+                RocchioWeights::from(params),
+                params.fb_terms,
+                inverted_index,
+            )
+        }
+    }
+}
+
+/// RM3: the relevance model `P(w|R) = sum_d P(w|d) * P(d|q)` estimated from
+/// the feedback documents, with `P(d|q)` taken as the document's share of the
+/// total retrieval score, truncated to `fb_terms` terms and interpolated with
+/// the normalised original query.
+pub fn rm3(
+    query: &Query,
+    feedback_docs: &[(DocId, f32)],
+    inverted_index: &InvertedIndex,
+    params: &FeedbackParams,
+) -> Query {
+    let score_sum: f32 = feedback_docs.iter().map(|(_doc_id, score)| score).sum();
+    if score_sum <= 0.0 {
+        return query.clone();
+    }
+
+    let mut relevance_model: HashMap<Term, f32> = HashMap::new();
+    for (doc_id, doc_score) in feedback_docs {
+        let Some((terms, doc_length)) = inverted_index.document_terms(*doc_id) else {
+            continue;
+        };
+        let p_doc = doc_score / score_sum;
+        for (token, tf) in terms {
+            let p_term = tf as f32 / doc_length.max(1) as f32;
+            *relevance_model.entry(token.to_string()).or_insert(0.0) += p_term * p_doc;
+        }
+    }
+
+    let expansion = top_terms(relevance_model, params.fb_terms);
+    let expansion_sum: f32 = expansion.iter().map(|(_token, weight)| weight).sum();
+    let query_sum: f32 = query.terms.iter().map(|term| term.weight).sum();
+
+    let mut weights: HashMap<Term, f32> = HashMap::new();
+    for term in &query.terms {
+        *weights.entry(term.token.clone()).or_insert(0.0) +=
+            params.original_weight * term.weight / query_sum.max(f32::EPSILON);
+    }
+    for (token, weight) in expansion {
+        *weights.entry(token).or_insert(0.0) +=
+            (1.0 - params.original_weight) * weight / expansion_sum.max(f32::EPSILON);
+    }
+
+    weighted_query(query, weights)
+}
+
+/// Weights of the Rocchio formula `q' = alpha * q + beta * centroid(R) -
+/// gamma * centroid(N)`.
+#[derive(Debug, Clone, Copy)]
+pub struct RocchioWeights {
+    pub alpha: f32,
+    pub beta: f32,
+    pub gamma: f32,
+}
+
+impl From<&FeedbackParams> for RocchioWeights {
+    fn from(params: &FeedbackParams) -> Self {
+        RocchioWeights {
+            alpha: params.original_weight,
+            beta: 1.0 - params.original_weight,
+            gamma: 0.0,
+        }
+    }
+}
+
+/// Rocchio over tf-idf document vectors (`tf / |d| * idf`). The centroids
+/// are scaled so that their largest component is 1, which keeps them on the
+/// same scale as the query weights; only the `fb_terms` best new terms are
+/// added and terms ending up with a non-positive weight are removed.
+pub fn rocchio(
+    query: &Query,
+    relevant: &[DocId],
+    nonrelevant: &[DocId],
+    weights: RocchioWeights,
+    fb_terms: usize,
+    inverted_index: &InvertedIndex,
+) -> Query {
+    let mut feedback: HashMap<Term, f32> = HashMap::new();
+    for (token, weight) in centroid(relevant, inverted_index) {
+        *feedback.entry(token).or_insert(0.0) += weights.beta * weight;
+    }
+    for (token, weight) in centroid(nonrelevant, inverted_index) {
+        *feedback.entry(token).or_insert(0.0) -= weights.gamma * weight;
+    }
+
+    let mut combined: HashMap<Term, f32> = HashMap::new();
+    for term in &query.terms {
+        let feedback_weight = feedback.remove(&term.token).unwrap_or(0.0);
+        combined.insert(
+            term.token.clone(),
+            weights.alpha * term.weight + feedback_weight,
+        );
+    }
+    combined.extend(top_terms(feedback, fb_terms));
+
+    combined.retain(|_token, weight| *weight > 0.0);
+    weighted_query(query, combined)
+}
+
+fn centroid(doc_ids: &[DocId], inverted_index: &InvertedIndex) -> HashMap<Term, f32> {
+    let mut centroid: HashMap<Term, f32> = HashMap::new();
+    if doc_ids.is_empty() {
+        return centroid;
+    }
+
+    for doc_id in doc_ids {
+        let Some((terms, doc_length)) = inverted_index.document_terms(*doc_id) else {
+            continue;
+        };
+        for (token, tf) in terms {
+            let df = inverted_index
+                .dictionary
+                .get(token)
+                .map(|postings| postings.df())
+                .unwrap_or(0);
+            let weight = tf as f32 / doc_length.max(1) as f32 * idf(df, inverted_index.n);
+            *centroid.entry(token.to_string()).or_insert(0.0) += weight / doc_ids.len() as f32;
+        }
+    }
+
+    let max_weight = centroid.values().copied().fold(0.0_f32, f32::max);
+    if max_weight > 0.0 {
+        centroid.values_mut().for_each(|weight| *weight /= max_weight);
+    }
+    centroid
+}
+
+fn top_terms(weights: HashMap<Term, f32>, count: usize) -> Vec<(Term, f32)> {
+    let mut ranked: Vec<(Term, f32)> = weights.into_iter().filter(|(_token, weight)| *weight > 0.0).collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked.truncate(count);
+    ranked
+}
+
+/// Builds the reformulated query, keeping the original expansions for
+/// display and listing the original terms first.
+fn weighted_query(original: &Query, mut weights: HashMap<Term, f32>) -> Query {
+    let mut query = Query {
+        terms: Vec::new(),
+        expansions: original.expansions.clone(),
+    };
+
+    for term in &original.terms {
+        if let Some(weight) = weights.remove(&term.token) {
+            query.terms.push(QueryTerm {
+                token: term.token.clone(),
+                weight,
+            });
+        }
+    }
+
+    let mut added: Vec<(Term, f32)> = weights.into_iter().collect();
+    added.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    query
+        .terms
+        .extend(added.into_iter().map(|(token, weight)| QueryTerm { token, weight }));
+
+    query
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preprocessing::tokenizer::{Analyzer, AnalyzerStep},
+        types::{DocIdx, Posting},
+    };
+
+    /// An index over documents `1..` with the given term frequencies and
+    /// matching term vectors.
+    fn index(documents: &[&[(&str, u32)]]) -> InvertedIndex {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        for (doc_idx, terms) in documents.iter().enumerate() {
+            let doc_length = terms.iter().map(|(_term, tf)| tf).sum();
+            inverted_index.add_document(doc_idx as DocId + 1, String::new(), doc_length, 0);
+            for (term, tf) in *terms {
+                inverted_index
+                    .add_posting(term.to_string(), Posting::new(doc_idx as DocIdx, *tf))
+                    .unwrap();
+            }
+        }
+        inverted_index.n = documents.len() as u32;
+        inverted_index.avdl = inverted_index.doc_lengths.iter().sum::<u32>() as f32 / documents.len() as f32;
+        inverted_index.rebuild_vocabulary();
+        for (doc_idx, terms) in documents.iter().enumerate() {
+            let positions = terms.iter().map(|(term, tf)| (term.to_string(), (0..*tf).collect())).collect();
+            inverted_index.set_term_vector(doc_idx as DocIdx, positions);
+        }
+        inverted_index
+    }
+
+    fn weight(query: &Query, token: &str) -> Option<f32> {
+        query.terms.iter().find(|term| term.token == token).map(|term| term.weight)
+    }
+
+    fn sample_index() -> InvertedIndex {
+        index(&[
+            &[("flow", 2), ("wing", 2)],
+            &[("flow", 1), ("shock", 3)],
+            &[("other", 1)],
+            &[("other", 1)],
+            &[("other", 1)],
+        ])
+    }
+
+    #[test]
+    fn rm3_interpolates_normalised_relevance_model_weights() {
+        let inverted_index = sample_index();
+        let query = Query::parse("flow", &inverted_index);
+        let params = FeedbackParams {
+            fb_terms: 2,
+            ..FeedbackParams::default()
+        };
+
+        // P(d|q) is 3/4 and 1/4, so P(flow|R) = 7/16, P(wing|R) = 6/16 and
+        // P(shock|R) = 3/16, of which the top two are kept and renormalised.
+        let expanded = rm3(&query, &[(1, 3.0), (2, 1.0)], &inverted_index, &params);
+
+        let total: f32 = expanded.terms.iter().map(|term| term.weight).sum();
+        assert!((total - 1.0).abs() < 1e-6);
+        assert!((weight(&expanded, "flow").unwrap() - (0.5 + 0.5 * 7.0 / 13.0)).abs() < 1e-6);
+        assert!((weight(&expanded, "wing").unwrap() - 0.5 * 6.0 / 13.0).abs() < 1e-6);
+        assert_eq!(weight(&expanded, "shock"), None);
+        assert_eq!(expanded.terms[0].token, "flow");
+    }
+
+    #[test]
+    fn rocchio_moves_towards_relevant_and_away_from_nonrelevant_documents() {
+        let inverted_index = sample_index();
+        let query = Query::parse("flow", &inverted_index);
+        let weights = RocchioWeights {
+            alpha: 1.0,
+            beta: 0.75,
+            gamma: 0.15,
+        };
+
+        let expanded = rocchio(&query, &[1], &[2], weights, 10, &inverted_index);
+
+        let idf_flow = idf(2, 5);
+        let idf_rare = idf(1, 5);
+        let relevant_flow = 0.5 * idf_flow / (0.5 * idf_rare);
+        let nonrelevant_flow = 0.25 * idf_flow / (0.75 * idf_rare);
+        let flow = 1.0 + 0.75 * relevant_flow - 0.15 * nonrelevant_flow;
+        assert!((weight(&expanded, "flow").unwrap() - flow).abs() < 1e-5);
+        assert!((weight(&expanded, "wing").unwrap() - 0.75).abs() < 1e-6);
+        // Only found in the nonrelevant document, so its weight is negative.
+        assert_eq!(weight(&expanded, "shock"), None);
+    }
+
+    #[test]
+    fn feedback_without_matches_keeps_the_query() {
+        let inverted_index = sample_index();
+        let query = Query::parse("turbulence", &inverted_index);
+        let expanded = expand_query(&query, &inverted_index, &FeedbackParams::default());
+
+        let tokens: Vec<&str> = expanded.tokens().collect();
+        assert_eq!(tokens, vec!["turbulence"]);
+    }
+}
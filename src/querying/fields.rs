@@ -0,0 +1,108 @@
+use crate::{
+    querying::{
+        query::Query,
+        score::{idf, score_component, with_accumulators},
+    },
+    types::{DocId, DocIdx, InvertedIndex, Posting, PostingsList},
+};
+
+/// Part of a document that `score_field` ranks on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Title,
+    /// Everything indexed except the title.
+    Body,
+}
+
+/// BM25 over a single field, with tf, df, document length and average length
+/// all taken from that field. The title's share of a posting is read from
+/// the term vector: its positions below the document's title length.
+pub fn score_field(query: &Query, inverted_index: &InvertedIndex, field: Field, k: usize) -> Vec<(DocId, f32)> {
+    let n = inverted_index.n;
+    let avg_length = average_field_length(inverted_index, field);
+
+    with_accumulators(inverted_index.doc_lengths.len(), |accumulators| {
+        for term in &query.terms {
+            let (Some(postings), Some(term_id)) = (
+                inverted_index.dictionary.get(&term.token),
+                inverted_index.vocabulary.term_id(&term.token),
+            ) else {
+                continue;
+            };
+            let field_postings = field_postings(inverted_index, postings, term_id, field);
+            let idf_j = idf(field_postings.len() as u32, n) * term.weight;
+
+            for posting in field_postings {
+                let l_di = field_length(inverted_index, posting.doc_idx, field) as f32;
+                let score = idf_j * score_component(posting.tf as f32, l_di, avg_length, inverted_index.bm25);
+                accumulators.add(posting.doc_idx, score);
+            }
+        }
+
+        accumulators
+            .top_k(k)
+            .into_iter()
+            .map(|(doc_idx, score)| (inverted_index.doc_id(doc_idx), score))
+            .collect()
+    })
+}
+
+/// Number of tokens of `field` in `doc_idx`.
+pub(crate) fn field_length(inverted_index: &InvertedIndex, doc_idx: DocIdx, field: Field) -> u32 {
+    let idx = doc_idx as usize;
+    let title_length = inverted_index.title_lengths.get(idx).copied().unwrap_or(0);
+    match field {
+        Field::Title => title_length,
+        Field::Body => inverted_index.doc_lengths[idx].saturating_sub(title_length),
+    }
+}
+
+pub(crate) fn average_field_length(inverted_index: &InvertedIndex, field: Field) -> f32 {
+    let total_length: u32 = (0..inverted_index.doc_lengths.len() as DocIdx)
+        .map(|doc_idx| field_length(inverted_index, doc_idx, field))
+        .sum();
+    total_length as f32 / inverted_index.n.max(1) as f32
+}
+
+/// The postings of a term restricted to `field`: tf counts only the field's
+/// occurrences, and documents without any are left out.
+pub(crate) fn field_postings(
+    inverted_index: &InvertedIndex,
+    postings: &PostingsList,
+    term_id: u32,
+    field: Field,
+) -> Vec<Posting> {
+    postings
+        .iter()
+        .map(|posting| Posting::new(posting.doc_idx, field_tf(inverted_index, posting, term_id, field)))
+        .filter(|posting| posting.tf > 0)
+        .collect()
+}
+
+/// How often the term of `posting` occurs in `field` of its document.
+pub(crate) fn field_tf(inverted_index: &InvertedIndex, posting: &Posting, term_id: u32, field: Field) -> u32 {
+    let title_tf = title_tf(inverted_index, posting.doc_idx, term_id);
+    match field {
+        Field::Title => title_tf,
+        Field::Body => posting.tf.saturating_sub(title_tf),
+    }
+}
+
+/// How often `term_id` occurs in the title of `doc_idx`.
+fn title_tf(inverted_index: &InvertedIndex, doc_idx: DocIdx, term_id: u32) -> u32 {
+    let idx = doc_idx as usize;
+    let title_length = inverted_index.title_lengths.get(idx).copied().unwrap_or(0);
+    let Some(term_vector) = inverted_index.term_vectors.get(idx) else {
+        return 0;
+    };
+    term_vector
+        .binary_search_by_key(&term_id, |entry| entry.term_id)
+        .map(|position| {
+            term_vector[position]
+                .positions
+                .iter()
+                .filter(|&&token_position| token_position < title_length)
+                .count() as u32
+        })
+        .unwrap_or(0)
+}
@@ -0,0 +1,203 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    querying::{
+        fields::{score_field, Field},
+        language_model::{score_lm_dirichlet, DEFAULT_MU},
+        query::Query,
+        score::score_bm25,
+    },
+    types::{DocId, InvertedIndex},
+};
+
+/// Number of documents every run contributes to the fusion.
+pub const DEFAULT_FUSION_DEPTH: usize = 1000;
+/// Rank offset of reciprocal rank fusion, as proposed by Cormack et al.
+pub const DEFAULT_RRF_K: f32 = 60.0;
+
+/// How the rankings of several runs are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FusionMethod {
+    /// `sum 1 / (rrf_k + rank)`
+    Rrf,
+    /// Sum of the min-max normalised scores.
+    CombSum,
+    /// CombSUM times the number of runs that retrieved the document.
+    CombMnz,
+}
+
+impl FusionMethod {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "rrf" => Some(FusionMethod::Rrf),
+            "combsum" => Some(FusionMethod::CombSum),
+            "combmnz" => Some(FusionMethod::CombMnz),
+            _ => None,
+        }
+    }
+}
+
+/// A single ranking that takes part in the fusion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Run {
+    /// BM25 over the whole document.
+    Bm25,
+    /// BM25 over the title only.
+    Title,
+    /// BM25 over everything but the title.
+    Body,
+    /// Query likelihood with Dirichlet smoothing.
+    LmDirichlet,
+}
+
+impl Run {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "bm25" => Some(Run::Bm25),
+            "title" => Some(Run::Title),
+            "body" => Some(Run::Body),
+            "lm" => Some(Run::LmDirichlet),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Run {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Run::Bm25 => "bm25",
+            Run::Title => "title",
+            Run::Body => "body",
+            Run::LmDirichlet => "lm",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The runs to fuse, how deep each of them is read and how they are
+/// combined. `mu` is the Dirichlet prior of the language model run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FusionParams {
+    pub method: FusionMethod,
+    pub runs: Vec<Run>,
+    pub depth: usize,
+    pub rrf_k: f32,
+    pub mu: f32,
+}
+
+impl Default for FusionParams {
+    fn default() -> Self {
+        FusionParams {
+            method: FusionMethod::Rrf,
+            runs: vec![Run::Bm25, Run::Title, Run::Body, Run::LmDirichlet],
+            depth: DEFAULT_FUSION_DEPTH,
+            rrf_k: DEFAULT_RRF_K,
+            mu: DEFAULT_MU,
+        }
+    }
+}
+
+/// Ranks `query` with every run of `params` and returns the `k` best
+/// documents of the fused ranking, best first.
+pub fn score_fused(query: &Query, inverted_index: &InvertedIndex, k: usize, params: &FusionParams) -> Vec<(DocId, f32)> {
+    let rankings: Vec<Vec<(DocId, f32)>> = params
+        .runs
+        .iter()
+        .map(|run| match run {
+            Run::Bm25 => score_bm25(query, inverted_index, params.depth),
+            Run::Title => score_field(query, inverted_index, Field::Title, params.depth),
+            Run::Body => score_field(query, inverted_index, Field::Body, params.depth),
+            Run::LmDirichlet => score_lm_dirichlet(query, inverted_index, params.depth, params.mu),
+        })
+        .collect();
+
+    let mut fused = fuse(&rankings, params.method, params.rrf_k);
+    fused.truncate(k);
+    fused
+}
+
+/// Combines rankings (each best first) into one, best first. Ties go to the
+/// lower document id.
+pub fn fuse(rankings: &[Vec<(DocId, f32)>], method: FusionMethod, rrf_k: f32) -> Vec<(DocId, f32)> {
+    let mut combined: HashMap<DocId, (f32, u32)> = HashMap::new();
+    for ranking in rankings {
+        let scores: Vec<f32> = ranking.iter().map(|(_doc_id, score)| *score).collect();
+        let normalized = min_max(&scores);
+        for (rank, ((doc_id, _score), normalized)) in ranking.iter().zip(normalized).enumerate() {
+            let contribution = match method {
+                FusionMethod::Rrf => 1.0 / (rrf_k + (rank + 1) as f32),
+                FusionMethod::CombSum | FusionMethod::CombMnz => normalized,
+            };
+            let (sum, count) = combined.entry(*doc_id).or_insert((0.0, 0));
+            *sum += contribution;
+            *count += 1;
+        }
+    }
+
+    let mut fused: Vec<(DocId, f32)> = combined
+        .into_iter()
+        .map(|(doc_id, (sum, count))| match method {
+            FusionMethod::CombMnz => (doc_id, sum * count as f32),
+            _ => (doc_id, sum),
+        })
+        .collect();
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    fused
+}
+
+/// Scales scores to `[0, 1]`. A list whose scores are all equal maps to 1.
+pub fn min_max(scores: &[f32]) -> Vec<f32> {
+    let min = scores.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    scores
+        .iter()
+        .map(|score| if max > min { (score - min) / (max - min) } else { 1.0 })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runs() -> Vec<Vec<(DocId, f32)>> {
+        vec![
+            vec![(1, 10.0), (2, 9.5), (3, 0.0)],
+            vec![(1, 10.0), (3, 6.0), (4, 0.0)],
+        ]
+    }
+
+    fn assert_fused(fused: Vec<(DocId, f32)>, expected: &[(DocId, f32)]) {
+        let doc_ids: Vec<DocId> = fused.iter().map(|(doc_id, _score)| *doc_id).collect();
+        let expected_ids: Vec<DocId> = expected.iter().map(|(doc_id, _score)| *doc_id).collect();
+        assert_eq!(doc_ids, expected_ids);
+        for ((_doc_id, score), (_expected_id, expected_score)) in fused.iter().zip(expected) {
+            assert!((score - expected_score).abs() < 1e-6, "{:?}", fused);
+        }
+    }
+
+    #[test]
+    fn rrf_sums_reciprocal_ranks() {
+        assert_fused(
+            fuse(&runs(), FusionMethod::Rrf, DEFAULT_RRF_K),
+            &[
+                (1, 2.0 / 61.0),
+                (3, 1.0 / 63.0 + 1.0 / 62.0),
+                (2, 1.0 / 62.0),
+                (4, 1.0 / 63.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn combmnz_rewards_documents_found_by_several_runs() {
+        assert_fused(
+            fuse(&runs(), FusionMethod::CombSum, DEFAULT_RRF_K),
+            &[(1, 2.0), (2, 0.95), (3, 0.6), (4, 0.0)],
+        );
+        assert_fused(
+            fuse(&runs(), FusionMethod::CombMnz, DEFAULT_RRF_K),
+            &[(1, 4.0), (3, 1.2), (2, 0.95), (4, 0.0)],
+        );
+        assert_eq!(min_max(&[3.0, 3.0]), vec![1.0, 1.0]);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{
+    querying::{query::Query, score::with_accumulators},
+    types::{DocId, ImpactIndex, ImpactSegment, InvertedIndex},
+};
+
+/// Score-at-a-time evaluation over impact-ordered postings. The segments of
+/// all query tokens are processed in order of descending impact until the
+/// share of postings given by the index `budget` has been read; the remaining
+/// low-impact segments are skipped. Impacts are scaled by the query weight
+/// of their token.
+pub fn score_impacts(
+    query: &Query,
+    inverted_index: &InvertedIndex,
+    impacts: &ImpactIndex,
+    k: usize,
+) -> Vec<(DocId, f32)> {
+    let mut segments: Vec<(&ImpactSegment, f32)> = query
+        .terms
+        .iter()
+        .filter_map(|term| {
+            let segments = impacts.segments.get(&term.token)?;
+            Some(segments.iter().map(move |segment| (segment, term.weight)))
+        })
+        .flatten()
+        .collect();
+    segments.sort_by(|a, b| {
+        let a_impact = a.0.impact as f32 * a.1;
+        let b_impact = b.0.impact as f32 * b.1;
+        b_impact.total_cmp(&a_impact)
+    });
+
+    let total: usize = segments.iter().map(|(segment, _weight)| segment.docs.len()).sum();
+    let limit = (total as f32 * impacts.budget).ceil() as usize;
+
+    with_accumulators(inverted_index.doc_lengths.len(), |accumulators| {
+        let mut processed: usize = 0;
+        for (segment, weight) in segments {
+            if processed >= limit {
+                break;
+            }
+            let impact = segment.impact as f32 * weight;
+            for &doc_idx in &segment.docs {
+                accumulators.add(doc_idx, impact);
+            }
+            processed += segment.docs.len();
+        }
+
+        accumulators
+            .top_k(k)
+            .into_iter()
+            .map(|(doc_idx, impact)| (inverted_index.doc_id(doc_idx), impact * impacts.scale))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preprocessing::{
+            impacts::build_impact_index,
+            tokenizer::{Analyzer, AnalyzerStep},
+        },
+        querying::score::score_bm25,
+        types::Posting,
+    };
+
+    /// Ten documents of equal length holding `a` 1 to 10 times, filled up
+    /// with `z`, and twenty documents without either term.
+    fn graded_index() -> InvertedIndex {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        for doc_idx in 0..30 {
+            inverted_index.add_document(doc_idx + 1, String::new(), 11, 0);
+            let terms = if doc_idx < 10 {
+                vec![("a", doc_idx + 1), ("z", 10 - doc_idx)]
+            } else {
+                vec![("other", 11)]
+            };
+            for (term, tf) in terms {
+                inverted_index.add_posting(term.to_string(), Posting::new(doc_idx, tf)).unwrap();
+            }
+        }
+        inverted_index.n = 30;
+        inverted_index.avdl = 11.0;
+        inverted_index.rebuild_vocabulary();
+        inverted_index
+    }
+
+    fn ranked_docs(ranked_results: &[(DocId, f32)]) -> Vec<DocId> {
+        ranked_results.iter().map(|(doc_id, _score)| *doc_id).collect()
+    }
+
+    #[test]
+    fn the_budget_stops_after_its_share_of_postings() {
+        let inverted_index = graded_index();
+        let query = Query::parse("a", &inverted_index);
+
+        for (budget, expected) in [(0.5, vec![10, 9, 8, 7, 6]), (0.2, vec![10, 9])] {
+            let impacts = build_impact_index(&inverted_index, budget);
+            let results = score_impacts(&query, &inverted_index, &impacts, 10);
+            assert_eq!(ranked_docs(&results), expected, "budget {}", budget);
+        }
+    }
+
+    #[test]
+    fn a_full_budget_ranks_like_exact_bm25() {
+        let mut inverted_index = graded_index();
+        let query = Query::parse("a z", &inverted_index);
+        let exact = score_bm25(&query, &inverted_index, 10);
+
+        inverted_index.impacts = Some(build_impact_index(&inverted_index, 1.0));
+        let impacts = score_bm25(&query, &inverted_index, 10);
+
+        // Every query term rounds its impact up by less than one level.
+        let scale = inverted_index.impacts.as_ref().unwrap().scale;
+        assert_eq!(ranked_docs(&impacts), ranked_docs(&exact));
+        for ((_doc_id, quantized), (_exact_doc_id, score)) in impacts.iter().zip(&exact) {
+            assert!((quantized - score).abs() <= 2.0 * scale);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crate::types::{DocIdx, PostingsList};
+
+/// Returns the documents that appear in every one of `lists`, in ascending
+/// `DocIdx` order. The shortest list drives the intersection and every other
+/// list is probed through a galloping cursor, so long lists are mostly
+/// skipped block by block.
+pub fn intersect(lists: &[&PostingsList]) -> Vec<DocIdx> {
+    let mut ordered: Vec<&PostingsList> = lists.to_vec();
+    ordered.sort_by_key(|list| list.len());
+
+    let Some((driver, others)) = ordered.split_first() else {
+        return Vec::new();
+    };
+
+    let mut cursors: Vec<_> = others.iter().map(|list| list.cursor()).collect();
+    let mut result: Vec<DocIdx> = Vec::new();
+
+    'candidates: for posting in driver.iter() {
+        let candidate = posting.doc_idx;
+        for cursor in cursors.iter_mut() {
+            match cursor.advance_to(candidate) {
+                Some(found) if found.doc_idx == candidate => {}
+                Some(_) => continue 'candidates,
+                None => break 'candidates,
+            }
+        }
+        result.push(candidate);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::types::Posting;
+
+    /// Deterministic pseudo-random doc ids: every id below `n` is kept with
+    /// roughly `density` probability.
+    fn random_list(seed: u64, n: DocIdx, density: f64) -> BTreeSet<DocIdx> {
+        let mut state = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (0..n)
+            .filter(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                ((state >> 33) as f64 / (1u64 << 31) as f64) < density
+            })
+            .collect()
+    }
+
+    fn postings(doc_idxs: &BTreeSet<DocIdx>) -> PostingsList {
+        doc_idxs.iter().map(|doc_idx| Posting::new(*doc_idx, 1)).collect()
+    }
+
+    fn naive_intersection(sets: &[BTreeSet<DocIdx>]) -> Vec<DocIdx> {
+        let Some((first, rest)) = sets.split_first() else {
+            return Vec::new();
+        };
+        first
+            .iter()
+            .copied()
+            .filter(|doc_idx| rest.iter().all(|set| set.contains(doc_idx)))
+            .collect()
+    }
+
+    #[test]
+    fn matches_naive_set_intersection() {
+        let densities = [0.001, 0.01, 0.05, 0.3, 0.9];
+        for (seed, n) in [(1, 200), (2, 5_000), (3, 20_000)] {
+            for (i, &sparse) in densities.iter().enumerate() {
+                for &dense in &densities[i..] {
+                    let sets = [
+                        random_list(seed, n, sparse),
+                        random_list(seed + 100, n, dense),
+                        random_list(seed + 200, n, (sparse + dense) / 2.0),
+                    ];
+                    let lists: Vec<PostingsList> = sets.iter().map(postings).collect();
+
+                    for count in 1..=sets.len() {
+                        let refs: Vec<&PostingsList> = lists[..count].iter().collect();
+                        assert_eq!(
+                            intersect(&refs),
+                            naive_intersection(&sets[..count]),
+                            "seed {} n {} densities {} {} lists {}",
+                            seed,
+                            n,
+                            sparse,
+                            dense,
+                            count
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn handles_empty_and_disjoint_lists() {
+        let evens: BTreeSet<DocIdx> = (0..500).map(|i| i * 2).collect();
+        let odds: BTreeSet<DocIdx> = (0..500).map(|i| i * 2 + 1).collect();
+        let empty = BTreeSet::new();
+
+        assert!(intersect(&[]).is_empty());
+        assert!(intersect(&[&postings(&evens), &postings(&odds)]).is_empty());
+        assert!(intersect(&[&postings(&evens), &postings(&empty)]).is_empty());
+        assert_eq!(intersect(&[&postings(&evens)]), evens.into_iter().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{
+    querying::{query::Query, score::with_accumulators},
+    types::{DocId, InvertedIndex},
+};
+
+/// Dirichlet prior of `score_lm_dirichlet` when none is given.
+pub const DEFAULT_MU: f32 = 2000.0;
+
+/// Query likelihood with Dirichlet smoothing in its rank-equivalent form:
+/// `sum_t w_t * ln(1 + tf / (mu * P(t|C))) + |q| * ln(mu / (|d| + mu))`,
+/// where `|q|` is the total query weight. Only documents containing at
+/// least one query term are ranked.
+pub fn score_lm_dirichlet(query: &Query, inverted_index: &InvertedIndex, k: usize, mu: f32) -> Vec<(DocId, f32)> {
+    let doc_lengths = &inverted_index.doc_lengths;
+    let collection_length: f32 = doc_lengths.iter().map(|&length| length as f32).sum();
+    if collection_length == 0.0 {
+        return Vec::new();
+    }
+
+    with_accumulators(doc_lengths.len(), |accumulators| {
+        let mut query_weight = 0.0;
+        for term in &query.terms {
+            query_weight += term.weight;
+            let Some(postings) = inverted_index.dictionary.get(&term.token) else {
+                continue;
+            };
+            let cf: u32 = postings.iter().map(|posting| posting.tf).sum();
+            let p_collection = cf as f32 / collection_length;
+
+            for posting in postings {
+                let score = term.weight * (1.0 + posting.tf as f32 / (mu * p_collection)).ln();
+                accumulators.add(posting.doc_idx, score);
+            }
+        }
+        accumulators.add_to_touched(|doc_idx| {
+            query_weight * (mu / (doc_lengths[doc_idx as usize] as f32 + mu)).ln()
+        });
+
+        accumulators
+            .top_k(k)
+            .into_iter()
+            .map(|(doc_idx, score)| (inverted_index.doc_id(doc_idx), score))
+            .collect()
+    })
+}
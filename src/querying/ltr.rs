@@ -0,0 +1,150 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    error::{Error, Result},
+    querying::{
+        features::{normalize_features, FeatureExtractor, FeatureVector, FEATURE_COUNT, FEATURE_NAMES},
+        language_model::DEFAULT_MU,
+        query::Query,
+    },
+    types::{DocId, InvertedIndex},
+};
+
+/// Model file written by `::ltr train` unless another path is given.
+pub const DEFAULT_MODEL_PATH: &str = "ltr.model";
+/// Number of first-stage documents a model reranks.
+pub const DEFAULT_CANDIDATES: usize = 100;
+const MODEL_HEADER: &str = "mini-retrieve ltr 1";
+
+/// Linear reranker over the per-query normalised features of the top
+/// `candidates` documents of the first-stage ranking.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearModel {
+    pub weights: FeatureVector,
+    pub candidates: usize,
+}
+
+impl Default for LinearModel {
+    /// Ranks by BM25 alone.
+    fn default() -> Self {
+        let mut weights = [0.0; FEATURE_COUNT];
+        weights[0] = 1.0;
+        LinearModel {
+            weights,
+            candidates: DEFAULT_CANDIDATES,
+        }
+    }
+}
+
+impl LinearModel {
+    pub fn score(&self, features: &FeatureVector) -> f32 {
+        self.weights.iter().zip(features).map(|(weight, feature)| weight * feature).sum()
+    }
+
+    /// Reorders the first `candidates` documents of a ranking by the model
+    /// score; documents beyond them are dropped. Ties keep the first-stage
+    /// order.
+    pub fn rerank(&self, query: &Query, ranked_results: &[(DocId, f32)], inverted_index: &InvertedIndex) -> Vec<(DocId, f32)> {
+        let candidates: Vec<_> = ranked_results
+            .iter()
+            .take(self.candidates)
+            .filter_map(|(doc_id, _score)| Some((*doc_id, inverted_index.doc_idx(*doc_id)?)))
+            .collect();
+        let extractor = FeatureExtractor::new(query, inverted_index, DEFAULT_MU);
+        let mut features: Vec<FeatureVector> = candidates
+            .iter()
+            .map(|(_doc_id, doc_idx)| extractor.features(*doc_idx))
+            .collect();
+        normalize_features(&mut features);
+
+        let mut reranked: Vec<(DocId, f32)> = candidates
+            .iter()
+            .zip(&features)
+            .map(|((doc_id, _doc_idx), features)| (*doc_id, self.score(features)))
+            .collect();
+        reranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        reranked
+    }
+
+    /// Writes the model as a header, the candidate count and one
+    /// `<feature> <weight>` line per feature.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", MODEL_HEADER)?;
+        writeln!(out, "candidates {}", self.candidates)?;
+        for (name, weight) in FEATURE_NAMES.iter().zip(self.weights) {
+            writeln!(out, "{} {}", name, weight)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Reads a model written by `save`. Features missing from the file get
+    /// a weight of 0.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).map_err(|e| Error::open("model", path, e))?;
+        let mut lines = content.lines().enumerate();
+        if lines.next().map(|(_number, line)| line) != Some(MODEL_HEADER) {
+            return Err(Error::parse(path, Some(1), "not a mini-retrieve ltr model"));
+        }
+
+        let mut model = LinearModel {
+            weights: [0.0; FEATURE_COUNT],
+            candidates: DEFAULT_CANDIDATES,
+        };
+        for (number, line) in lines.filter(|(_number, line)| !line.trim().is_empty()) {
+            let invalid = |message: &str| Error::parse(path, Some(number + 1), message);
+            let Some((name, value)) = line.split_once(' ') else {
+                return Err(invalid("expected '<name> <value>'"));
+            };
+            if name == "candidates" {
+                model.candidates = value
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|candidates| *candidates > 0)
+                    .ok_or_else(|| invalid("the candidate count has to be a positive number"))?;
+                continue;
+            }
+            let feature = FEATURE_NAMES
+                .iter()
+                .position(|feature| *feature == name)
+                .ok_or_else(|| invalid(&format!("unknown feature '{}'", name)))?;
+            model.weights[feature] = value
+                .trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|weight| weight.is_finite())
+                .ok_or_else(|| invalid("weights have to be numbers"))?;
+        }
+        Ok(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn models_survive_a_round_trip_and_reject_unknown_features() {
+        let path = std::env::temp_dir().join(format!("mini-retrieve-ltr-{}.model", std::process::id()));
+        let mut model = LinearModel {
+            candidates: 50,
+            ..LinearModel::default()
+        };
+        model.weights[0] = 0.625;
+        model.weights[5] = -0.375;
+
+        model.save(&path).unwrap();
+        assert_eq!(LinearModel::load(&path).unwrap(), model);
+
+        fs::write(&path, format!("{}\nbm25 1\nclicks 0.5\n", MODEL_HEADER)).unwrap();
+        let error = LinearModel::load(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(error, Error::Parse { line: Some(3), .. }), "{}", error);
+    }
+}
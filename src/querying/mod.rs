@@ -1 +1,16 @@
 pub mod score;
+pub mod intersect;
+pub mod phrase;
+pub mod impact;
+pub mod query;
+pub mod spelling;
+pub mod feedback;
+pub mod similar;
+pub mod snippet;
+pub mod explain;
+pub mod batch;
+pub mod fields;
+pub mod language_model;
+pub mod fusion;
+pub mod features;
+pub mod ltr;
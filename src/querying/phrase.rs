@@ -0,0 +1,152 @@
+use crate::{
+    querying::{intersect::intersect, query::Query, score::score_matches},
+    types::{DocId, DocIdx, InvertedIndex, PostingsList},
+};
+
+/// The analysed tokens of `text` in their order, repetitions included.
+/// Stopwords are dropped like in documents, whose positions only count
+/// indexed tokens, so "layer of the flow" matches "layer flow".
+pub fn phrase_tokens(text: &str, inverted_index: &InvertedIndex) -> Vec<String> {
+    text.split_whitespace()
+        .filter_map(|word| inverted_index.analyzer.analyze(word))
+        .collect()
+}
+
+/// Documents holding `tokens` at consecutive positions, in ascending
+/// `DocIdx` order. Candidates containing every token come from `intersect`;
+/// their positions are then checked in the term vectors.
+pub fn match_phrase(tokens: &[String], inverted_index: &InvertedIndex) -> Vec<DocIdx> {
+    let lists: Option<Vec<&PostingsList>> = tokens
+        .iter()
+        .map(|token| inverted_index.dictionary.get(token))
+        .collect();
+    let Some(lists) = lists.filter(|lists| !lists.is_empty()) else {
+        return Vec::new();
+    };
+    let Some(term_ids) = tokens
+        .iter()
+        .map(|token| inverted_index.vocabulary.term_id(token))
+        .collect::<Option<Vec<u32>>>()
+    else {
+        return Vec::new();
+    };
+
+    intersect(&lists)
+        .into_iter()
+        .filter(|&doc_idx| contains_phrase(inverted_index, doc_idx, &term_ids))
+        .collect()
+}
+
+/// Ranks the documents matching the phrase `text` with BM25 over its tokens
+/// and returns the `k` best together with the query that was scored.
+pub fn score_phrase(text: &str, inverted_index: &InvertedIndex, k: usize) -> (Query, Vec<(DocId, f32)>) {
+    let tokens = phrase_tokens(text, inverted_index);
+    let mut query = Query::default();
+    for token in &tokens {
+        query.add_term(token.clone(), 1.0);
+    }
+
+    let matches = match_phrase(&tokens, inverted_index);
+    let ranked_results = score_matches(&query, inverted_index, &matches, k);
+    (query, ranked_results)
+}
+
+fn contains_phrase(inverted_index: &InvertedIndex, doc_idx: DocIdx, term_ids: &[u32]) -> bool {
+    let Some(term_vector) = inverted_index.term_vectors.get(doc_idx as usize) else {
+        return false;
+    };
+    let positions: Option<Vec<&[u32]>> = term_ids
+        .iter()
+        .map(|term_id| {
+            let entry = term_vector.binary_search_by_key(term_id, |entry| entry.term_id).ok()?;
+            Some(term_vector[entry].positions.as_slice())
+        })
+        .collect();
+    let Some((first, rest)) = positions.as_deref().and_then(<[&[u32]]>::split_first) else {
+        return false;
+    };
+
+    first.iter().any(|&start| {
+        rest.iter()
+            .zip(1..)
+            .all(|(positions, offset)| positions.binary_search(&(start + offset)).is_ok())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preprocessing::tokenizer::{Analyzer, AnalyzerStep},
+        types::Posting,
+    };
+
+    /// An index over documents `1..` with the given token sequences.
+    fn index(documents: &[&str]) -> InvertedIndex {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        let mut document_positions = Vec::new();
+        for (doc_idx, text) in documents.iter().enumerate() {
+            let mut positions: Vec<(String, Vec<u32>)> = Vec::new();
+            for (position, token) in text.split_whitespace().enumerate() {
+                match positions.iter_mut().find(|(term, _positions)| term == token) {
+                    Some((_term, positions)) => positions.push(position as u32),
+                    None => positions.push((token.to_string(), vec![position as u32])),
+                }
+            }
+            let length = text.split_whitespace().count() as u32;
+            inverted_index.add_document(doc_idx as DocId + 1, String::new(), length, 0);
+            for (term, term_positions) in &positions {
+                inverted_index
+                    .add_posting(term.clone(), Posting::new(doc_idx as DocIdx, term_positions.len() as u32))
+                    .unwrap();
+            }
+            document_positions.push(positions);
+        }
+        inverted_index.n = documents.len() as u32;
+        inverted_index.avdl = inverted_index.doc_lengths.iter().sum::<u32>() as f32 / documents.len() as f32;
+        inverted_index.rebuild_vocabulary();
+        for (doc_idx, positions) in document_positions.into_iter().enumerate() {
+            inverted_index.set_term_vector(doc_idx as DocIdx, positions);
+        }
+        inverted_index
+    }
+
+    fn matched_docs(text: &str, inverted_index: &InvertedIndex) -> Vec<DocIdx> {
+        match_phrase(&phrase_tokens(text, inverted_index), inverted_index)
+    }
+
+    #[test]
+    fn phrases_need_consecutive_positions() {
+        let inverted_index = index(&[
+            "boundary layer flow",
+            "layer boundary flow",
+            "boundary flow layer boundary layer",
+            "boundary wing layer",
+        ]);
+
+        assert_eq!(matched_docs("boundary layer", &inverted_index), vec![0, 2]);
+        assert_eq!(matched_docs("Boundary Layer Flow", &inverted_index), vec![0]);
+        assert_eq!(matched_docs("layer boundary", &inverted_index), vec![1, 2]);
+        assert_eq!(matched_docs("flow", &inverted_index), vec![0, 1, 2]);
+        assert!(matched_docs("boundary shock", &inverted_index).is_empty());
+        assert!(matched_docs("", &inverted_index).is_empty());
+    }
+
+    #[test]
+    fn repeated_words_need_repeated_positions() {
+        let inverted_index = index(&["flow flow", "flow wing flow"]);
+
+        assert_eq!(matched_docs("flow flow", &inverted_index), vec![0]);
+    }
+
+    #[test]
+    fn phrase_scores_only_rank_matches() {
+        let inverted_index = index(&["boundary layer flow", "layer boundary", "wing", "shock"]);
+        let (query, ranked_results) = score_phrase("boundary layer", &inverted_index, 10);
+
+        assert_eq!(query.terms.len(), 2);
+        assert_eq!(ranked_results.len(), 1);
+        assert_eq!(ranked_results[0].0, 1);
+    }
+}
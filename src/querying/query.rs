@@ -0,0 +1,304 @@
+use crate::{
+    querying::spelling::levenshtein,
+    types::InvertedIndex,
+};
+
+/// Upper bound on the number of terms a single wildcard or fuzzy term may
+/// expand to. When more terms match, the closest and most frequent ones are
+/// kept.
+pub const MAX_EXPANSIONS: usize = 50;
+/// Largest edit distance accepted by the fuzzy operator (`term~2`).
+pub const MAX_FUZZY_DISTANCE: usize = 2;
+
+#[derive(Debug, Clone)]
+pub struct QueryTerm {
+    pub token: String,
+    pub weight: f32,
+}
+
+/// Records which weighted index terms a wildcard or fuzzy term of the query
+/// was expanded to.
+#[derive(Debug, Clone)]
+pub struct Expansion {
+    pub pattern: String,
+    pub terms: Vec<QueryTerm>,
+    pub dropped: usize,
+}
+
+/// An analysed query: the weighted tokens that get scored, plus the
+/// expansions that produced some of them.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    pub terms: Vec<QueryTerm>,
+    pub expansions: Vec<Expansion>,
+}
+
+impl Query {
+    /// Tokenizes `text` word by word. Words containing `*`, or `?` anywhere
+    /// but at their end, are wildcards: `aero*` (prefix), `*dynamic` (suffix)
+    /// and `b?und*` (general pattern) are expanded against the vocabulary
+    /// into OR'ed terms. `term~1` and `term~2` expand to every term within
+    /// that edit distance, weighted by `1 / (1 + distance)`; larger distances
+    /// are clamped to `MAX_FUZZY_DISTANCE`, the expansion keeps the requested
+    /// pattern.
+    pub fn parse(text: &str, inverted_index: &InvertedIndex) -> Self {
+        let mut query = Query::default();
+
+        for word in text.split_whitespace() {
+            if let Some((base, distance)) = parse_fuzzy(word) {
+                query.expand_fuzzy(base, distance, inverted_index);
+            } else if is_wildcard(word) {
+                query.expand_wildcard(word, inverted_index);
+            } else if let Some(token) = inverted_index.analyzer.analyze(word) {
+                query.add_term(token, 1.0);
+            }
+        }
+
+        query
+    }
+
+    /// Adds `token` with `weight`; a token that is already part of the query
+    /// keeps the larger of both weights.
+    pub fn add_term(&mut self, token: String, weight: f32) {
+        match self.terms.iter_mut().find(|term| term.token == token) {
+            Some(term) => term.weight = term.weight.max(weight),
+            None => self.terms.push(QueryTerm { token, weight }),
+        }
+    }
+
+    /// Swaps `from` for `to`, e.g. to apply a spelling correction.
+    pub fn replace_token(&mut self, from: &str, to: String) {
+        let Some(position) = self.terms.iter().position(|term| term.token == from) else {
+            return;
+        };
+        let removed = self.terms.remove(position);
+        self.add_term(to, removed.weight);
+    }
+
+    /// The terms grouped by the query word they stem from: one group per
+    /// wildcard or fuzzy expansion and one per remaining term. A document
+    /// matches a word if it contains any term of its group.
+    pub fn word_groups(&self) -> Vec<Vec<&QueryTerm>> {
+        let expanded = |token: &str| {
+            self.expansions
+                .iter()
+                .any(|expansion| expansion.terms.iter().any(|term| term.token == token))
+        };
+        let mut groups: Vec<Vec<&QueryTerm>> = self
+            .expansions
+            .iter()
+            .map(|expansion| {
+                self.terms
+                    .iter()
+                    .filter(|term| expansion.terms.iter().any(|expanded| expanded.token == term.token))
+                    .collect()
+            })
+            .collect();
+        groups.extend(self.terms.iter().filter(|term| !expanded(&term.token)).map(|term| vec![term]));
+        groups
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = &str> {
+        self.terms.iter().map(|term| term.token.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    fn expand_wildcard(&mut self, word: &str, inverted_index: &InvertedIndex) {
+        let pattern: String = word
+            .to_lowercase()
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == '*' || *c == '?')
+            .collect();
+        let literal = pattern.trim_matches(['*', '?']);
+        if literal.is_empty() {
+            return;
+        }
+
+        let vocabulary = &inverted_index.vocabulary;
+        let is_literal = !literal.contains(['*', '?']);
+        let term_ids = if is_literal && pattern == format!("{literal}*") {
+            vocabulary.with_prefix(literal)
+        } else if is_literal && pattern == format!("*{literal}") {
+            vocabulary.with_suffix(literal)
+        } else {
+            vocabulary.matching_wildcard(&pattern)
+        };
+
+        let mut matches: Vec<(&str, usize)> = term_ids
+            .into_iter()
+            .map(|term_id| {
+                let token = vocabulary.term(term_id);
+                let df = inverted_index
+                    .dictionary
+                    .get(token)
+                    .map(|postings| postings.len())
+                    .unwrap_or(0);
+                (token, df)
+            })
+            .collect();
+        matches.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+        let dropped = matches.len().saturating_sub(MAX_EXPANSIONS);
+        matches.truncate(MAX_EXPANSIONS);
+
+        let terms: Vec<QueryTerm> = matches
+            .into_iter()
+            .map(|(token, _df)| QueryTerm {
+                token: token.to_string(),
+                weight: 1.0,
+            })
+            .collect();
+        self.push_expansion(pattern, terms, dropped);
+    }
+
+    fn expand_fuzzy(&mut self, base: &str, max_distance: usize, inverted_index: &InvertedIndex) {
+        let pattern = format!("{base}~{max_distance}");
+        let max_distance = max_distance.min(MAX_FUZZY_DISTANCE);
+        let Some(token) = inverted_index.analyzer.analyze(base) else {
+            return;
+        };
+        let token_length = token.chars().count();
+
+        let mut matches: Vec<(&str, usize, usize)> = inverted_index
+            .dictionary
+            .iter()
+            .filter(|(term, _postings)| term.chars().count().abs_diff(token_length) <= max_distance)
+            .filter_map(|(term, postings)| {
+                let distance = levenshtein(&token, term);
+                (distance <= max_distance).then_some((term.as_str(), distance, postings.len()))
+            })
+            .collect();
+        matches.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| b.2.cmp(&a.2)).then_with(|| a.0.cmp(b.0)));
+
+        let dropped = matches.len().saturating_sub(MAX_EXPANSIONS);
+        matches.truncate(MAX_EXPANSIONS);
+
+        let terms: Vec<QueryTerm> = matches
+            .into_iter()
+            .map(|(term, distance, _df)| QueryTerm {
+                token: term.to_string(),
+                weight: 1.0 / (1.0 + distance as f32),
+            })
+            .collect();
+        self.push_expansion(pattern, terms, dropped);
+    }
+
+    fn push_expansion(&mut self, pattern: String, terms: Vec<QueryTerm>, dropped: usize) {
+        for term in &terms {
+            self.add_term(term.token.clone(), term.weight);
+        }
+        self.expansions.push(Expansion {
+            pattern,
+            terms,
+            dropped,
+        });
+    }
+}
+
+pub(crate) fn is_wildcard(word: &str) -> bool {
+    word.contains('*') || word.trim_end_matches('?').contains('?')
+}
+
+/// Splits `term~N` into the term and its requested maximum edit distance,
+/// which may exceed `MAX_FUZZY_DISTANCE`.
+pub(crate) fn parse_fuzzy(word: &str) -> Option<(&str, usize)> {
+    let (base, distance) = word.rsplit_once('~')?;
+    let distance = distance.parse::<usize>().ok()?;
+    (!base.is_empty()).then_some((base, distance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preprocessing::tokenizer::{Analyzer, AnalyzerStep},
+        types::{DocIdx, Posting},
+    };
+
+    fn index(terms: &[&str]) -> InvertedIndex {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        inverted_index.add_document(1, String::new(), terms.len() as u32, 0);
+        for term in terms {
+            inverted_index.add_posting(term.to_string(), Posting::new(0 as DocIdx, 1)).unwrap();
+        }
+        inverted_index.n = 1;
+        inverted_index.rebuild_vocabulary();
+        inverted_index
+    }
+
+    fn weights(query: &Query) -> Vec<(&str, f32)> {
+        let mut weights: Vec<(&str, f32)> = query.terms.iter().map(|term| (term.token.as_str(), term.weight)).collect();
+        weights.sort_by(|a, b| a.0.cmp(b.0));
+        weights
+    }
+
+    #[test]
+    fn fuzzy_terms_are_weighted_by_edit_distance() {
+        let inverted_index = index(&["flow", "flows", "glows", "slowly"]);
+
+        let query = Query::parse("flow~2", &inverted_index);
+        assert_eq!(weights(&query), vec![("flow", 1.0), ("flows", 0.5), ("glows", 1.0 / 3.0)]);
+
+        let query = Query::parse("flow~1", &inverted_index);
+        assert_eq!(weights(&query), vec![("flow", 1.0), ("flows", 0.5)]);
+
+        let query = Query::parse("flos~1", &inverted_index);
+        assert_eq!(weights(&query), vec![("flow", 0.5), ("flows", 0.5)]);
+    }
+
+    #[test]
+    fn fuzzy_distances_above_the_maximum_are_clamped() {
+        let inverted_index = index(&["flow", "flows", "glows", "flow3"]);
+        let clamped = Query::parse("flow~2", &inverted_index);
+
+        for text in ["flow~3", "flow~9"] {
+            let query = Query::parse(text, &inverted_index);
+            assert_eq!(query.expansions.len(), 1);
+            assert_eq!(query.expansions[0].pattern, text);
+            assert_eq!(weights(&query), weights(&clamped));
+        }
+    }
+
+    #[test]
+    fn fuzzy_expansions_form_one_word_group() {
+        let inverted_index = index(&["flow", "flows", "wing"]);
+        let query = Query::parse("flow~1 wing", &inverted_index);
+        let groups: Vec<Vec<&str>> = query
+            .word_groups()
+            .into_iter()
+            .map(|group| group.into_iter().map(|term| term.token.as_str()).collect())
+            .collect();
+
+        assert_eq!(groups, vec![vec!["flow", "flows"], vec!["wing"]]);
+    }
+
+    #[test]
+    fn expansions_are_capped_at_the_most_frequent_terms() {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        inverted_index.add_document(1, String::new(), 60, 0);
+        inverted_index.add_document(2, String::new(), 10, 0);
+        let terms: Vec<String> = (0..60).map(|i| format!("aero{i:02}")).collect();
+        for term in &terms {
+            inverted_index.add_posting(term.clone(), Posting::new(0, 1)).unwrap();
+        }
+        for term in &terms[50..] {
+            inverted_index.add_posting(term.clone(), Posting::new(1, 1)).unwrap();
+        }
+        inverted_index.n = 2;
+        inverted_index.rebuild_vocabulary();
+
+        let query = Query::parse("aero*", &inverted_index);
+        let expansion = &query.expansions[0];
+        assert_eq!(query.terms.len(), MAX_EXPANSIONS);
+        assert_eq!(expansion.dropped, 10);
+
+        let kept: Vec<&str> = query.tokens().collect();
+        assert!(terms[50..].iter().all(|term| kept.contains(&term.as_str())));
+        assert!(terms[40..50].iter().all(|term| !kept.contains(&term.as_str())));
+    }
+}
@@ -1,51 +1,283 @@
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+};
 
 use crate::{
-    types::{DocId, InvertedIndex},
-    utils::calculate_document_tf,
+    querying::{impact::score_impacts, intersect::intersect, language_model::score_lm_dirichlet, query::Query},
+    types::{DocId, DocIdx, InvertedIndex, PostingsList},
 };
 
-const K1: f32 = 1.2;
-const B: f32 = 0.75;
+/// Free parameters of BM25: `k1` saturates the term frequency, `b` sets the
+/// strength of the document length normalisation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bm25Params {
+    pub k1: f32,
+    pub b: f32,
+}
+
+impl Default for Bm25Params {
+    fn default() -> Self {
+        Bm25Params { k1: 1.2, b: 0.75 }
+    }
+}
+
+/// Ranking function `score` ranks with.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ScoringModel {
+    #[default]
+    Bm25,
+    /// Query likelihood with Dirichlet smoothing, see `score_lm_dirichlet`.
+    LmDirichlet { mu: f32 },
+}
 
-pub fn score(query: String, inverted_index: &InvertedIndex) -> HashMap<u32, f32> {
-    let (qtf_map, _query_length) = calculate_document_tf(&query);
+thread_local! {
+    static ACCUMULATORS: RefCell<Accumulators> = RefCell::new(Accumulators::default());
+}
+
+/// Per-document score accumulators that survive across queries. Only the
+/// documents listed in `touched` are read and reset, so a query costs time
+/// proportional to its postings rather than to the collection size.
+#[derive(Debug, Default)]
+pub(crate) struct Accumulators {
+    scores: Vec<f32>,
+    seen: Vec<bool>,
+    touched: Vec<DocIdx>,
+}
+
+impl Accumulators {
+    /// Sizes the accumulators for `n` documents. Scores left behind by a
+    /// query that was aborted by a panic are cleared first.
+    fn prepare(&mut self, n: usize) {
+        for &doc_idx in &self.touched {
+            self.scores[doc_idx as usize] = 0.0;
+            self.seen[doc_idx as usize] = false;
+        }
+        self.touched.clear();
+        if self.scores.len() < n {
+            self.scores.resize(n, 0.0);
+            self.seen.resize(n, false);
+        }
+    }
+
+    pub(crate) fn add(&mut self, doc_idx: DocIdx, score: f32) {
+        let idx = doc_idx as usize;
+        if !self.seen[idx] {
+            self.seen[idx] = true;
+            self.touched.push(doc_idx);
+        }
+        self.scores[idx] += score;
+    }
+
+    /// Adds `f(doc_idx)` to the score of every document scored so far.
+    pub(crate) fn add_to_touched(&mut self, f: impl Fn(DocIdx) -> f32) {
+        for &doc_idx in &self.touched {
+            self.scores[doc_idx as usize] += f(doc_idx);
+        }
+    }
+
+    /// Drains the accumulators into the `k` best documents, best first.
+    pub(crate) fn top_k(&mut self, k: usize) -> Vec<(DocIdx, f32)> {
+        let mut heap: BinaryHeap<Reverse<Ranked>> =
+            BinaryHeap::with_capacity(k.min(self.touched.len()) + 1);
+
+        for &doc_idx in &self.touched {
+            let idx = doc_idx as usize;
+            let candidate = Ranked {
+                score: self.scores[idx],
+                doc_idx,
+            };
+            self.scores[idx] = 0.0;
+            self.seen[idx] = false;
+
+            if heap.len() < k {
+                heap.push(Reverse(candidate));
+            } else if let Some(Reverse(worst)) = heap.peek() {
+                if candidate > *worst {
+                    heap.pop();
+                    heap.push(Reverse(candidate));
+                }
+            }
+        }
+        self.touched.clear();
+
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(ranked)| (ranked.doc_idx, ranked.score))
+            .collect()
+    }
+}
+
+/// Runs `f` with this thread's accumulators sized for `n` documents.
+pub(crate) fn with_accumulators<R>(n: usize, f: impl FnOnce(&mut Accumulators) -> R) -> R {
+    ACCUMULATORS.with(|accumulators| {
+        let mut accumulators = accumulators.borrow_mut();
+        accumulators.prepare(n);
+        f(&mut accumulators)
+    })
+}
+
+/// Heap entry ordered by score; ties go to the lower document index.
+#[derive(Debug, Clone, Copy)]
+struct Ranked {
+    score: f32,
+    doc_idx: DocIdx,
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.doc_idx.cmp(&self.doc_idx))
+    }
+}
+
+/// Scores `query` with the scoring model of the index and returns the `k`
+/// best documents, best first.
+pub fn score(query: &Query, inverted_index: &InvertedIndex, k: usize) -> Vec<(DocId, f32)> {
+    match inverted_index.model {
+        ScoringModel::Bm25 => score_bm25(query, inverted_index, k),
+        ScoringModel::LmDirichlet { mu } => score_lm_dirichlet(query, inverted_index, k, mu),
+    }
+}
+
+/// Scores `query` with BM25 and returns the `k` best documents, best first.
+/// Indexes built in impact mode are evaluated on their quantized impacts.
+/// Every token's contribution is multiplied by its query weight.
+pub fn score_bm25(query: &Query, inverted_index: &InvertedIndex, k: usize) -> Vec<(DocId, f32)> {
+    if let Some(impacts) = &inverted_index.impacts {
+        return score_impacts(query, inverted_index, impacts, k);
+    }
 
     let n = inverted_index.n;
     let avdl = inverted_index.avdl;
-    let mut scores: HashMap<DocId, f32> = HashMap::new();
+    let doc_lengths = &inverted_index.doc_lengths;
+
+    with_accumulators(doc_lengths.len(), |accumulators| {
+        for term in &query.terms {
+            let Some(postings) = inverted_index.dictionary.get(&term.token) else {
+                continue;
+            };
+            let df_j = postings.df();
+            let idf_j = idf(df_j, n) * term.weight;
+
+            for posting in postings {
+                let tf_ij = posting.tf as f32;
+                let l_di = doc_lengths[posting.doc_idx as usize] as f32;
+
+                let score = idf_j * score_component(tf_ij, l_di, avdl, inverted_index.bm25);
+                accumulators.add(posting.doc_idx, score);
+            }
+        }
+
+        accumulators
+            .top_k(k)
+            .into_iter()
+            .map(|(doc_idx, score)| (inverted_index.doc_id(doc_idx), score))
+            .collect()
+    })
+}
+
+/// Conjunctive variant of `score_bm25`: only documents matching every query word
+/// are ranked. The terms a wildcard or fuzzy word expands to are OR'ed, so
+/// any one of them satisfies the word (see `Query::word_groups`). A word
+/// without any term in the dictionary empties the result.
+pub fn score_conjunctive(
+    query: &Query,
+    inverted_index: &InvertedIndex,
+    k: usize,
+) -> Vec<(DocId, f32)> {
+    let groups: Option<Vec<Vec<&PostingsList>>> = query
+        .word_groups()
+        .into_iter()
+        .map(|group| {
+            let lists: Vec<&PostingsList> = group
+                .into_iter()
+                .filter_map(|term| inverted_index.dictionary.get(&term.token))
+                .collect();
+            (!lists.is_empty()).then_some(lists)
+        })
+        .collect();
+    let Some(groups) = groups.filter(|groups| !groups.is_empty()) else {
+        return Vec::new();
+    };
 
-    qtf_map
+    let group_lists: Vec<Cow<PostingsList>> = groups
         .iter()
-        .for_each(|(token, _qtf)| match inverted_index.dictionary.get(token) {
-            Some(postings) => {
-                let df_j = postings.len() as u32;
-                let idf_j = idf(df_j, n);
-
-                for posting in postings {
-                    let doc_id = posting.doc_id;
-                    let tf_ij = posting.tf as f32;
-                    let l_di = inverted_index
-                        .doc_lengths
-                        .get(&doc_id)
-                        .copied()
-                        .unwrap_or(0) as f32;
-
-                    let score = idf_j * score_component(tf_ij, l_di, avdl);
-                    *scores.entry(doc_id).or_insert(0.0) += score;
+        .map(|lists| match lists.as_slice() {
+            [postings] => Cow::Borrowed(*postings),
+            _ => Cow::Owned(lists.iter().flat_map(|postings| postings.iter().cloned()).collect()),
+        })
+        .collect();
+    let matches = intersect(&group_lists.iter().map(|postings| postings.as_ref()).collect::<Vec<_>>());
+
+    score_matches(query, inverted_index, &matches, k)
+}
+
+/// Scores `query` with BM25 over the `matches` only, which have to be in
+/// ascending `DocIdx` order, and returns the `k` best of them.
+pub(crate) fn score_matches(
+    query: &Query,
+    inverted_index: &InvertedIndex,
+    matches: &[DocIdx],
+    k: usize,
+) -> Vec<(DocId, f32)> {
+    let n = inverted_index.n;
+    let avdl = inverted_index.avdl;
+    let doc_lengths = &inverted_index.doc_lengths;
+
+    with_accumulators(doc_lengths.len(), |accumulators| {
+        for term in &query.terms {
+            let Some(postings) = inverted_index.dictionary.get(&term.token) else {
+                continue;
+            };
+            let idf_j = idf(postings.df(), n) * term.weight;
+            let mut cursor = postings.cursor();
+
+            for &doc_idx in matches {
+                let Some(posting) = cursor.advance_to(doc_idx) else {
+                    break;
+                };
+                if posting.doc_idx != doc_idx {
+                    continue;
                 }
+                let tf_ij = posting.tf as f32;
+                let l_di = doc_lengths[doc_idx as usize] as f32;
+
+                accumulators.add(doc_idx, idf_j * score_component(tf_ij, l_di, avdl, inverted_index.bm25));
             }
-            None => {}
-        });
+        }
 
-    scores
+        accumulators
+            .top_k(k)
+            .into_iter()
+            .map(|(doc_idx, score)| (inverted_index.doc_id(doc_idx), score))
+            .collect()
+    })
 }
 
-fn score_component(tf_ij: f32, l_di: f32, avdl: f32) -> f32 {
-    let numerator = tf_ij * (K1 + 1.0);
+pub fn score_component(tf_ij: f32, l_di: f32, avdl: f32, params: Bm25Params) -> f32 {
+    let Bm25Params { k1, b } = params;
+    let numerator = tf_ij * (k1 + 1.0);
 
-    let length_norm = 1.0 - B + B * (l_di / avdl);
-    let demoninator = tf_ij + K1 * length_norm;
+    let length_norm = 1.0 - b + b * (l_di / avdl);
+    let demoninator = tf_ij + k1 * length_norm;
 
     numerator / demoninator
 }
@@ -56,3 +288,70 @@ pub fn idf(df_j: u32, n: u32) -> f32 {
 
     (dividend / divisor).ln().max(0.0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preprocessing::tokenizer::{Analyzer, AnalyzerStep},
+        types::Posting,
+    };
+
+    /// An index over documents `1..` holding the given terms once each.
+    fn index(documents: &[&[&str]]) -> InvertedIndex {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        for (doc_idx, terms) in documents.iter().enumerate() {
+            inverted_index.add_document(doc_idx as DocId + 1, String::new(), terms.len() as u32, 0);
+            for term in *terms {
+                inverted_index
+                    .add_posting(term.to_string(), Posting::new(doc_idx as DocIdx, 1))
+                    .unwrap();
+            }
+        }
+        inverted_index.n = documents.len() as u32;
+        inverted_index.avdl = inverted_index.doc_lengths.iter().sum::<u32>() as f32 / documents.len() as f32;
+        inverted_index.rebuild_vocabulary();
+        inverted_index
+    }
+
+    fn matched_docs(text: &str, inverted_index: &InvertedIndex) -> Vec<DocId> {
+        let query = Query::parse(text, inverted_index);
+        let mut doc_ids: Vec<DocId> = score_conjunctive(&query, inverted_index, 10)
+            .into_iter()
+            .map(|(doc_id, _score)| doc_id)
+            .collect();
+        doc_ids.sort_unstable();
+        doc_ids
+    }
+
+    #[test]
+    fn conjunctive_queries_or_the_expansions_of_a_word() {
+        let inverted_index = index(&[
+            &["aero", "flow"],
+            &["aerial", "flow"],
+            &["flow"],
+            &["aero", "aerial"],
+        ]);
+
+        assert_eq!(matched_docs("aer* flow", &inverted_index), vec![1, 2]);
+        assert_eq!(matched_docs("aer*", &inverted_index), vec![1, 2, 4]);
+        assert_eq!(matched_docs("aer* flow", &inverted_index), matched_docs("flow aer*", &inverted_index));
+        assert!(matched_docs("aer* wing", &inverted_index).is_empty());
+        assert!(matched_docs("xyz* flow", &inverted_index).is_empty());
+    }
+
+    #[test]
+    fn conjunctive_scores_count_every_matched_expansion() {
+        let inverted_index = index(&[&["aero", "flow"], &["aero", "aerial", "flow"]]);
+        let query = Query::parse("aer* flow", &inverted_index);
+        let results = score_conjunctive(&query, &inverted_index, 10);
+        let disjunctive = score_bm25(&query, &inverted_index, 10);
+
+        assert_eq!(results.len(), 2);
+        for (doc_id, score) in disjunctive {
+            let (_doc_id, conjunctive) = results.iter().find(|(id, _score)| *id == doc_id).unwrap();
+            assert!((conjunctive - score).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{
+    querying::{
+        query::{Query, QueryTerm},
+        score::{idf, score},
+    },
+    types::{DocId, InvertedIndex},
+};
+
+/// Number of the most discriminative document terms that make up the query.
+pub const SIMILAR_QUERY_TERMS: usize = 25;
+
+/// Builds a query from the `SIMILAR_QUERY_TERMS` terms of `doc_id` with the
+/// highest tf-idf, weighted relative to the best of them.
+pub fn similar_query(doc_id: DocId, inverted_index: &InvertedIndex) -> Option<Query> {
+    let (terms, _doc_length) = inverted_index.document_terms(doc_id)?;
+
+    let mut weighted: Vec<(String, f32)> = terms
+        .into_iter()
+        .filter_map(|(token, tf)| {
+            let df = inverted_index.dictionary.get(token)?.df();
+            Some((token.to_string(), tf as f32 * idf(df, inverted_index.n)))
+        })
+        .filter(|(_token, weight)| *weight > 0.0)
+        .collect();
+    weighted.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    weighted.truncate(SIMILAR_QUERY_TERMS);
+
+    let max_weight = weighted.first().map(|(_token, weight)| *weight)?;
+    Some(Query {
+        terms: weighted
+            .into_iter()
+            .map(|(token, weight)| QueryTerm {
+                token,
+                weight: weight / max_weight,
+            })
+            .collect(),
+        expansions: Vec::new(),
+    })
+}
+
+/// Ranks the `k` documents closest to `doc_id`, leaving the document itself
+/// out. Returns the generated query alongside the ranking.
+pub fn more_like_this(
+    doc_id: DocId,
+    inverted_index: &InvertedIndex,
+    k: usize,
+) -> Option<(Query, Vec<(DocId, f32)>)> {
+    let query = similar_query(doc_id, inverted_index)?;
+    let mut ranked_results = score(&query, inverted_index, k + 1);
+    ranked_results.retain(|(result_id, _score)| *result_id != doc_id);
+    ranked_results.truncate(k);
+
+    Some((query, ranked_results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preprocessing::tokenizer::{Analyzer, AnalyzerStep},
+        types::{DocIdx, Posting},
+    };
+
+    /// An index over documents `1..` with the given term frequencies and
+    /// matching term vectors.
+    fn index(documents: &[&[(&str, u32)]]) -> InvertedIndex {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        for (doc_idx, terms) in documents.iter().enumerate() {
+            let doc_length = terms.iter().map(|(_term, tf)| tf).sum();
+            inverted_index.add_document(doc_idx as DocId + 1, String::new(), doc_length, 0);
+            for (term, tf) in *terms {
+                inverted_index
+                    .add_posting(term.to_string(), Posting::new(doc_idx as DocIdx, *tf))
+                    .unwrap();
+            }
+        }
+        inverted_index.n = documents.len() as u32;
+        inverted_index.avdl = inverted_index.doc_lengths.iter().sum::<u32>() as f32 / documents.len() as f32;
+        inverted_index.rebuild_vocabulary();
+        for (doc_idx, terms) in documents.iter().enumerate() {
+            let positions = terms.iter().map(|(term, tf)| (term.to_string(), (0..*tf).collect())).collect();
+            inverted_index.set_term_vector(doc_idx as DocIdx, positions);
+        }
+        inverted_index
+    }
+
+    fn sample_index() -> InvertedIndex {
+        index(&[
+            &[("shock", 3), ("wave", 2), ("flow", 1)],
+            &[("shock", 1), ("wave", 1)],
+            &[("flow", 2), ("heat", 1)],
+            &[("heat", 2), ("plate", 1)],
+            &[("plate", 1), ("wing", 1)],
+            &[("wing", 2)],
+        ])
+    }
+
+    #[test]
+    fn similar_queries_weight_terms_by_tf_idf_relative_to_the_best() {
+        let inverted_index = sample_index();
+        let query = similar_query(1, &inverted_index).unwrap();
+
+        let weights: Vec<(&str, f32)> = query.terms.iter().map(|term| (term.token.as_str(), term.weight)).collect();
+        assert_eq!(weights[0], ("shock", 1.0));
+        assert_eq!(weights[1].0, "wave");
+        assert!((weights[1].1 - 2.0 / 3.0).abs() < 1e-6);
+        assert_eq!(weights[2].0, "flow");
+        assert!(similar_query(99, &inverted_index).is_none());
+    }
+
+    #[test]
+    fn more_like_this_leaves_the_document_itself_out() {
+        let inverted_index = sample_index();
+        let (_query, ranked_results) = more_like_this(1, &inverted_index, 10).unwrap();
+
+        let doc_ids: Vec<DocId> = ranked_results.iter().map(|(doc_id, _score)| *doc_id).collect();
+        assert_eq!(doc_ids, vec![2, 3]);
+
+        let (_query, ranked_results) = more_like_this(1, &inverted_index, 1).unwrap();
+        assert_eq!(ranked_results.len(), 1);
+    }
+}
@@ -0,0 +1,250 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::{
+    preprocessing::tokenizer::Analyzer,
+    querying::{query::Query, score::idf},
+    types::InvertedIndex,
+};
+
+/// Maximum number of characters of a snippet.
+pub const SNIPPET_LENGTH: usize = 140;
+
+/// A passage of a document together with the byte ranges (relative to
+/// `text`) of the words whose analysed form is a query term.
+#[derive(Debug, Clone)]
+pub struct Snippet {
+    pub text: String,
+    pub highlights: Vec<Range<usize>>,
+}
+
+#[derive(Debug)]
+struct Word<'a> {
+    span: Range<usize>,
+    token: Option<String>,
+    weight: f32,
+    text: &'a str,
+}
+
+/// Picks the sentence of the document body that covers the most query weight
+/// (sum of `idf * query weight` over distinct matched terms) and cuts it down
+/// to the best window of `SNIPPET_LENGTH` characters. Falls back to the start
+/// of the body when no sentence matches.
+pub fn query_biased_snippet(content: &str, query: &Query, inverted_index: &InvertedIndex) -> Option<Snippet> {
+    let body = extract_body(content);
+    let term_weights: HashMap<&str, f32> = query
+        .terms
+        .iter()
+        .map(|term| {
+            let df = inverted_index
+                .dictionary
+                .get(&term.token)
+                .map(|postings| postings.df())
+                .unwrap_or(0);
+            (term.token.as_str(), idf(df, inverted_index.n).max(0.1) * term.weight)
+        })
+        .collect();
+
+    let words = analyse_words(&body, &term_weights, &inverted_index.analyzer);
+    if words.is_empty() {
+        return None;
+    }
+
+    let mut best: Option<(f32, Range<usize>)> = None;
+    for sentence in sentences(&words) {
+        let window = best_window(&words, sentence);
+        let score = window_score(&words[window.clone()]);
+        if score > 0.0 && best.as_ref().is_none_or(|(best_score, _)| score > *best_score) {
+            best = Some((score, window));
+        }
+    }
+    let window = match best {
+        Some((_score, window)) => window,
+        None => best_window(&words, 0..words.len()),
+    };
+
+    let start = words[window.start].span.start;
+    let end = words[window.end - 1].span.end;
+    let highlights = words[window]
+        .iter()
+        .filter(|word| word.weight > 0.0)
+        .map(|word| {
+            let trimmed = word.text.trim_matches(|c: char| !c.is_alphanumeric());
+            let offset = word.text.find(trimmed).unwrap_or(0);
+            let word_start = word.span.start + offset - start;
+            word_start..word_start + trimmed.len()
+        })
+        .collect();
+
+    Some(Snippet {
+        text: body[start..end].to_string(),
+        highlights,
+    })
+}
+
+/// The `.W` section of a document with its lines joined by single spaces.
+fn extract_body(content: &str) -> String {
+    let mut body: Vec<&str> = Vec::new();
+    let mut collecting = false;
+
+    for line in content.lines() {
+        if line.starts_with(".W") {
+            collecting = true;
+        } else if line.starts_with(".I") || line.starts_with(".T") || line.starts_with(".A") || line.starts_with(".B") {
+            collecting = false;
+        } else if collecting {
+            body.push(line.trim());
+        }
+    }
+
+    body.join(" ")
+}
+
+fn analyse_words<'a>(body: &'a str, term_weights: &HashMap<&str, f32>, analyzer: &Analyzer) -> Vec<Word<'a>> {
+    let mut words = Vec::new();
+    let mut start: Option<usize> = None;
+
+    for (offset, c) in body.char_indices().chain(std::iter::once((body.len(), ' '))) {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(offset),
+            (true, Some(word_start)) => {
+                let text = &body[word_start..offset];
+                let token = analyzer.analyze(text);
+                let weight = token
+                    .as_deref()
+                    .and_then(|token| term_weights.get(token))
+                    .copied()
+                    .unwrap_or(0.0);
+                words.push(Word {
+                    span: word_start..offset,
+                    token,
+                    weight,
+                    text,
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    words
+}
+
+/// Word ranges of the sentences, split after words ending in `.`, `?` or `!`.
+fn sentences(words: &[Word]) -> Vec<Range<usize>> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    for (idx, word) in words.iter().enumerate() {
+        if word.text.ends_with(['.', '?', '!']) {
+            sentences.push(start..idx + 1);
+            start = idx + 1;
+        }
+    }
+    if start < words.len() {
+        sentences.push(start..words.len());
+    }
+    sentences
+}
+
+/// Within `range`, the run of words fitting into `SNIPPET_LENGTH` characters
+/// with the highest score; earlier windows win ties.
+fn best_window(words: &[Word], range: Range<usize>) -> Range<usize> {
+    let mut best = range.start..range.start + 1;
+    let mut best_score = f32::MIN;
+
+    for window_start in range.clone() {
+        let mut window_end = window_start + 1;
+        while window_end < range.end
+            && words[window_end].span.end - words[window_start].span.start <= SNIPPET_LENGTH
+        {
+            window_end += 1;
+        }
+        let score = window_score(&words[window_start..window_end]);
+        if score > best_score {
+            best_score = score;
+            best = window_start..window_end;
+        }
+        if window_end == range.end {
+            break;
+        }
+    }
+
+    best
+}
+
+/// Counts every matched term once with its full weight; repetitions add a
+/// small bonus.
+fn window_score(words: &[Word]) -> f32 {
+    let mut seen: HashMap<&str, f32> = HashMap::new();
+    let mut repetitions = 0;
+    for word in words.iter().filter(|word| word.weight > 0.0) {
+        if let Some(token) = word.token.as_deref() {
+            if seen.insert(token, word.weight).is_some() {
+                repetitions += 1;
+            }
+        }
+    }
+    seen.values().sum::<f32>() + 0.1 * repetitions as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preprocessing::tokenizer::AnalyzerStep,
+        types::{DocIdx, Posting},
+    };
+
+    /// An index whose `terms` each appear in one of ten documents, so that
+    /// they all get the same idf.
+    fn index(terms: &[&str]) -> InvertedIndex {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase, AnalyzerStep::Specials]);
+        for doc_idx in 0..10 {
+            inverted_index.add_document(doc_idx + 1, String::new(), 1, 0);
+        }
+        for term in terms {
+            inverted_index.add_posting(term.to_string(), Posting::new(0 as DocIdx, 1)).unwrap();
+        }
+        inverted_index.n = 10;
+        inverted_index.rebuild_vocabulary();
+        inverted_index
+    }
+
+    fn highlighted(snippet: &Snippet) -> Vec<&str> {
+        snippet.highlights.iter().map(|range| &snippet.text[range.clone()]).collect()
+    }
+
+    const CONTENT: &str = ".I 1\n.T\nwings\n.W\nthe flow is laminar .\nshock waves hit the\nwing. nothing else here.\n";
+
+    #[test]
+    fn snippets_pick_the_sentence_with_the_most_query_weight() {
+        let inverted_index = index(&["flow", "shock", "wing"]);
+        let query = Query::parse("shock wing", &inverted_index);
+        let snippet = query_biased_snippet(CONTENT, &query, &inverted_index).unwrap();
+
+        assert_eq!(snippet.text, "shock waves hit the wing.");
+        assert_eq!(highlighted(&snippet), vec!["shock", "wing"]);
+    }
+
+    #[test]
+    fn snippets_fall_back_to_the_start_of_the_body() {
+        let inverted_index = index(&["flow", "shock", "wing"]);
+        let query = Query::parse("turbulence", &inverted_index);
+        let snippet = query_biased_snippet(CONTENT, &query, &inverted_index).unwrap();
+
+        assert!(snippet.text.starts_with("the flow is laminar"));
+        assert!(snippet.highlights.is_empty());
+    }
+
+    #[test]
+    fn long_sentences_are_cut_around_the_matches() {
+        let inverted_index = index(&["shock"]);
+        let filler = "word ".repeat(60);
+        let content = format!(".I 1\n.W\n{filler}shock {filler}.\n");
+        let query = Query::parse("shock", &inverted_index);
+        let snippet = query_biased_snippet(&content, &query, &inverted_index).unwrap();
+
+        assert!(snippet.text.len() <= SNIPPET_LENGTH);
+        assert_eq!(highlighted(&snippet), vec!["shock"]);
+    }
+}
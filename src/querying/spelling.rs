@@ -0,0 +1,161 @@
+use crate::{
+    querying::query::{is_wildcard, parse_fuzzy},
+    types::{InvertedIndex, KGRAM_SIZE},
+};
+
+/// Largest edit distance at which a vocabulary term is offered as correction.
+pub const MAX_CORRECTION_DISTANCE: usize = 2;
+
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub word: String,
+    pub token: String,
+    pub correction: Option<String>,
+    pub distance: usize,
+}
+
+/// Looks up every plain word of `text` and returns a suggestion for each one
+/// whose token is missing from the dictionary. Stopwords and wildcards are
+/// not checked.
+pub fn check_query(text: &str, inverted_index: &InvertedIndex) -> Vec<Suggestion> {
+    text.split_whitespace()
+        .filter(|word| !is_wildcard(word) && parse_fuzzy(word).is_none())
+        .filter_map(|word| {
+            let token = inverted_index.analyzer.analyze(word)?;
+            if inverted_index.dictionary.contains_key(&token) {
+                return None;
+            }
+            let (correction, distance) = match correct(&token, inverted_index) {
+                Some((correction, distance)) => (Some(correction), distance),
+                None => (None, 0),
+            };
+            Some(Suggestion {
+                word: word.to_string(),
+                token,
+                correction,
+                distance,
+            })
+        })
+        .collect()
+}
+
+/// Finds the closest vocabulary term to `token`. Candidates share at least
+/// one k-gram with the token and differ in length by at most
+/// `MAX_CORRECTION_DISTANCE`; the smallest edit distance wins and ties go to
+/// the term with the highest document frequency.
+pub fn correct(token: &str, inverted_index: &InvertedIndex) -> Option<(String, usize)> {
+    let vocabulary = &inverted_index.vocabulary;
+    let token_length = token.chars().count();
+
+    let anchored: Vec<char> = format!("${token}$").chars().collect();
+    let mut candidates: Vec<u32> = anchored
+        .windows(KGRAM_SIZE)
+        .filter_map(|window| vocabulary.kgrams.get(&window.iter().collect::<String>()))
+        .flatten()
+        .copied()
+        .collect();
+    candidates.sort_unstable();
+    candidates.dedup();
+
+    candidates
+        .into_iter()
+        .map(|term_id| vocabulary.term(term_id))
+        .filter(|term| term.chars().count().abs_diff(token_length) <= MAX_CORRECTION_DISTANCE)
+        .filter_map(|term| {
+            let distance = levenshtein(token, term);
+            (distance <= MAX_CORRECTION_DISTANCE).then_some((term, distance))
+        })
+        .max_by(|a, b| {
+            b.1.cmp(&a.1)
+                .then_with(|| document_frequency(a.0, inverted_index).cmp(&document_frequency(b.0, inverted_index)))
+                .then_with(|| b.0.cmp(a.0))
+        })
+        .map(|(term, distance)| (term.to_string(), distance))
+}
+
+fn document_frequency(term: &str, inverted_index: &InvertedIndex) -> usize {
+    inverted_index
+        .dictionary
+        .get(term)
+        .map(|postings| postings.len())
+        .unwrap_or(0)
+}
+
+/// Classic dynamic-programming edit distance over characters, keeping only
+/// one row of the matrix.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_char != *b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preprocessing::tokenizer::{Analyzer, AnalyzerStep},
+        types::{DocId, DocIdx, Posting},
+    };
+
+    /// An index over documents `1..` holding the given terms once each.
+    fn index(documents: &[&[&str]]) -> InvertedIndex {
+        let mut inverted_index = InvertedIndex::new();
+        inverted_index.analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        for (doc_idx, terms) in documents.iter().enumerate() {
+            inverted_index.add_document(doc_idx as DocId + 1, String::new(), terms.len() as u32, 0);
+            for term in *terms {
+                inverted_index
+                    .add_posting(term.to_string(), Posting::new(doc_idx as DocIdx, 1))
+                    .unwrap();
+            }
+        }
+        inverted_index.n = documents.len() as u32;
+        inverted_index.rebuild_vocabulary();
+        inverted_index
+    }
+
+    #[test]
+    fn levenshtein_counts_insertions_deletions_and_substitutions() {
+        assert_eq!(levenshtein("flow", "flow"), 0);
+        assert_eq!(levenshtein("flow", "flaw"), 1);
+        assert_eq!(levenshtein("flow", "flows"), 1);
+        assert_eq!(levenshtein("flow", "low"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "wing"), 4);
+    }
+
+    #[test]
+    fn the_closest_term_wins_and_ties_go_to_the_higher_df() {
+        let inverted_index = index(&[&["flow", "glow"], &["flow", "glow"], &["flow"], &["slow"]]);
+
+        // flow, glow and slow are all one edit away; flow is in most documents.
+        assert_eq!(correct("blow", &inverted_index), Some(("flow".to_string(), 1)));
+        // glows is closer to glow than to the more frequent flow.
+        assert_eq!(correct("glows", &inverted_index), Some(("glow".to_string(), 1)));
+        assert_eq!(correct("flwoo", &inverted_index), Some(("flow".to_string(), 2)));
+        assert_eq!(correct("turbulence", &inverted_index), None);
+    }
+
+    #[test]
+    fn only_unknown_plain_words_get_suggestions() {
+        let inverted_index = index(&[&["flow", "glow"], &["flow"]]);
+        let suggestions = check_query("Flow blow glo* flw~1 turbulence", &inverted_index);
+
+        let found: Vec<(&str, Option<&str>)> = suggestions
+            .iter()
+            .map(|suggestion| (suggestion.word.as_str(), suggestion.correction.as_deref()))
+            .collect();
+        assert_eq!(found, vec![("blow", Some("flow")), ("turbulence", None)]);
+    }
+}
@@ -0,0 +1,454 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    engine::{SearchEngine, SearchOptions, SearchResults},
+    evaluation::query_extractor::extract_queries,
+    types::DocId,
+    utils::json_string,
+};
+
+/// Number of results returned by `/search` when `k` is not given.
+pub const DEFAULT_K: usize = 10;
+/// Upper bound on `k` for `/search`.
+pub const MAX_K: usize = 1000;
+/// Requests with a larger body are rejected.
+const MAX_BODY_BYTES: usize = 1 << 20;
+/// A connection that sends nothing for this long is dropped, so idle
+/// clients cannot hold on to the workers.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Settings of the HTTP server.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub port: u16,
+    pub threads: usize,
+    /// Test queries evaluated by `POST /eval` when the body is empty.
+    pub queries_path: PathBuf,
+    pub qrels_path: PathBuf,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    params: HashMap<String, String>,
+    body: String,
+}
+
+struct Response {
+    status: u16,
+    body: String,
+}
+
+impl Response {
+    fn json(body: String) -> Self {
+        Response { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Response {
+            status,
+            body: format!("{{\"error\":{}}}", json_string(message)),
+        }
+    }
+}
+
+/// Serves the read-only index over HTTP/1.1 on `127.0.0.1:<port>`. Only
+/// returns if binding fails.
+pub fn serve(engine: SearchEngine, config: ServerConfig) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", config.port))?;
+    println!("🌐 Serving on http://{}", listener.local_addr()?);
+    serve_listener(listener, engine, config)
+}
+
+/// Serves on an already bound listener. Each of the `threads` workers
+/// accepts connections from it and answers one request per connection; a
+/// request whose handling panics is answered with a 500 and the worker
+/// carries on.
+pub fn serve_listener(listener: TcpListener, engine: SearchEngine, config: ServerConfig) -> io::Result<()> {
+    let engine = Arc::new(engine);
+    let config = Arc::new(config);
+    let workers: Vec<_> = (0..config.threads.max(1))
+        .map(|_| {
+            let listener = listener.try_clone()?;
+            let engine = Arc::clone(&engine);
+            let config = Arc::clone(&config);
+            Ok(std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => handle_connection(stream, &|request| route(request, &engine, &config)),
+                        Err(e) => eprintln!("ERROR: Failed to accept connection: {}", e),
+                    }
+                }
+            }))
+        })
+        .collect::<io::Result<_>>()?;
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+    Ok(())
+}
+
+fn handle_connection(mut stream: TcpStream, handler: &dyn Fn(&Request) -> Response) {
+    let start_time = Instant::now();
+    if let Err(e) = stream.set_read_timeout(Some(READ_TIMEOUT)) {
+        eprintln!("ERROR: Failed to set the read timeout: {}", e);
+    }
+    let response = match read_request(&mut stream) {
+        Ok(request) => {
+            let response = panic::catch_unwind(AssertUnwindSafe(|| handler(&request)))
+                .unwrap_or_else(|_panic| Response::error(500, "internal error while handling the request"));
+            println!(
+                "   {} {} -> {} ({:?})",
+                request.method,
+                request.path,
+                response.status,
+                start_time.elapsed()
+            );
+            response
+        }
+        Err(e) => Response::error(400, &e.to_string()),
+    };
+
+    if let Err(e) = write_response(&mut stream, &response) {
+        eprintln!("ERROR: Failed to write response: {}", e);
+    }
+}
+
+fn read_request(stream: &mut TcpStream) -> io::Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed request line"));
+    };
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().map_err(|_e| {
+                    io::Error::new(io::ErrorKind::InvalidData, "invalid Content-Length")
+                })?;
+            }
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "request body too large"));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let (path, query_string) = target.split_once('?').unwrap_or((target, ""));
+    let params = query_string
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(name), percent_decode(value))
+        })
+        .collect();
+
+    Ok(Request {
+        method: method.to_string(),
+        path: percent_decode(path),
+        params,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response) -> io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+fn route(request: &Request, engine: &SearchEngine, config: &ServerConfig) -> Response {
+    let segments: Vec<&str> = request.path.split('/').filter(|segment| !segment.is_empty()).collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["search"]) => search(request, engine),
+        ("GET", ["doc", doc_id]) => match doc_id.parse::<DocId>() {
+            Ok(doc_id) => document(doc_id, engine),
+            Err(_) => Response::error(400, "document ids are numbers"),
+        },
+        ("GET", ["stats"]) => stats(engine),
+        ("POST", ["eval"]) => eval(request, engine, config),
+        (_, ["search"] | ["doc", _] | ["stats"] | ["eval"]) => {
+            Response::error(405, "method not allowed")
+        }
+        _ => Response::error(404, "not found"),
+    }
+}
+
+/// `GET /search?q=<query>&k=<n>`: the top `k` documents with title, score
+/// and a query-biased snippet.
+fn search(request: &Request, engine: &SearchEngine) -> Response {
+    let Some(text) = request.params.get("q").filter(|text| !text.trim().is_empty()) else {
+        return Response::error(400, "missing query parameter 'q'");
+    };
+    let k = match request.params.get("k").map(|k| k.parse::<usize>()) {
+        Some(Ok(k)) => k.min(MAX_K),
+        Some(Err(_)) => return Response::error(400, "'k' has to be a number"),
+        None => DEFAULT_K,
+    };
+
+    let options = SearchOptions {
+        k,
+        ..SearchOptions::default()
+    };
+    Response::json(search_results_json(text, &engine.search(text, &options)))
+}
+
+/// Renders search results as a JSON object with the query, the search time
+/// and every hit with its snippet (text plus highlighted byte ranges).
+pub fn search_results_json(text: &str, results: &SearchResults) -> String {
+    let hits: Vec<String> = results
+        .hits
+        .iter()
+        .map(|hit| {
+            let snippet = match &hit.snippet {
+                Some(snippet) => {
+                    let highlights: Vec<String> = snippet
+                        .highlights
+                        .iter()
+                        .map(|range| format!("[{},{}]", range.start, range.end))
+                        .collect();
+                    format!(
+                        "{{\"text\":{},\"highlights\":[{}]}}",
+                        json_string(&snippet.text),
+                        highlights.join(",")
+                    )
+                }
+                None => "null".to_string(),
+            };
+            format!(
+                "{{\"rank\":{},\"doc_id\":{},\"title\":{},\"score\":{},\"snippet\":{}}}",
+                hit.rank,
+                hit.doc_id,
+                json_string(&hit.title),
+                hit.score,
+                snippet
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\"query\":{},\"took_us\":{},\"results\":[{}]}}",
+        json_string(text),
+        results.took.as_micros(),
+        hits.join(",")
+    )
+}
+
+/// `GET /doc/{id}`: title, length and raw content of a document.
+fn document(doc_id: DocId, engine: &SearchEngine) -> Response {
+    let Some(document) = engine.document(doc_id) else {
+        return Response::error(404, "document is not part of the index");
+    };
+
+    Response::json(format!(
+        "{{\"doc_id\":{},\"title\":{},\"length\":{},\"content\":{}}}",
+        document.doc_id,
+        json_string(&document.title),
+        document.length,
+        json_string(&document.content)
+    ))
+}
+
+/// `GET /stats`: collection statistics.
+fn stats(engine: &SearchEngine) -> Response {
+    let stats = engine.stats();
+    Response::json(format!(
+        "{{\"documents\":{},\"terms\":{},\"avdl\":{},\"impacts\":{}}}",
+        stats.documents,
+        stats.terms,
+        stats.avdl,
+        stats.impact_budget.is_some()
+    ))
+}
+
+/// `POST /eval`: average precision per query and MAP against the server's
+/// qrels. The body lists `qid<TAB>query` lines; an empty body evaluates the
+/// configured test queries.
+fn eval(request: &Request, engine: &SearchEngine, config: &ServerConfig) -> Response {
+    let queries: HashMap<u32, String> = if request.body.trim().is_empty() {
+        match extract_queries(&config.queries_path) {
+            Ok(queries) => queries,
+            Err(e) => return Response::error(500, &e.to_string()),
+        }
+    } else {
+        let mut queries = HashMap::new();
+        for line in request.body.lines().filter(|line| !line.trim().is_empty()) {
+            let parsed = line
+                .split_once('\t')
+                .and_then(|(qid, text)| Some((qid.trim().parse::<u32>().ok()?, text.trim())));
+            let Some((qid, text)) = parsed else {
+                return Response::error(400, "every line needs the form '<numeric qid>\\t<query>'");
+            };
+            queries.insert(qid, text.to_string());
+        }
+        queries
+    };
+    let report = match engine.evaluate(&queries, &config.qrels_path, None, None, None) {
+        Ok(report) => report,
+        Err(e) => return Response::error(500, &e.to_string()),
+    };
+    let per_query: Vec<String> = report
+        .queries
+        .iter()
+        .map(|evaluation| {
+            format!(
+                "{{\"qid\":{},\"ap\":{}}}",
+                evaluation.query_id, evaluation.average_precision
+            )
+        })
+        .collect();
+
+    Response::json(format!(
+        "{{\"queries\":{},\"map\":{},\"per_query\":[{}]}}",
+        report.queries.len(),
+        report.map,
+        per_query.join(",")
+    ))
+}
+
+/// Decodes `%XX` escapes and `+` (space) of a URL component.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let escaped = std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match escaped {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, net::SocketAddr};
+
+    use super::*;
+    use crate::config::Config;
+
+    const CORPUS: &str = ".I 1\n.T\nboundary layer flow\n.W\nboundary layer flow over a flat plate .\n\
+                          .I 2\n.T\nheat transfer\n.W\nheat transfer in a hypersonic flow .\n";
+
+    fn start_server(name: &str) -> SocketAddr {
+        let dir = std::env::temp_dir().join(format!("mini-retrieve-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("corpus.all"), CORPUS).unwrap();
+        let config = Config {
+            corpus: dir.join("corpus.all"),
+            index_dir: dir.join("index"),
+            ..Config::default()
+        };
+        let engine = SearchEngine::build(&config, None).unwrap();
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_config = ServerConfig {
+            port: addr.port(),
+            threads: 1,
+            queries_path: dir.join("queries"),
+            qrels_path: dir.join("qrels"),
+        };
+        std::thread::spawn(move || serve_listener(listener, engine, server_config));
+        addr
+    }
+
+    fn read_response(stream: &mut TcpStream) -> (u16, String) {
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response.split_whitespace().nth(1).unwrap().parse().unwrap();
+        let body = response.split_once("\r\n\r\n").map_or("", |(_head, body)| body);
+        (status, body.to_string())
+    }
+
+    fn get(addr: SocketAddr, target: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target).unwrap();
+        read_response(&mut stream)
+    }
+
+    #[test]
+    fn answers_requests_on_a_single_worker() {
+        let addr = start_server("server-requests");
+
+        let (status, body) = get(addr, "/search?q=boundary+layer&k=1");
+        assert_eq!(status, 200);
+        assert!(body.contains("\"doc_id\":1"), "{}", body);
+        assert_eq!(get(addr, "/search?q=%C3%A9%C3%A9").0, 200);
+        assert_eq!(get(addr, "/search").0, 400);
+
+        let (status, body) = get(addr, "/stats");
+        assert_eq!(status, 200);
+        assert!(body.contains("\"documents\":2"), "{}", body);
+        assert_eq!(get(addr, "/doc/2").0, 200);
+        assert_eq!(get(addr, "/doc/7").0, 404);
+        assert_eq!(get(addr, "/unknown").0, 404);
+    }
+
+    #[test]
+    fn idle_connections_time_out() {
+        let addr = start_server("server-idle");
+        let _idle = TcpStream::connect(addr).unwrap();
+
+        let start_time = Instant::now();
+        assert_eq!(get(addr, "/stats").0, 200);
+        assert!(start_time.elapsed() < READ_TIMEOUT * 2);
+    }
+
+    #[test]
+    fn a_panicking_handler_is_answered_with_500() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _addr) = listener.accept().unwrap();
+        write!(client, "GET /stats HTTP/1.1\r\n\r\n").unwrap();
+
+        handle_connection(stream, &|_request| panic!("handler failed"));
+        assert_eq!(read_response(&mut client).0, 500);
+    }
+}
@@ -0,0 +1,24 @@
+use std::collections::HashMap;
+
+use crate::types::DocIdx;
+
+/// All documents of one term that share the same quantized impact.
+#[derive(Debug, Clone)]
+pub struct ImpactSegment {
+    pub impact: u8,
+    pub docs: Vec<DocIdx>,
+}
+
+/// Impact-ordered postings: for every term the segments are sorted by
+/// descending impact, so the most valuable postings are read first.
+///
+/// An impact is the BM25 contribution of a posting quantized to 8 bits;
+/// `scale` converts an accumulated impact back to the BM25 range.
+/// `budget` is the share of a query's postings that gets processed before
+/// query evaluation terminates early.
+#[derive(Debug, Default)]
+pub struct ImpactIndex {
+    pub segments: HashMap<String, Vec<ImpactSegment>>,
+    pub scale: f32,
+    pub budget: f32,
+}
@@ -1,48 +1,170 @@
-use std::collections::HashMap;
+use std::{collections::BTreeMap, path::PathBuf};
 
-use crate::types::DocId;
+use crate::{
+    preprocessing::tokenizer::Analyzer,
+    querying::score::{Bm25Params, ScoringModel},
+    types::{DocId, DocIdx, ImpactIndex, PostingsList, TermVectorEntry, Vocabulary},
+};
 
-#[derive(Debug)]
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct Posting {
-    pub doc_id: DocId,
+    pub doc_idx: DocIdx,
     pub tf: u32,
 }
 
-#[derive(Debug)]
+/// Postings refer to documents through their dense `DocIdx` (`0..n`), so all
+/// per-document tables are plain `Vec`s indexed by it. `doc_ids` maps the
+/// dense index back to the id used in the corpus and is kept sorted.
+///
+/// The dictionary is ordered by term; `vocabulary` adds term ids, suffix and
+/// k-gram lookups on top of it and has to be rebuilt whenever terms are
+/// added or removed. `term_vectors` is the forward index: for every document
+/// its terms (sorted by term id) with tf and positions. `documents_dir` is
+/// where the split documents the index was built from live. `analyzer`,
+/// `model` and `bm25` are the analysis and ranking settings the index is
+/// searched with.
+#[derive(Debug, Default)]
 pub struct InvertedIndex {
-    pub dictionary: HashMap<String, Vec<Posting>>,
+    pub dictionary: BTreeMap<String, PostingsList>,
+    pub vocabulary: Vocabulary,
 
-    pub doc_lengths: HashMap<DocId, u32>,
-    pub doc_titles: HashMap<DocId, String>,
+    pub doc_ids: Vec<DocId>,
+    pub doc_lengths: Vec<u32>,
+    pub doc_titles: Vec<String>,
+    /// Number of tokens of every title. The title opens the token stream of
+    /// its document, so its tokens are the positions below this length.
+    pub title_lengths: Vec<u32>,
+    pub term_vectors: Vec<Vec<TermVectorEntry>>,
     pub n: u32,
     pub avdl: f32,
+    pub documents_dir: PathBuf,
+    /// Analyzer chain the documents were indexed with; queries use it too.
+    pub analyzer: Analyzer,
+    pub model: ScoringModel,
+    pub bm25: Bm25Params,
+
+    /// Quantized BM25 impacts, only present when the index was built in
+    /// impact mode.
+    pub impacts: Option<ImpactIndex>,
 }
 
 impl Posting {
-    pub fn new(doc_id: DocId, tf: u32) -> Self {
-        Posting {
-            doc_id: doc_id,
-            tf: tf,
-        }
+    pub fn new(doc_idx: DocIdx, tf: u32) -> Self {
+        Posting { doc_idx, tf }
     }
 }
 
 impl InvertedIndex {
     pub fn new() -> Self {
-        InvertedIndex {
-            dictionary: HashMap::new(),
-            doc_lengths: HashMap::new(),
-            doc_titles: HashMap::new(),
-            n: 0,
-            avdl: 0.0,
+        Self::default()
+    }
+
+    /// Appends a posting to the list of `token`. Fails with the posting if
+    /// the list already holds this or a later document.
+    pub fn add_posting(&mut self, token: String, posting: Posting) -> Result<(), Posting> {
+        self.dictionary.entry(token).or_default().push(posting)
+    }
+
+    /// Rebuilds the vocabulary from the dictionary and moves the term
+    /// vectors over to the new term ids. Entries of terms that left the
+    /// dictionary are dropped.
+    pub fn rebuild_vocabulary(&mut self) {
+        let previous = std::mem::replace(
+            &mut self.vocabulary,
+            Vocabulary::build(self.dictionary.keys()),
+        );
+        if previous.terms.is_empty() {
+            return;
+        }
+
+        let remapped: Vec<Option<u32>> = previous
+            .terms
+            .iter()
+            .map(|term| self.vocabulary.term_id(term))
+            .collect();
+        for term_vector in &mut self.term_vectors {
+            term_vector.retain_mut(|entry| match remapped[entry.term_id as usize] {
+                Some(term_id) => {
+                    entry.term_id = term_id;
+                    true
+                }
+                None => false,
+            });
+        }
+    }
+
+    /// Stores the term vector of `doc_idx`. The terms have to be part of the
+    /// vocabulary already.
+    pub fn set_term_vector(&mut self, doc_idx: DocIdx, positions: Vec<(String, Vec<u32>)>) {
+        let mut term_vector: Vec<TermVectorEntry> = positions
+            .into_iter()
+            .filter_map(|(token, positions)| {
+                Some(TermVectorEntry {
+                    term_id: self.vocabulary.term_id(&token)?,
+                    tf: positions.len() as u32,
+                    positions,
+                })
+            })
+            .collect();
+        term_vector.sort_by_key(|entry| entry.term_id);
+
+        let idx = doc_idx as usize;
+        if self.term_vectors.len() <= idx {
+            self.term_vectors.resize_with(idx + 1, Vec::new);
         }
+        self.term_vectors[idx] = term_vector;
+    }
+
+    /// The stored term vector of a document, addressed by its corpus id.
+    pub fn term_vector(&self, doc_id: DocId) -> Option<&[TermVectorEntry]> {
+        let doc_idx = self.doc_idx(doc_id)?;
+        self.term_vectors.get(doc_idx as usize).map(Vec::as_slice)
+    }
+
+    /// Term frequencies of a document together with its length, read from
+    /// the forward index.
+    pub fn document_terms(&self, doc_id: DocId) -> Option<(Vec<(&str, u32)>, u32)> {
+        let doc_idx = self.doc_idx(doc_id)?;
+        let term_vector = self.term_vectors.get(doc_idx as usize)?;
+        let terms = term_vector
+            .iter()
+            .map(|entry| (self.vocabulary.term(entry.term_id), entry.tf))
+            .collect();
+        Some((terms, self.doc_lengths[doc_idx as usize]))
+    }
+
+    /// Registers a document and returns its dense index. Documents have to be
+    /// added in ascending `DocId` order. `title_length` counts the tokens of
+    /// the whole title section, `title` is only its first line.
+    pub fn add_document(&mut self, doc_id: DocId, title: String, length: u32, title_length: u32) -> DocIdx {
+        let doc_idx = self.doc_ids.len() as DocIdx;
+        self.doc_ids.push(doc_id);
+        self.title_lengths.push(title_length.min(length));
+        self.doc_titles.push(title);
+        self.doc_lengths.push(length);
+        doc_idx
+    }
+
+    pub fn doc_id(&self, doc_idx: DocIdx) -> DocId {
+        self.doc_ids[doc_idx as usize]
+    }
+
+    pub fn doc_idx(&self, doc_id: DocId) -> Option<DocIdx> {
+        self.doc_ids
+            .binary_search(&doc_id)
+            .ok()
+            .map(|idx| idx as DocIdx)
+    }
+
+    /// Path of the split file holding the raw content of a document.
+    pub fn document_path(&self, doc_id: DocId) -> PathBuf {
+        self.documents_dir.join(format!("doc{}", doc_id))
     }
 
-    pub fn add_posting(&mut self, token: String, posting: Posting) {
-        self.dictionary
-            .entry(token)
-            .or_insert_with(Vec::new)
-            .push(posting);
+    pub fn title(&self, doc_idx: DocIdx) -> &str {
+        self.doc_titles
+            .get(doc_idx as usize)
+            .map(|s| s.as_str())
+            .unwrap_or("Title Missing")
     }
 }
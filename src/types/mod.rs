@@ -1,7 +1,19 @@
 pub type DocId = u32;
+/// Dense, zero-based document number assigned at indexing time.
+pub type DocIdx = u32;
 pub type Term = String;
 
+mod impact_index;
 mod inverted_index;
+mod postings_list;
+mod session;
+mod term_vector;
+mod vocabulary;
 
+pub use impact_index::{ImpactIndex, ImpactSegment};
 pub use inverted_index::Posting;
 pub use inverted_index::InvertedIndex;
+pub use postings_list::{PostingsCursor, PostingsList, SKIP_BLOCK_SIZE};
+pub use session::{Collection, LastQuery, Session, DEFAULT_COLLECTION};
+pub use term_vector::TermVectorEntry;
+pub use vocabulary::{Vocabulary, KGRAM_SIZE};
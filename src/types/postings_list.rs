@@ -0,0 +1,251 @@
+use std::ops::Deref;
+
+use crate::types::{DocIdx, Posting};
+
+/// Number of postings covered by one skip entry.
+pub const SKIP_BLOCK_SIZE: usize = 64;
+
+/// A postings list sorted by `DocIdx`. Every `SKIP_BLOCK_SIZE` postings form a
+/// block, and `skips[i]` holds the last `DocIdx` of block `i`, which lets a
+/// cursor jump over whole blocks without looking at their postings.
+///
+/// `df` is the document frequency the term had before the list was pruned;
+/// unpruned lists leave it unset and count their postings instead.
+#[derive(Debug, Clone, Default)]
+pub struct PostingsList {
+    postings: Vec<Posting>,
+    skips: Vec<DocIdx>,
+    df: Option<u32>,
+}
+
+impl PostingsList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a posting. Postings have to arrive in strictly ascending
+    /// `DocIdx` order, which the skips and cursors rely on; a posting that
+    /// would break it is handed back and the list stays unchanged.
+    pub fn push(&mut self, posting: Posting) -> Result<(), Posting> {
+        if self
+            .postings
+            .last()
+            .is_some_and(|last| last.doc_idx >= posting.doc_idx)
+        {
+            return Err(posting);
+        }
+
+        if self.postings.len().is_multiple_of(SKIP_BLOCK_SIZE) {
+            self.skips.push(posting.doc_idx);
+        } else if let Some(last) = self.skips.last_mut() {
+            *last = posting.doc_idx;
+        }
+        self.postings.push(posting);
+        Ok(())
+    }
+
+    /// Keeps `df` as the document frequency of a pruned list, so that idf
+    /// stays that of the full collection.
+    pub fn with_df(mut self, df: u32) -> Self {
+        self.df = (df as usize != self.postings.len()).then_some(df);
+        self
+    }
+
+    /// Number of documents containing the term, including those whose
+    /// postings were pruned.
+    pub fn df(&self) -> u32 {
+        self.df.unwrap_or(self.postings.len() as u32)
+    }
+
+    pub fn postings(&self) -> &[Posting] {
+        &self.postings
+    }
+
+    pub fn skips(&self) -> &[DocIdx] {
+        &self.skips
+    }
+
+    pub fn cursor(&self) -> PostingsCursor<'_> {
+        PostingsCursor {
+            list: self,
+            position: 0,
+        }
+    }
+}
+
+impl Deref for PostingsList {
+    type Target = [Posting];
+
+    fn deref(&self) -> &[Posting] {
+        &self.postings
+    }
+}
+
+impl<'a> IntoIterator for &'a PostingsList {
+    type Item = &'a Posting;
+    type IntoIter = std::slice::Iter<'a, Posting>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.postings.iter()
+    }
+}
+
+/// Sorts the postings by `DocIdx`; of several postings of the same document
+/// only the first is kept.
+impl FromIterator<Posting> for PostingsList {
+    fn from_iter<I: IntoIterator<Item = Posting>>(iter: I) -> Self {
+        let mut postings: Vec<Posting> = iter.into_iter().collect();
+        postings.sort_by_key(|posting| posting.doc_idx);
+
+        let mut list = PostingsList::new();
+        for posting in postings {
+            let _duplicate = list.push(posting);
+        }
+        list
+    }
+}
+
+/// Forward-only position in a `PostingsList`.
+#[derive(Debug, Clone)]
+pub struct PostingsCursor<'a> {
+    list: &'a PostingsList,
+    position: usize,
+}
+
+impl<'a> PostingsCursor<'a> {
+    pub fn current(&self) -> Option<&'a Posting> {
+        self.list.postings.get(self.position)
+    }
+
+    /// Moves to the first posting with `doc_idx >= target` and returns it.
+    /// First gallops over the skip entries to find the block that can hold
+    /// `target`, then gallops inside that block.
+    pub fn advance_to(&mut self, target: DocIdx) -> Option<&'a Posting> {
+        let postings = &self.list.postings;
+        match postings.get(self.position) {
+            Some(posting) if posting.doc_idx >= target => return Some(posting),
+            None => return None,
+            _ => {}
+        }
+
+        let skips = &self.list.skips;
+        let current_block = self.position / SKIP_BLOCK_SIZE;
+        let block = current_block + gallop(&skips[current_block..], target, |&doc_idx| doc_idx);
+        if block >= skips.len() {
+            self.position = postings.len();
+            return None;
+        }
+
+        let block_start = (block * SKIP_BLOCK_SIZE).max(self.position);
+        let block_end = ((block + 1) * SKIP_BLOCK_SIZE).min(postings.len());
+        let offset = gallop(&postings[block_start..block_end], target, |posting| posting.doc_idx);
+        self.position = block_start + offset;
+
+        postings.get(self.position)
+    }
+}
+
+/// Index of the first element whose key is `>= target` in a sorted slice,
+/// found with an exponential probe followed by a binary search over the
+/// bracketed range.
+fn gallop<T>(sorted: &[T], target: DocIdx, key: impl Fn(&T) -> DocIdx) -> usize {
+    let mut bound = 1;
+    while bound < sorted.len() && key(&sorted[bound - 1]) < target {
+        bound *= 2;
+    }
+    let low = bound / 2;
+    let high = bound.min(sorted.len());
+    low + sorted[low..high].partition_point(|element| key(element) < target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(doc_idxs: impl IntoIterator<Item = DocIdx>) -> PostingsList {
+        doc_idxs.into_iter().map(|doc_idx| Posting::new(doc_idx, 1)).collect()
+    }
+
+    fn naive_advance(doc_idxs: &[DocIdx], target: DocIdx) -> Option<DocIdx> {
+        doc_idxs.iter().copied().find(|doc_idx| *doc_idx >= target)
+    }
+
+    #[test]
+    fn skips_hold_the_last_doc_of_every_block() {
+        let doc_idxs: Vec<DocIdx> = (0..200).map(|i| i * 3).collect();
+        let postings = list(doc_idxs.clone());
+
+        assert_eq!(postings.skips().len(), 200_usize.div_ceil(SKIP_BLOCK_SIZE));
+        for (block, skip) in postings.skips().iter().enumerate() {
+            let last = ((block + 1) * SKIP_BLOCK_SIZE).min(doc_idxs.len()) - 1;
+            assert_eq!(*skip, doc_idxs[last]);
+        }
+    }
+
+    #[test]
+    fn advance_to_matches_a_linear_scan() {
+        let doc_idxs: Vec<DocIdx> = (0..500).map(|i| i * 3 + i % 3).collect();
+        let postings = list(doc_idxs.clone());
+        let last = *doc_idxs.last().unwrap();
+
+        for target in 0..last + 5 {
+            let found = postings.cursor().advance_to(target).map(|posting| posting.doc_idx);
+            assert_eq!(found, naive_advance(&doc_idxs, target), "target {}", target);
+        }
+    }
+
+    #[test]
+    fn advance_to_moves_forward_across_blocks() {
+        let doc_idxs: Vec<DocIdx> = (0..1000).map(|i| i * 2).collect();
+        let postings = list(doc_idxs.clone());
+
+        for step in [1, 5, 63, 64, 65, 130, 700] {
+            let mut cursor = postings.cursor();
+            let mut target = 0;
+            while target <= 2000 {
+                let found = cursor.advance_to(target).map(|posting| posting.doc_idx);
+                assert_eq!(found, naive_advance(&doc_idxs, target), "step {} target {}", step, target);
+                assert_eq!(cursor.current().map(|posting| posting.doc_idx), found);
+                target += step;
+            }
+        }
+    }
+
+    #[test]
+    fn advance_to_never_moves_backwards() {
+        let postings = list((0..300).map(|i| i * 10));
+        let mut cursor = postings.cursor();
+
+        assert_eq!(cursor.advance_to(1500).map(|posting| posting.doc_idx), Some(1500));
+        assert_eq!(cursor.advance_to(20).map(|posting| posting.doc_idx), Some(1500));
+        assert!(cursor.advance_to(2991).is_none());
+        assert!(cursor.advance_to(0).is_none());
+    }
+
+    #[test]
+    fn push_rejects_unordered_postings() {
+        let mut postings = PostingsList::new();
+        assert!(postings.push(Posting::new(4, 1)).is_ok());
+        assert!(postings.push(Posting::new(4, 2)).is_err());
+        assert!(postings.push(Posting::new(2, 1)).is_err());
+        assert!(postings.push(Posting::new(9, 1)).is_ok());
+        assert_eq!(postings.len(), 2);
+        assert_eq!(postings.skips(), &[9]);
+    }
+
+    #[test]
+    fn collecting_sorts_and_drops_duplicates() {
+        let postings = list([7, 3, 3, 9, 1]);
+        let doc_idxs: Vec<DocIdx> = postings.iter().map(|posting| posting.doc_idx).collect();
+        assert_eq!(doc_idxs, vec![1, 3, 7, 9]);
+    }
+
+    #[test]
+    fn pruned_lists_keep_their_df() {
+        assert_eq!(list([1, 2, 3]).df(), 3);
+        let pruned = list([2]).with_df(3);
+        assert_eq!(pruned.df(), 3);
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(list([1, 2]).with_df(2).df, None);
+    }
+}
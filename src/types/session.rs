@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    config::Config,
+    engine::SearchEngine,
+    federated::Normalization,
+    querying::{feedback::FeedbackParams, fusion::FusionParams, ltr::LinearModel, query::Query},
+    types::DocId,
+};
+
+/// The most recent interactive query and the relevance judgments the user
+/// gave on its results.
+#[derive(Debug, Clone)]
+pub struct LastQuery {
+    pub text: String,
+    pub query: Query,
+    pub relevant: Vec<DocId>,
+    pub nonrelevant: Vec<DocId>,
+}
+
+impl LastQuery {
+    pub fn new(text: String, query: Query) -> Self {
+        LastQuery {
+            text,
+            query,
+            relevant: Vec::new(),
+            nonrelevant: Vec::new(),
+        }
+    }
+
+    /// Records a judgment, replacing an earlier opposite judgment of the
+    /// same document.
+    pub fn judge(&mut self, doc_id: DocId, relevant: bool) {
+        self.relevant.retain(|judged| *judged != doc_id);
+        self.nonrelevant.retain(|judged| *judged != doc_id);
+        if relevant {
+            self.relevant.push(doc_id);
+        } else {
+            self.nonrelevant.push(doc_id);
+        }
+    }
+}
+
+/// Name of the collection built from the configured corpus at startup.
+pub const DEFAULT_COLLECTION: &str = "default";
+
+/// A named index of the session with the settings it was opened with.
+#[derive(Debug, Default)]
+pub struct Collection {
+    pub config: Config,
+    pub engine: SearchEngine,
+    /// Whether the index was built from `config.corpus` (and can be rebuilt
+    /// with `::reindex`) rather than loaded from a stored index.
+    pub from_corpus: bool,
+}
+
+/// State of one interactive session.
+#[derive(Debug)]
+pub struct Session {
+    /// Open collections by name; `active` is always one of them.
+    pub collections: BTreeMap<String, Collection>,
+    pub active: String,
+    /// Run queries against every open collection instead of the active one.
+    pub cross_index: bool,
+    /// Merge the results of a cross-index query into one ranking with this
+    /// score normalisation instead of listing them per index.
+    pub federated: Option<Normalization>,
+    /// Replace unknown query terms with their best spelling correction
+    /// instead of only suggesting it.
+    pub autocorrect: bool,
+    /// Pseudo-relevance feedback applied to every interactive query.
+    pub feedback: Option<FeedbackParams>,
+    /// Rank fusion applied to every interactive query.
+    pub fusion: Option<FusionParams>,
+    /// Learned model reranking every interactive query.
+    pub reranker: Option<LinearModel>,
+    pub last_query: Option<LastQuery>,
+}
+
+impl Session {
+    /// A session with only the default collection, which still has to be
+    /// indexed.
+    pub fn new(config: Config) -> Self {
+        let default = Collection {
+            config,
+            engine: SearchEngine::default(),
+            from_corpus: true,
+        };
+        Session {
+            collections: BTreeMap::from([(DEFAULT_COLLECTION.to_string(), default)]),
+            active: DEFAULT_COLLECTION.to_string(),
+            cross_index: false,
+            federated: None,
+            autocorrect: false,
+            feedback: None,
+            fusion: None,
+            reranker: None,
+            last_query: None,
+        }
+    }
+
+    pub fn collection(&self) -> &Collection {
+        &self.collections[&self.active]
+    }
+
+    pub fn collection_mut(&mut self) -> &mut Collection {
+        self.collections
+            .get_mut(&self.active)
+            .expect("the active collection is always open")
+    }
+}
@@ -0,0 +1,8 @@
+/// One term of a document's term vector: the term id from the vocabulary,
+/// its frequency in the document and its token positions.
+#[derive(Debug, Clone)]
+pub struct TermVectorEntry {
+    pub term_id: u32,
+    pub tf: u32,
+    pub positions: Vec<u32>,
+}
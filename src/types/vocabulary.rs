@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+/// Length of the grams in the k-gram index.
+pub const KGRAM_SIZE: usize = 3;
+const BOUNDARY: char = '$';
+
+/// Lookup structures over the index terms. `terms` is sorted, so a term's
+/// position in it doubles as its term id. `reversed` holds every term spelled
+/// backwards (sorted) for suffix lookups, and `kgrams` maps each k-gram of
+/// `$term$` to the ids of the terms containing it.
+#[derive(Debug, Default)]
+pub struct Vocabulary {
+    pub terms: Vec<String>,
+    pub reversed: Vec<(String, u32)>,
+    pub kgrams: HashMap<String, Vec<u32>>,
+}
+
+impl Vocabulary {
+    /// Builds the vocabulary from terms given in ascending order.
+    pub fn build<'a>(sorted_terms: impl Iterator<Item = &'a String>) -> Self {
+        let terms: Vec<String> = sorted_terms.cloned().collect();
+
+        let mut reversed: Vec<(String, u32)> = terms
+            .iter()
+            .zip(0..)
+            .map(|(term, term_id)| (term.chars().rev().collect(), term_id))
+            .collect();
+        reversed.sort();
+
+        let mut kgrams: HashMap<String, Vec<u32>> = HashMap::new();
+        for (term, term_id) in terms.iter().zip(0..) {
+            for gram in kgrams_of(&format!("{BOUNDARY}{term}{BOUNDARY}")) {
+                let ids = kgrams.entry(gram).or_default();
+                if ids.last() != Some(&term_id) {
+                    ids.push(term_id);
+                }
+            }
+        }
+
+        Vocabulary {
+            terms,
+            reversed,
+            kgrams,
+        }
+    }
+
+    pub fn term_id(&self, term: &str) -> Option<u32> {
+        self.terms
+            .binary_search_by(|probe| probe.as_str().cmp(term))
+            .ok()
+            .map(|idx| idx as u32)
+    }
+
+    pub fn term(&self, term_id: u32) -> &str {
+        &self.terms[term_id as usize]
+    }
+
+    pub fn with_prefix(&self, prefix: &str) -> Vec<u32> {
+        let start = self.terms.partition_point(|term| term.as_str() < prefix);
+        self.terms[start..]
+            .iter()
+            .take_while(|term| term.starts_with(prefix))
+            .zip(start as u32..)
+            .map(|(_term, term_id)| term_id)
+            .collect()
+    }
+
+    pub fn with_suffix(&self, suffix: &str) -> Vec<u32> {
+        let reversed_suffix: String = suffix.chars().rev().collect();
+        let start = self
+            .reversed
+            .partition_point(|(term, _term_id)| term.as_str() < reversed_suffix.as_str());
+        let mut term_ids: Vec<u32> = self.reversed[start..]
+            .iter()
+            .take_while(|(term, _term_id)| term.starts_with(&reversed_suffix))
+            .map(|(_term, term_id)| *term_id)
+            .collect();
+        term_ids.sort_unstable();
+        term_ids
+    }
+
+    /// Terms matching a pattern with `*` (any run of characters) and `?`
+    /// (exactly one character). Candidates come from the k-gram index when the
+    /// pattern has a literal part of at least `KGRAM_SIZE` characters, and are
+    /// always verified against the pattern.
+    pub fn matching_wildcard(&self, pattern: &str) -> Vec<u32> {
+        let anchored = format!("{BOUNDARY}{pattern}{BOUNDARY}");
+        let grams: Vec<String> = anchored
+            .split(['*', '?'])
+            .flat_map(kgrams_of)
+            .collect();
+
+        let candidates: Vec<u32> = match grams.split_first() {
+            Some((first, rest)) => {
+                let mut candidates = self.kgrams.get(first).cloned().unwrap_or_default();
+                for gram in rest {
+                    let ids = self.kgrams.get(gram).map(Vec::as_slice).unwrap_or(&[]);
+                    candidates.retain(|term_id| ids.binary_search(term_id).is_ok());
+                }
+                candidates
+            }
+            None => (0..self.terms.len() as u32).collect(),
+        };
+
+        candidates
+            .into_iter()
+            .filter(|&term_id| wildcard_match(pattern, self.term(term_id)))
+            .collect()
+    }
+}
+
+fn kgrams_of(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    chars
+        .windows(KGRAM_SIZE)
+        .map(|window| window.iter().collect())
+        .collect()
+}
+
+fn wildcard_match(pattern: &str, term: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let term: Vec<char> = term.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < term.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == term[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vocabulary(terms: &[&str]) -> Vocabulary {
+        let mut terms: Vec<String> = terms.iter().map(|term| term.to_string()).collect();
+        terms.sort();
+        Vocabulary::build(terms.iter())
+    }
+
+    fn terms(vocabulary: &Vocabulary, term_ids: Vec<u32>) -> Vec<&str> {
+        term_ids.into_iter().map(|term_id| vocabulary.term(term_id)).collect()
+    }
+
+    #[test]
+    fn prefixes_select_a_range_of_the_sorted_terms() {
+        let vocabulary = vocabulary(&["aero", "aerofoil", "aerodynamic", "aer", "airfoil", "wing"]);
+
+        assert_eq!(
+            terms(&vocabulary, vocabulary.with_prefix("aero")),
+            vec!["aero", "aerodynamic", "aerofoil"]
+        );
+        assert_eq!(terms(&vocabulary, vocabulary.with_prefix("wing")), vec!["wing"]);
+        assert!(vocabulary.with_prefix("zeta").is_empty());
+    }
+
+    #[test]
+    fn suffixes_are_found_through_the_reversed_terms() {
+        let vocabulary = vocabulary(&["aerodynamic", "dynamic", "hydrodynamic", "dynamics", "static"]);
+
+        assert_eq!(
+            terms(&vocabulary, vocabulary.with_suffix("dynamic")),
+            vec!["aerodynamic", "dynamic", "hydrodynamic"]
+        );
+        assert_eq!(terms(&vocabulary, vocabulary.with_suffix("ic")).len(), 4);
+        assert!(vocabulary.with_suffix("xyz").is_empty());
+    }
+
+    #[test]
+    fn wildcards_match_single_characters_and_backtrack_over_runs() {
+        let vocabulary = vocabulary(&[
+            "bound", "boundary", "bounds", "baund", "bund", "abound", "brand", "bondboundary",
+        ]);
+
+        assert_eq!(
+            terms(&vocabulary, vocabulary.matching_wildcard("b?und*")),
+            vec!["baund", "bound", "boundary", "bounds"]
+        );
+        // The first "nd" after the star is a dead end for "bondboundary".
+        assert_eq!(
+            terms(&vocabulary, vocabulary.matching_wildcard("b*ndary")),
+            vec!["bondboundary", "boundary"]
+        );
+        assert_eq!(
+            terms(&vocabulary, vocabulary.matching_wildcard("*ound")),
+            vec!["abound", "bound"]
+        );
+        assert!(vocabulary.matching_wildcard("b?und?x").is_empty());
+    }
+}
@@ -1,16 +1,33 @@
-use std::{collections::HashMap};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
 
-use crate::{preprocessing::tokenizer::tokenize, types::{DocId, InvertedIndex, Term}};
+use crate::{
+    error::Error,
+    engine::{Hit, SearchResults},
+    federated::{FederatedHit, FederatedResults},
+    evaluation::precision_calculator::EvalReport,
+    preprocessing::tokenizer::Analyzer,
+    querying::{query::{parse_fuzzy, Query, MAX_FUZZY_DISTANCE}, snippet::Snippet, spelling::Suggestion},
+    types::{DocId, InvertedIndex, Term},
+};
 
 const MAX_TITLE_WIDTH: usize = 70;
+/// Title column of the federated table, which gives up room for the source.
+const FEDERATED_TITLE_WIDTH: usize = 57;
+const MAX_SOURCE_WIDTH: usize = 10;
+const MAX_RESULTS_SHOWN: usize = 50;
+const HIGHLIGHT_START: &str = "\x1b[1;33m";
+const HIGHLIGHT_END: &str = "\x1b[0m";
 
-pub fn calculate_document_tf(content: &str) -> (HashMap<Term, u32>, u32) {
+pub fn calculate_document_tf(content: &str, analyzer: &Analyzer) -> (HashMap<Term, u32>, u32) {
     let mut doc_length: u32 = 0;
     let mut tf_map: HashMap<Term, u32> = HashMap::new();
 
     let searchable_content = extract_searchable_content(content);
     searchable_content.split_whitespace().for_each(|term| {
-        if let Some(token) = tokenize(term) {
+        if let Some(token) = analyzer.analyze(term) {
             doc_length += 1;
             let counter = tf_map.entry(token).or_insert(0);
             *counter += 1;
@@ -20,34 +37,62 @@ pub fn calculate_document_tf(content: &str) -> (HashMap<Term, u32>, u32) {
     (tf_map, doc_length)
 }
 
+/// Like `calculate_document_tf`, but keeps the position of every token in
+/// the analysed token stream instead of only counting it. Also returns the
+/// number of tokens of the whole `.T` section, which opens the stream.
+pub fn calculate_document_positions(content: &str, analyzer: &Analyzer) -> (HashMap<Term, Vec<u32>>, u32, u32) {
+    let mut doc_length: u32 = 0;
+    let mut positions: HashMap<Term, Vec<u32>> = HashMap::new();
+
+    let (title, body) = split_searchable_content(content);
+    let mut title_length: u32 = 0;
+    for (section, text) in [title, body].iter().enumerate() {
+        text.split_whitespace().for_each(|term| {
+            if let Some(token) = analyzer.analyze(term) {
+                positions.entry(token).or_default().push(doc_length);
+                doc_length += 1;
+            }
+        });
+        if section == 0 {
+            title_length = doc_length;
+        }
+    }
+
+    (positions, doc_length, title_length)
+}
+
 fn extract_searchable_content(content: &str) -> String {
-    let mut result = String::new();
-    let mut collecting = true;
+    let (title, body) = split_searchable_content(content);
+    title + &body
+}
+
+/// The text of the `.T` section(s) and of the `.W` section(s), in that order.
+fn split_searchable_content(content: &str) -> (String, String) {
+    let mut title = String::new();
+    let mut body = String::new();
+    let mut section = None;
 
     for line in content.lines() {
-        if line.starts_with(".T") || line.starts_with(".W") {
-            collecting = true;
+        if line.starts_with(".T") {
+            section = Some(&mut title);
+        } else if line.starts_with(".W") {
+            section = Some(&mut body);
         } else if line.starts_with(".I") || line.starts_with(".A") || line.starts_with(".B") {
-            collecting = false;
-        } else if collecting {
-            result.push_str(line.trim());
-            result.push(' ');
+            section = None;
+        } else if let Some(text) = section.as_mut() {
+            text.push_str(line.trim());
+            text.push(' ');
         }
     }
 
-    result
+    (title, body)
 }
 
-pub fn display_top_results(scores: HashMap<DocId, f32>, index: &InvertedIndex) {
-    let mut ranked_results: Vec<(DocId, f32)> = scores.into_iter().collect();
-
-    ranked_results.sort_by(|a, b| {
-        b.1.partial_cmp(&a.1)
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
-
+/// Prints the hits as a table, each followed by its snippet (if any) with
+/// the matched terms highlighted.
+pub fn display_top_results(hits: &[Hit]) {
     println!("\n+------------------------------------------------------------------------------------------------+");
-    println!("|                                       🎉 Top 10 Results                                        |");
+    println!("|{:^95}|", format!("🎉 Top {} Results", hits.len().min(MAX_RESULTS_SHOWN)));
     println!("+----------+------------------------------------------------------------------------+------------+");
     println!(
         "| {:^8} | {:<MAX_TITLE_WIDTH$} | {:^10} |",
@@ -55,29 +100,290 @@ pub fn display_top_results(scores: HashMap<DocId, f32>, index: &InvertedIndex) {
     );
     println!("+----------+------------------------------------------------------------------------+------------+");
 
-    for (_rank, (doc_id, score)) in ranked_results.iter().take(10).enumerate() {
-        let title = index
-            .doc_titles
-            .get(doc_id)
-            .map(|s| s.as_str())
-            .unwrap_or("Title Missing");
-
-        let truncated_title = if title.len() > MAX_TITLE_WIDTH {
-            format!("{}...", &title[0..MAX_TITLE_WIDTH - 3])
+    let with_snippets = hits.iter().any(|hit| hit.snippet.is_some());
+    for (rank, hit) in hits.iter().take(MAX_RESULTS_SHOWN).enumerate() {
+        let truncated_title = if hit.title.len() > MAX_TITLE_WIDTH {
+            format!("{}...", &hit.title[0..MAX_TITLE_WIDTH - 3])
         } else {
-            title.to_string()
+            hit.title.clone()
         };
 
+        if rank > 0 && with_snippets {
+            println!("|          |                                                                        |            |");
+        }
         println!(
             "| {:^8} | {:<MAX_TITLE_WIDTH$} | {:>10.4} |",
-            doc_id,
+            hit.doc_id,
             truncated_title,
-            score
+            hit.score
         );
+
+        if let Some(snippet) = &hit.snippet {
+            for line in highlighted_lines(snippet, MAX_TITLE_WIDTH) {
+                println!("|          | {} |            |", line);
+            }
+        }
     }
     println!("+----------+------------------------------------------------------------------------+------------+");
 }
 
+/// Prints spelling suggestions, expansions, the reformulated query (if
+/// feedback changed it), the hits and the search time.
+pub fn display_search_results(results: &SearchResults, autocorrect: bool) {
+    display_suggestions(&results.suggestions, autocorrect);
+    display_expansions(&results.query);
+    if let Some(reformulated) = &results.reformulated {
+        display_weighted_query("🔁 Reformulated query", reformulated);
+    }
+    display_top_results(&results.hits);
+    println!("({:?})", results.took);
+}
+
+/// Prints the merged hits of a federated search with their source index,
+/// preceded by the CORI collection weights if they were used.
+pub fn display_federated_results(results: &FederatedResults) {
+    if !results.collection_weights.is_empty() {
+        let weights: Vec<String> = results
+            .collection_weights
+            .iter()
+            .map(|(name, weight)| format!("{} {:.3}", name, weight))
+            .collect();
+        println!("🏛️ Collection weights: {}", weights.join(", "));
+    }
+    display_federated_hits(&results.hits);
+    println!("({:?})", results.took);
+}
+
+fn display_federated_hits(hits: &[FederatedHit]) {
+    println!("\n+------------------------------------------------------------------------------------------------+");
+    println!("|{:^95}|", format!("🎉 Top {} Federated Results", hits.len().min(MAX_RESULTS_SHOWN)));
+    println!("+------------+----------+-----------------------------------------------------------+------------+");
+    println!(
+        "| {:<MAX_SOURCE_WIDTH$} | {:^8} | {:<FEDERATED_TITLE_WIDTH$} | {:^10} |",
+        "Index", "Doc ID", "Document Title", "Score"
+    );
+    println!("+------------+----------+-----------------------------------------------------------+------------+");
+
+    let with_snippets = hits.iter().any(|federated| federated.hit.snippet.is_some());
+    for (rank, FederatedHit { source, hit, .. }) in hits.iter().take(MAX_RESULTS_SHOWN).enumerate() {
+        let truncated_title = if hit.title.len() > FEDERATED_TITLE_WIDTH {
+            format!("{}...", &hit.title[0..FEDERATED_TITLE_WIDTH - 3])
+        } else {
+            hit.title.clone()
+        };
+        let truncated_source: String = source.chars().take(MAX_SOURCE_WIDTH).collect();
+
+        if rank > 0 && with_snippets {
+            println!("|            |          |                                                           |            |");
+        }
+        println!(
+            "| {:<MAX_SOURCE_WIDTH$} | {:^8} | {:<FEDERATED_TITLE_WIDTH$} | {:>10.4} |",
+            truncated_source,
+            hit.doc_id,
+            truncated_title,
+            hit.score
+        );
+
+        if let Some(snippet) = &hit.snippet {
+            for line in highlighted_lines(snippet, FEDERATED_TITLE_WIDTH) {
+                println!("|            |          | {} |            |", line);
+            }
+        }
+    }
+    println!("+------------+----------+-----------------------------------------------------------+------------+");
+}
+
+/// Reports an error in the interactive mode, which keeps running afterwards.
+pub fn display_error(error: &Error) {
+    println!("❌ {}", error);
+}
+
+/// Prints the rank of every relevant hit per query, followed by the MAP.
+pub fn display_eval_report(report: &EvalReport) {
+    for evaluation in report.queries.iter().filter(|evaluation| evaluation.relevant_count > 0) {
+        println!("\nEvaluating Query ID {}:", evaluation.query_id);
+        for hit in &evaluation.hits {
+            println!("      Hit: Rank #{} ID {} with score {}", hit.rank, hit.doc_id, hit.score);
+        }
+    }
+    println!("\n🦀 The MAP was calculated to be: {}", report.map);
+}
+
+/// Wraps a snippet into lines of `width` visible characters, wrapping the
+/// highlighted ranges in ANSI colour codes and padding every line.
+fn highlighted_lines(snippet: &Snippet, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+
+    let mut word_start = 0;
+    for word in snippet.text.split(' ') {
+        let word_end = word_start + word.len();
+        let word_width = word.chars().count();
+
+        if line_width > 0 && line_width + 1 + word_width > width {
+            line.push_str(&" ".repeat(width.saturating_sub(line_width)));
+            lines.push(std::mem::take(&mut line));
+            line_width = 0;
+        }
+        if line_width > 0 {
+            line.push(' ');
+            line_width += 1;
+        }
+
+        let mut rendered = String::new();
+        let mut position = word_start;
+        for highlight in snippet
+            .highlights
+            .iter()
+            .filter(|highlight| highlight.start >= word_start && highlight.end <= word_end)
+        {
+            rendered.push_str(&snippet.text[position..highlight.start]);
+            rendered.push_str(HIGHLIGHT_START);
+            rendered.push_str(&snippet.text[highlight.clone()]);
+            rendered.push_str(HIGHLIGHT_END);
+            position = highlight.end;
+        }
+        rendered.push_str(&snippet.text[position..word_end]);
+
+        line.push_str(&rendered);
+        line_width += word_width;
+        word_start = word_end + 1;
+    }
+
+    if line_width > 0 {
+        line.push_str(&" ".repeat(width.saturating_sub(line_width)));
+        lines.push(line);
+    }
+    lines
+}
+
+/// Highlights every word of `line` whose token is one of `tokens`. Returns
+/// the rendered line and its visible width in characters.
+pub fn highlight_line(line: &str, tokens: &HashSet<&str>, analyzer: &Analyzer) -> (String, usize) {
+    let visible_width = line.chars().count();
+    if tokens.is_empty() {
+        return (line.to_string(), visible_width);
+    }
+
+    let rendered: Vec<String> = line
+        .split(' ')
+        .map(|word| match analyzer.analyze(word) {
+            Some(token) if tokens.contains(token.as_str()) => {
+                let trimmed = word.trim_matches(|c: char| !c.is_alphanumeric());
+                let offset = word.find(trimmed).unwrap_or(0);
+                format!(
+                    "{}{}{}{}{}",
+                    &word[..offset],
+                    HIGHLIGHT_START,
+                    trimmed,
+                    HIGHLIGHT_END,
+                    &word[offset + trimmed.len()..]
+                )
+            }
+            _ => word.to_string(),
+        })
+        .collect();
+
+    (rendered.join(" "), visible_width)
+}
+
+/// Term frequencies per field of a tagged document, keyed by the field tag
+/// (`T`itle, `A`uthor, `B`ibliography, `W`ords).
+pub fn field_term_frequencies(content: &str, analyzer: &Analyzer) -> HashMap<char, HashMap<Term, u32>> {
+    let mut fields: HashMap<char, HashMap<Term, u32>> = HashMap::new();
+    let mut field: Option<char> = None;
+
+    for line in content.lines() {
+        if let Some(tag) = line.strip_prefix('.').and_then(|rest| rest.chars().next()) {
+            if matches!(tag, 'I' | 'T' | 'A' | 'B' | 'W') {
+                field = Some(tag).filter(|tag| *tag != 'I');
+                continue;
+            }
+        }
+        let Some(field) = field else {
+            continue;
+        };
+        let tfs = fields.entry(field).or_default();
+        for token in line.split_whitespace().filter_map(|word| analyzer.analyze(word)) {
+            *tfs.entry(token).or_insert(0) += 1;
+        }
+    }
+
+    fields
+}
+
+/// Reads the raw content of a split document.
+pub fn read_document(inverted_index: &InvertedIndex, doc_id: DocId) -> Option<String> {
+    fs::read_to_string(inverted_index.document_path(doc_id)).ok()
+}
+
+pub fn display_suggestions(suggestions: &[Suggestion], applied: bool) {
+    for suggestion in suggestions {
+        match &suggestion.correction {
+            Some(correction) if applied => println!(
+                "✏️ \"{}\" is not in the index, searched for \"{}\" instead",
+                suggestion.word, correction
+            ),
+            Some(correction) => println!(
+                "🤔 \"{}\" is not in the index. Did you mean \"{}\"?",
+                suggestion.word, correction
+            ),
+            None => println!(
+                "❓ \"{}\" is not in the index and was ignored",
+                suggestion.word
+            ),
+        }
+    }
+}
+
+pub fn display_expansions(query: &Query) {
+    for expansion in &query.expansions {
+        let clamped = parse_fuzzy(&expansion.pattern).filter(|(_base, distance)| *distance > MAX_FUZZY_DISTANCE);
+        if let Some((base, distance)) = clamped {
+            println!(
+                "⚠️ {}~{}: edit distances above {} are not supported, searching {}~{}",
+                base, distance, MAX_FUZZY_DISTANCE, base, MAX_FUZZY_DISTANCE
+            );
+        }
+        if expansion.terms.is_empty() {
+            println!("🃏 {} matched no terms", expansion.pattern);
+            continue;
+        }
+        let more = if expansion.dropped > 0 {
+            format!(" (+{} more terms dropped)", expansion.dropped)
+        } else {
+            String::new()
+        };
+        let variants: Vec<String> = expansion
+            .terms
+            .iter()
+            .map(|term| {
+                if term.weight < 1.0 {
+                    format!("{} (x{:.2})", term.token, term.weight)
+                } else {
+                    term.token.clone()
+                }
+            })
+            .collect();
+        println!(
+            "🃏 {} -> {}{}",
+            expansion.pattern,
+            variants.join(" | "),
+            more
+        );
+    }
+}
+
+pub fn display_weighted_query(label: &str, query: &Query) {
+    let terms: Vec<String> = query
+        .terms
+        .iter()
+        .map(|term| format!("{}({:.3})", term.token, term.weight))
+        .collect();
+    println!("{}: {}", label, terms.join(" "));
+}
+
 pub fn extract_title_from_content(content: &str) -> String {
     let mut lines = content.lines();
 
@@ -90,3 +396,52 @@ pub fn extract_title_from_content(content: &str) -> String {
     }
     "Title Missing".to_string()
 }
+
+/// Quotes `text` as a JSON string literal.
+pub fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preprocessing::tokenizer::AnalyzerStep;
+
+    #[test]
+    fn title_length_covers_every_title_line() {
+        let content = ".I 1\n.T\nflow over\nswept wings\n.A\nsmith\n.B\njournal\n.W\nthe wings stall\n";
+        let analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        let (positions, doc_length, title_length) = calculate_document_positions(content, &analyzer);
+
+        assert_eq!(title_length, 4);
+        assert_eq!(doc_length, 7);
+        assert_eq!(positions["wings"], vec![3, 5]);
+        assert!(!positions.contains_key("smith"));
+        assert_eq!(extract_title_from_content(content), "flow over");
+    }
+
+    #[test]
+    fn the_title_opens_the_token_stream() {
+        let content = ".I 2\n.W\nbody text\n.T\nlate title\n";
+        let analyzer = Analyzer::new(vec![AnalyzerStep::Lowercase]);
+        let (positions, doc_length, title_length) = calculate_document_positions(content, &analyzer);
+
+        assert_eq!((doc_length, title_length), (4, 2));
+        assert_eq!(positions["late"], vec![0]);
+        assert_eq!(positions["body"], vec![2]);
+    }
+}